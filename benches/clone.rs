@@ -0,0 +1,86 @@
+//! Compares layering request-local data over a shared `Context` with the deep copy of its
+//! entries that cloning used to perform, down to updates of one leaf of a large nested map.
+//!
+//! Run with `cargo bench --bench clone`.
+
+use oxidex::Context;
+use serde_value::Value;
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 2_000;
+
+/// Builds a global context with a few thousand values.
+fn sample() -> Context {
+    let mut context = Context::new();
+    for service in 0..200 {
+        for key in ["host", "port", "timeout", "retries", "enabled"] {
+            context
+                .set_path(
+                    &format!("services.s{}.{}", service, key),
+                    Value::String(format!("{}-{}", key, service)),
+                )
+                .unwrap();
+        }
+        context
+            .set_path(
+                &format!("features.f{}", service),
+                Value::Bool(service % 2 == 0),
+            )
+            .unwrap();
+    }
+    context
+}
+
+/// Returns the average duration of `f` over `ITERATIONS` runs.
+fn measure<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let global = sample();
+    let entries: BTreeMap<String, Value> = global.clone().into();
+
+    let deep = measure(|| {
+        black_box(entries.clone());
+    });
+    let shared = measure(|| {
+        black_box(global.clone());
+    });
+    let layered = measure(|| {
+        let mut request = global.clone();
+        request
+            .set_path("request.id", Value::String("abc".to_string()))
+            .unwrap();
+        black_box(request);
+    });
+    let touched = measure(|| {
+        let mut request = global.clone();
+        request.set_path("features.f7", Value::Bool(true)).unwrap();
+        black_box(request);
+    });
+    let nested = measure(|| {
+        let mut request = global.clone();
+        request
+            .set_path("services.s7.port", Value::U64(8080))
+            .unwrap();
+        black_box(request);
+    });
+
+    println!("{:<40} {:>12?}", "deep copy of the entries", deep);
+    println!("{:<40} {:>12?}", "Context::clone", shared);
+    println!("{:<40} {:>12?}", "clone + new top-level key", layered);
+    println!(
+        "{:<40} {:>12?}",
+        "clone + update inside a top-level map", touched
+    );
+    println!(
+        "{:<40} {:>12?}",
+        "clone + update a leaf of a nested map", nested
+    );
+}
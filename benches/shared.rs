@@ -0,0 +1,122 @@
+//! Compares the read throughput of `SharedContext` and `RwLock<Context>` while a writer keeps
+//! updating the context.
+//!
+//! Run with `cargo bench --bench shared`.
+
+use oxidex::{Context, SharedContext};
+use serde_value::Value;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const READERS: usize = 4;
+const DURATION: Duration = Duration::from_millis(500);
+
+/// Builds a context with a few hundred values, so that writers have real work to do.
+fn sample() -> Context {
+    let mut context = Context::new();
+    for service in 0..50 {
+        for key in ["host", "port", "timeout", "retries", "enabled"] {
+            context
+                .set_path(
+                    &format!("services.s{}.{}", service, key),
+                    Value::String(format!("{}-{}", key, service)),
+                )
+                .unwrap();
+        }
+    }
+    context
+}
+
+/// Runs `READERS` reader threads and one writer thread for `DURATION`, returning the number of
+/// reads and writes completed.
+fn run<R, W>(read: R, write: W) -> (u64, u64)
+where
+    R: Fn() + Send + Sync + 'static,
+    W: Fn(u64) + Send + Sync + 'static,
+{
+    let read = Arc::new(read);
+    let stop = Arc::new(AtomicBool::new(false));
+    let reads = Arc::new(AtomicU64::new(0));
+    let mut threads = Vec::new();
+    for _ in 0..READERS {
+        let (read, stop, reads) = (read.clone(), stop.clone(), reads.clone());
+        threads.push(thread::spawn(move || {
+            let mut count = 0;
+            while !stop.load(Ordering::Relaxed) {
+                read();
+                count += 1;
+            }
+            reads.fetch_add(count, Ordering::Relaxed);
+        }));
+    }
+    let writes = {
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let mut count = 0;
+            while !stop.load(Ordering::Relaxed) {
+                write(count);
+                count += 1;
+            }
+            count
+        })
+    };
+    let start = Instant::now();
+    while start.elapsed() < DURATION {
+        thread::sleep(Duration::from_millis(10));
+    }
+    stop.store(true, Ordering::Relaxed);
+    threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    (reads.load(Ordering::Relaxed), writes.join().unwrap())
+}
+
+fn report(name: &str, (reads, writes): (u64, u64)) {
+    let seconds = DURATION.as_secs_f64();
+    println!(
+        "{:<20} {:>12.0} reads/s {:>10.0} writes/s",
+        name,
+        reads as f64 / seconds,
+        writes as f64 / seconds
+    );
+}
+
+fn main() {
+    let locked = Arc::new(RwLock::new(sample()));
+    let (reader, writer) = (locked.clone(), locked);
+    report(
+        "RwLock<Context>",
+        run(
+            move || {
+                black_box(reader.read().unwrap().get_path("services.s7.port"));
+            },
+            move |i| {
+                let mut context = writer.write().unwrap();
+                context
+                    .set_path("services.s7.retries", Value::U64(i))
+                    .unwrap();
+            },
+        ),
+    );
+
+    let shared = Arc::new(SharedContext::new(sample()));
+    let (reader, writer) = (shared.clone(), shared);
+    report(
+        "SharedContext",
+        run(
+            move || {
+                black_box(reader.snapshot().get_path("services.s7.port"));
+            },
+            move |i| {
+                writer.transaction(|context| {
+                    context
+                        .set_path("services.s7.retries", Value::U64(i))
+                        .unwrap();
+                });
+            },
+        ),
+    );
+}
@@ -0,0 +1,180 @@
+use crate::value::parse_scalar;
+use crate::{Context, Error};
+
+/// Command-line overrides, ready to be flattened into a `clap` parser.
+///
+/// Available if the "clap" feature is enabled, it adds a repeatable `--set PATH=VALUE` option to
+/// any binary.
+///
+/// # Example
+/// ```rust
+/// use clap::Parser;
+///
+/// #[derive(Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     overrides: oxidex::Args,
+/// }
+///
+/// let cli = Cli::parse_from(["app", "--set", "db.port=5432", "--set", "features[0]=a"]);
+/// let overrides = cli.overrides.to_context().unwrap();
+/// assert_eq!(overrides.get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+/// ```
+#[cfg(feature = "clap")]
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct Args {
+    /// Overrides a context value (e.g. `db.host=localhost`); may be repeated.
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    pub set: Vec<String>,
+}
+
+#[cfg(feature = "clap")]
+impl Args {
+    /// Builds a `Context` from the collected overrides, as [`Context::from_args`] does.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Args` variant if an override is not of the form `PATH=VALUE`.
+    /// - Returns an `Error::Path` variant if a path is invalid.
+    pub fn to_context(&self) -> crate::Result<Context> {
+        let mut context = Context::new();
+        self.apply_to(&mut context)?;
+        Ok(context)
+    }
+
+    /// Applies the collected overrides onto `context`, as [`Context::apply_args`] does.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Args` variant if an override is not of the form `PATH=VALUE`.
+    /// - Returns an `Error::Path` variant if a path is invalid.
+    pub fn apply_to(&self, context: &mut Context) -> crate::Result<()> {
+        for assignment in &self.set {
+            context.set_assignment(assignment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Creates a `Context` from `--set PATH=VALUE` command-line overrides.
+    ///
+    /// Both `--set PATH=VALUE` and `--set=PATH=VALUE` are accepted; other arguments are ignored,
+    /// so the whole command line can be given. Values are read as YAML/JSON scalars: `null`,
+    /// booleans and numbers are typed, quoted strings are unquoted, anything else is a string.
+    /// The result is meant to be [merged](Context::merge) over a file-based configuration.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Args` variant if `--set` has no value or the value has no `=`.
+    /// - Returns an `Error::Path` variant if a path is invalid.
+    ///
+    /// # Example
+    /// ```rust
+    /// let args = ["--set", "db.host=x", "--set=db.port=5432", "--set", "features[0]=\"a\"", "-v"];
+    /// let context = oxidex::Context::from_args(args).unwrap();
+    ///
+    /// assert_eq!(context.get_path("db.host").unwrap(), &serde_value::Value::String("x".to_string()));
+    /// assert_eq!(context.get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+    /// assert_eq!(context.get_path("features[0]").unwrap(), &serde_value::Value::String("a".to_string()));
+    /// ```
+    pub fn from_args<I, S>(args: I) -> crate::Result<Context>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut context = Context::new();
+        context.apply_args(args)?;
+        Ok(context)
+    }
+
+    /// Applies `--set PATH=VALUE` command-line overrides directly onto this `Context`.
+    ///
+    /// Arguments are read as in [`Context::from_args`], but each value is set in place with
+    /// [`Context::set_path`], so `--set features[1]=b` only replaces the second item of an
+    /// existing sequence.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Args` variant if `--set` has no value or the value has no `=`.
+    /// - Returns an `Error::Path` variant if a path is invalid. Overrides preceding the faulty
+    ///   one are kept.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut context = oxidex::Context::from_args(["--set", "features[0]=a", "--set", "features[1]=b"]).unwrap();
+    /// context.apply_args(["--set", "features[1]=c"]).unwrap();
+    ///
+    /// assert_eq!(context.get_path("features[0]").unwrap(), &serde_value::Value::String("a".to_string()));
+    /// assert_eq!(context.get_path("features[1]").unwrap(), &serde_value::Value::String("c".to_string()));
+    /// ```
+    pub fn apply_args<I, S>(&mut self, args: I) -> crate::Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.audited(|context| {
+            let mut args = args.into_iter();
+            while let Some(arg) = args.next() {
+                let arg = arg.as_ref();
+                if let Some(assignment) = arg.strip_prefix("--set=") {
+                    context.set_assignment(assignment)?;
+                } else if arg == "--set" {
+                    let assignment = args
+                        .next()
+                        .ok_or_else(|| Error::Args("missing value after '--set'".to_string()))?;
+                    context.set_assignment(assignment.as_ref())?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies a single `PATH=VALUE` override.
+    fn set_assignment(&mut self, assignment: &str) -> crate::Result<()> {
+        let (path, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| Error::Args(format!("expected PATH=VALUE, got '{}'", assignment)))?;
+        self.set_path(path.trim(), parse_scalar(value))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_value::Value;
+
+    #[test]
+    fn test_scalars() {
+        let context = Context::from_args([
+            "--set=a=~",
+            "--set=b=TRUE",
+            "--set=c=-3",
+            "--set=d=1.5",
+            "--set=e='it''s'",
+            "--set=f=\"tab\\there\"",
+            "--set=g=a=b",
+        ])
+        .unwrap();
+        assert_eq!(context.get("a"), Some(&Value::Unit));
+        assert_eq!(context.get("b"), Some(&Value::Bool(true)));
+        assert_eq!(context.get("c"), Some(&Value::I64(-3)));
+        assert_eq!(context.get("d"), Some(&Value::F64(1.5)));
+        assert_eq!(context.get("e"), Some(&Value::String("it's".to_string())));
+        assert_eq!(
+            context.get("f"),
+            Some(&Value::String("tab\there".to_string()))
+        );
+        assert_eq!(context.get("g"), Some(&Value::String("a=b".to_string())));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(Context::from_args(["--set"]), Err(Error::Args(_))));
+        assert!(matches!(
+            Context::from_args(["--set", "novalue"]),
+            Err(Error::Args(_))
+        ));
+        assert!(matches!(
+            Context::from_args(["--set", "a..b=1"]),
+            Err(Error::Path(_))
+        ));
+    }
+}
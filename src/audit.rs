@@ -0,0 +1,378 @@
+use crate::diff::Change;
+use crate::path::Path;
+use crate::Context;
+use serde::{Serialize, Serializer};
+use serde_value::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of timestamps for audit entries.
+///
+/// Any `Fn() -> SystemTime` closure is a clock, which makes it easy to inject a fixed or simulated
+/// time in tests.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+impl<F: Fn() -> SystemTime + Send + Sync> Clock for F {
+    fn now(&self) -> SystemTime {
+        self()
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with milliseconds.
+fn rfc3339(time: &SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (days, seconds) = (elapsed.as_secs() / 86_400, elapsed.as_secs() % 86_400);
+    // Civil date from a day count (Howard Hinnant's algorithm), for dates after 1970.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+fn serialize_timestamp<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(time))
+}
+
+/// A recorded mutation of an audited `Context`.
+///
+/// Secret values (see [`Context::mark_secret`]) are recorded as `"[REDACTED]"`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    /// When the mutation happened, serialized as an RFC 3339 UTC timestamp.
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub timestamp: SystemTime,
+    /// The actor of the log when the mutation happened.
+    pub actor: String,
+    /// The location of the changed value.
+    pub path: Path,
+    /// The value before the mutation, or `None` if it was added.
+    pub old: Option<Value>,
+    /// The value after the mutation, or `None` if it was removed.
+    pub new: Option<Value>,
+}
+
+struct State {
+    actor: String,
+    clock: Arc<dyn Clock>,
+    entries: Vec<AuditEntry>,
+}
+
+/// The audit log of one or more contexts, attached with [`Context::attach_audit`].
+///
+/// An `AuditLog` is a handle: its clones share the same entries, so one log can be attached to
+/// several contexts. A clone of an audited `Context` starts without a log. Every mutation of an audited `Context` (`insert`, `extend`, `set_path`, merges,
+/// patches, transforms, ...) records one entry per changed value, stamped with the current actor
+/// and the time given by the log's clock.
+///
+/// Example:
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let log = oxidex::AuditLog::with_clock("deploy-bot", || UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+/// let mut context = oxidex::Context::from_args(["--set", "db.port=5432"]).unwrap();
+/// context.attach_audit(log.clone());
+///
+/// context.set_path("db.port", serde_value::Value::U64(6432)).unwrap();
+/// log.set_actor("alice");
+/// context.insert("debug".to_string(), serde_value::Value::Bool(true));
+///
+/// let entries = log.entries();
+/// assert_eq!(entries.len(), 2);
+/// assert_eq!(entries[0].path.to_string(), "db.port");
+/// assert_eq!(entries[0].old, Some(serde_value::Value::U64(5432)));
+/// assert_eq!(entries[1].actor, "alice");
+/// # #[cfg(feature = "json")] {
+/// assert_eq!(
+///     log.to_json_lines().unwrap().lines().next().unwrap(),
+///     r#"{"timestamp":"2023-11-14T22:13:20.000Z","actor":"deploy-bot","path":"db.port","old":5432,"new":6432}"#
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AuditLog {
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("AuditLog")
+            .field("actor", &state.actor)
+            .field("entries", &state.entries.len())
+            .finish()
+    }
+}
+
+impl AuditLog {
+    /// Creates an empty log timestamped by the system clock.
+    ///
+    /// `actor`: The label of the actor performing the next mutations.
+    pub fn new(actor: &str) -> AuditLog {
+        AuditLog::with_clock(actor, SystemClock)
+    }
+
+    /// Creates an empty log timestamped by a custom clock.
+    ///
+    /// `actor`: The label of the actor performing the next mutations.
+    /// `clock`: The source of timestamps.
+    pub fn with_clock<C: Clock + 'static>(actor: &str, clock: C) -> AuditLog {
+        AuditLog {
+            state: Arc::new(Mutex::new(State {
+                actor: actor.to_string(),
+                clock: Arc::new(clock),
+                entries: Vec::new(),
+            })),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        // Entries are pushed whole, so a poisoned log is still consistent.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sets the label of the actor performing the next mutations.
+    pub fn set_actor(&self, actor: &str) {
+        self.state().actor = actor.to_string();
+    }
+
+    /// Returns the label of the actor performing the next mutations.
+    pub fn actor(&self) -> String {
+        self.state().actor.clone()
+    }
+
+    /// Returns the recorded entries, oldest first.
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.state().entries.clone()
+    }
+
+    /// Removes and returns the recorded entries, oldest first, for instance to ship them.
+    pub fn take_entries(&self) -> Vec<AuditEntry> {
+        std::mem::take(&mut self.state().entries)
+    }
+
+    /// Returns the number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    /// Returns `true` if no entry was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.state().entries.is_empty()
+    }
+
+    /// Serializes the recorded entries as JSON Lines, one JSON object per entry.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Json` variant if serialization fails.
+    #[cfg(feature = "json")]
+    pub fn to_json_lines(&self) -> crate::Result<String> {
+        let mut lines = String::new();
+        for entry in &self.state().entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        Ok(lines)
+    }
+
+    /// Records the changes between `old` and `new`, with the values marked secret in either of
+    /// them redacted: a rollback can drop the mark of a value it removes.
+    fn record(&self, old: &Context, new: &Context) {
+        let diff = old.diff(new);
+        if diff.is_empty() {
+            return;
+        }
+        let mut state = self.state();
+        let timestamp = state.clock.now();
+        let redact = |path: &Path, value: Value| {
+            [old, new].into_iter().fold(value, |value, context| {
+                match context.is_secret(&path.to_string()) {
+                    true => Value::String(crate::secret::REDACTED.to_string()),
+                    false => context.redact_below(path, value),
+                }
+            })
+        };
+        for change in diff {
+            let (path, old, new) = match change {
+                Change::Added { path, value } => (path, None, Some(value)),
+                Change::Removed { path, value } => (path, Some(value), None),
+                Change::Changed { path, old, new } => (path, Some(old), Some(new)),
+            };
+            let entry = AuditEntry {
+                timestamp,
+                actor: state.actor.clone(),
+                old: old.map(|value| redact(&path, value)),
+                new: new.map(|value| redact(&path, value)),
+                path,
+            };
+            state.entries.push(entry);
+        }
+    }
+}
+
+impl Context {
+    /// Attaches an audit log recording every later mutation of the `Context`.
+    ///
+    /// Existing call sites do not change: `insert`, `extend`, `set_path` and the other mutating
+    /// methods record their changes on their own. Clones of the `Context` are not audited unless
+    /// a log is attached to them as well.
+    ///
+    /// `log`: The log to write to; keep a clone of it to read the entries.
+    pub fn attach_audit(&mut self, log: AuditLog) {
+        self.audit = Some(log);
+    }
+
+    /// Detaches the audit log, returning it.
+    pub fn detach_audit(&mut self) -> Option<AuditLog> {
+        self.audit.take()
+    }
+
+    /// Returns the attached audit log, if any.
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// Runs a mutation, recording its changes in the attached audit log.
+    ///
+    /// The log is detached while `f` runs, so that mutations built on other ones are recorded once.
+    pub(crate) fn audited<T, F>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Context) -> T,
+    {
+        let Some(log) = self.audit.take() else {
+            return f(self);
+        };
+        let old = self.clone();
+        let result = f(self);
+        log.record(&old, self);
+        self.audit = Some(log);
+        result
+    }
+
+    /// Runs a fallible mutation like [`Context::audited`], restoring the `Context` if it fails:
+    /// nothing is recorded then.
+    pub(crate) fn try_audited<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Context) -> Result<T, E>,
+    {
+        let log = self.audit.take();
+        let old = self.clone();
+        let result = f(self);
+        match (&result, &log) {
+            (Ok(_), Some(log)) => log.record(&old, self),
+            (Ok(_), None) => {}
+            (Err(_), _) => *self = old,
+        }
+        self.audit = log;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_nested_mutations_and_secrets_are_recorded_once() {
+        let log = AuditLog::with_clock("ops", || {
+            UNIX_EPOCH + Duration::from_millis(951_782_400_123)
+        });
+        let mut context = Context::new();
+        context.attach_audit(log.clone());
+        context
+            .set_secret("db.password", Value::String("hunter2".to_string()))
+            .unwrap();
+        context.merge(Context::from_args(["--set", "db.user=admin"]).unwrap());
+        assert!(context.set_path("db.password.x", Value::Unit).is_err());
+
+        let entries = log.take_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path.to_string(), "db");
+        assert_eq!(
+            entries[0].new,
+            Some(Value::Map(
+                [(
+                    Value::String("password".to_string()),
+                    Value::String("[REDACTED]".to_string())
+                )]
+                .into()
+            ))
+        );
+        assert_eq!(entries[1].path.to_string(), "db.user");
+        assert_eq!(rfc3339(&entries[1].timestamp), "2000-02-29T00:00:00.123Z");
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_clones_are_not_audited() {
+        let log = AuditLog::new("ops");
+        let mut context = Context::new();
+        context.attach_audit(log.clone());
+        let mut request = context.clone();
+        request
+            .set_path("request.id", Value::String("42".to_string()))
+            .unwrap();
+        assert!(request.audit_log().is_none());
+        assert!(log.is_empty());
+
+        request.attach_audit(log.clone());
+        request.insert("debug".to_string(), Value::Bool(true));
+        assert_eq!(log.len(), 1);
+    }
+
+    #[test]
+    fn test_rollback_redacts_removed_secrets() {
+        let log = AuditLog::new("ops");
+        let mut context = Context::new();
+        context.attach_audit(log.clone());
+        let checkpoint = context.checkpoint();
+        context
+            .set_secret("db.password", Value::String("hunter2".to_string()))
+            .unwrap();
+        context.rollback_to(&checkpoint);
+
+        let entries = log.take_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].path.to_string(), "db");
+        assert_eq!(entries[1].new, None);
+        assert_eq!(
+            entries[1].old,
+            Some(Value::Map(
+                [(
+                    Value::String("password".to_string()),
+                    Value::String("[REDACTED]".to_string())
+                )]
+                .into()
+            ))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! `oxidex` command-line tool: converts, queries and combines contexts stored as JSON, TOML or YAML.
+//!
+//! Every command reads its inputs from files, or from the standard input when `-` is given, and
+//! writes its result to the standard output.
+//!
+//! ```text
+//! oxidex convert in.yaml --to toml
+//! oxidex get config.toml db.host
+//! oxidex set config.toml --set db.port=5432 --in-place
+//! oxidex merge base.yaml overrides.json --to yaml
+//! oxidex diff old.toml new.yaml --json
+//! ```
+
+use clap::{Parser, Subcommand, ValueEnum};
+use oxidex::{Context, Error};
+use serde_value::Value;
+use std::io::{Read, Write};
+use std::process::ExitCode;
+
+/// Converts, queries and combines contexts stored as JSON, TOML or YAML.
+#[derive(Parser, Debug)]
+#[command(name = "oxidex", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Converts a context from one format to another.
+    Convert {
+        /// The input file, or `-` for the standard input.
+        input: String,
+        #[command(flatten)]
+        formats: Formats,
+    },
+    /// Prints the value found at a dotted path (e.g. `db.hosts[0]`).
+    Get {
+        /// The input file, or `-` for the standard input.
+        input: String,
+        /// The path of the value to print.
+        path: String,
+        #[command(flatten)]
+        formats: Formats,
+    },
+    /// Sets values at dotted paths and prints the resulting context.
+    Set {
+        /// The input file, or `-` for the standard input.
+        input: String,
+        #[command(flatten)]
+        overrides: oxidex::Args,
+        /// Writes the result back to the input file instead of the standard output.
+        #[arg(short, long)]
+        in_place: bool,
+        #[command(flatten)]
+        formats: Formats,
+    },
+    /// Deeply merges contexts, the last one taking precedence.
+    Merge {
+        /// The base file, or `-` for the standard input; its format is the default output format.
+        base: String,
+        /// The files merged over the base, in order.
+        overrides: Vec<String>,
+        #[command(flatten)]
+        formats: Formats,
+    },
+    /// Shows the structural differences between two contexts.
+    Diff {
+        /// The old version, or `-` for the standard input.
+        old: String,
+        /// The new version, or `-` for the standard input.
+        new: String,
+        /// Prints the differences as a JSON array instead of a unified diff.
+        #[arg(long)]
+        json: bool,
+        /// The input format; guessed from the file extensions when omitted.
+        #[arg(long, value_enum)]
+        from: Option<Format>,
+    },
+}
+
+/// Options selecting the input and output formats.
+#[derive(clap::Args, Debug)]
+struct Formats {
+    /// The input format; guessed from the file extension when omitted.
+    #[arg(long, value_enum)]
+    from: Option<Format>,
+    /// The output format; defaults to the format of the (first) input.
+    #[arg(long, value_enum)]
+    to: Option<Format>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    fn guess(input: &str) -> oxidex::Result<Format> {
+        match std::path::Path::new(input)
+            .extension()
+            .and_then(|e| e.to_str())
+        {
+            Some("json") => Ok(Format::Json),
+            Some("toml") => Ok(Format::Toml),
+            Some("yaml") | Some("yml") => Ok(Format::Yaml),
+            _ => Err(Error::Args(format!(
+                "cannot guess the format of '{}', use --from",
+                input
+            ))),
+        }
+    }
+
+    fn parse(&self, data: &str) -> oxidex::Result<Context> {
+        match self {
+            Format::Json => Context::from_json(data),
+            Format::Toml => Context::from_toml(data),
+            Format::Yaml => Context::from_yaml(data),
+        }
+    }
+
+    fn export(&self, context: &Context) -> oxidex::Result<String> {
+        match self {
+            Format::Json => context.to_json(true).map(|json| json + "\n"),
+            Format::Toml => context.to_toml(true),
+            Format::Yaml => context.to_yaml(),
+        }
+    }
+
+    /// Renders a single value: scalars are printed as plain text, structures in this format.
+    fn render(&self, value: &Value) -> oxidex::Result<String> {
+        match value {
+            Value::String(v) => Ok(format!("{}\n", v)),
+            Value::Map(_) | Value::Seq(_) | Value::Option(Some(_)) | Value::Newtype(_) => {
+                match self {
+                    Format::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+                    Format::Toml => Ok(toml::to_string_pretty(value)?),
+                    Format::Yaml => Ok(serde_yaml::to_string(value)?),
+                }
+            }
+            _ => Ok(serde_json::to_string(value)? + "\n"),
+        }
+    }
+}
+
+/// Reads and parses an input, returning the context and the format it was read with.
+fn load(input: &str, from: Option<Format>) -> oxidex::Result<(Context, Format)> {
+    let format = match from {
+        Some(format) => format,
+        None if input == "-" => {
+            return Err(Error::Args(
+                "--from is required when reading the standard input".to_string(),
+            ))
+        }
+        None => Format::guess(input)?,
+    };
+    let mut data = String::new();
+    match input {
+        "-" => std::io::stdin().read_to_string(&mut data)?,
+        path => std::fs::File::open(path)?.read_to_string(&mut data)?,
+    };
+    Ok((format.parse(&data)?, format))
+}
+
+fn run(cli: Cli) -> oxidex::Result<()> {
+    let output = match cli.command {
+        Command::Convert { input, formats } => {
+            let (context, format) = load(&input, formats.from)?;
+            formats.to.unwrap_or(format).export(&context)?
+        }
+        Command::Get {
+            input,
+            path,
+            formats,
+        } => {
+            let (context, format) = load(&input, formats.from)?;
+            let value = context
+                .get_path(&path)
+                .ok_or_else(|| Error::Path(format!("no value at '{}'", path)))?;
+            formats.to.unwrap_or(format).render(value)?
+        }
+        Command::Set {
+            input,
+            overrides,
+            in_place,
+            formats,
+        } => {
+            let (mut context, format) = load(&input, formats.from)?;
+            overrides.apply_to(&mut context)?;
+            let output = formats.to.unwrap_or(format).export(&context)?;
+            if in_place {
+                if input == "-" {
+                    return Err(Error::Args(
+                        "--in-place cannot be used with the standard input".to_string(),
+                    ));
+                }
+                return Ok(std::fs::write(&input, output)?);
+            }
+            output
+        }
+        Command::Merge {
+            base,
+            overrides,
+            formats,
+        } => {
+            let (mut context, format) = load(&base, formats.from)?;
+            for input in &overrides {
+                context.merge(load(input, formats.from)?.0);
+            }
+            formats.to.unwrap_or(format).export(&context)?
+        }
+        Command::Diff {
+            old,
+            new,
+            json,
+            from,
+        } => {
+            let diff = load(&old, from)?.0.diff(&load(&new, from)?.0);
+            match json {
+                true => diff.to_json(true)? + "\n",
+                false => diff.to_string(),
+            }
+        }
+    };
+    Ok(std::io::stdout().write_all(output.as_bytes())?)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("oxidex: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::value::{as_integer, to_inline, unwrap};
+use serde_value::Value;
+use std::fmt::Write;
+
+/// Writes a string as a JSON string literal.
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes the canonical form of a value: compact JSON with keys sorted by their UTF-8 bytes,
+/// integers written without a fractional part and floats with one.
+///
+/// Returns `None` if the value holds a NaN or infinite float, which JSON cannot represent.
+pub(crate) fn write_canonical(out: &mut String, value: &Value) -> Option<()> {
+    match unwrap(value) {
+        Value::Unit | Value::Option(None) => out.push_str("null"),
+        Value::Bool(v) => out.push_str(&v.to_string()),
+        Value::String(v) => write_string(out, v),
+        Value::Char(v) => write_string(out, &v.to_string()),
+        // Formats reload every float as an f64, so an f32 is widened from its shortest decimal form.
+        Value::F32(v) => write_float(out, v.to_string().parse().unwrap_or(f64::NAN))?,
+        Value::F64(v) => write_float(out, *v)?,
+        Value::Bytes(bytes) => {
+            let items: Vec<Value> = bytes.iter().map(|byte| Value::U8(*byte)).collect();
+            write_canonical(out, &Value::Seq(items))?;
+        }
+        Value::Seq(seq) => {
+            out.push('[');
+            for (index, item) in seq.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item)?;
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            let mut entries: Vec<(String, &Value)> = map
+                .iter()
+                .map(|(key, value)| match unwrap(key) {
+                    Value::String(key) => (key.clone(), value),
+                    key => (to_inline(key), value),
+                })
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_string(out, &key);
+                out.push(':');
+                write_canonical(out, value)?;
+            }
+            out.push('}');
+        }
+        value => out.push_str(&as_integer(value).unwrap_or_default().to_string()),
+    }
+    Some(())
+}
+
+fn write_float(out: &mut String, v: f64) -> Option<()> {
+    v.is_finite().then(|| {
+        let _ = write!(out, "{:?}", v);
+    })
+}
@@ -0,0 +1,338 @@
+use crate::flatten::{flatten_value, unflatten_value};
+use crate::path::{self, Path};
+use crate::value::scalar_to_string;
+use crate::{Context, Error, FlattenOptions, IndexStyle};
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+impl From<csv::Error> for Error {
+    /// Converts a `csv::Error` (CSV reading/writing error) into the custom `Error` type.
+    ///
+    /// This allows automatic conversion of `csv::Error` into `Error::Csv(String)`,
+    /// making it easier to use the `?` operator in functions that return `Result<T, Error>`.
+    ///
+    /// # Example
+    /// ```rust
+    /// fn count_records(csv_str: &str) -> oxidex::Result<usize> {
+    ///     let mut count = 0;
+    ///     for record in csv::Reader::from_reader(csv_str.as_bytes()).records() {
+    ///         record?; // `?` converts csv::Error into Error::Csv
+    ///         count += 1;
+    ///     }
+    ///     Ok(count)
+    /// }
+    ///
+    /// let result = count_records("name,age\nAlice,30\nBob");
+    /// assert!(matches!(result, Err(oxidex::Error::Csv(_))));
+    /// ```
+    fn from(err: csv::Error) -> Self {
+        Error::Csv(err.to_string())
+    }
+}
+
+/// Options controlling how a `Context` is exported to and imported from CSV.
+///
+/// The default options describe a comma separated file with a header row. Use [`CsvOptions::tsv`]
+/// for tab separated files.
+///
+/// # Example
+/// ```rust
+/// let options = oxidex::CsvOptions {
+///     columns: Some(vec!["name".to_string(), "address.city".to_string()]),
+///     ..oxidex::CsvOptions::tsv()
+/// };
+/// assert_eq!(options.delimiter, b'\t');
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// The field delimiter (`,` by default).
+    pub delimiter: u8,
+    /// The quote character (`"` by default).
+    pub quote: u8,
+    /// If `true`, every field is quoted on export; otherwise only fields that need it are.
+    pub always_quote: bool,
+    /// If `true`, the first row holds the column names (on export, a header row is written).
+    pub has_headers: bool,
+    /// The columns to export or import, in order. All columns are used when `None`.
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            always_quote: false,
+            has_headers: true,
+            columns: None,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Creates options for tab separated values, keeping all other defaults.
+    pub fn tsv() -> CsvOptions {
+        CsvOptions {
+            delimiter: b'\t',
+            ..CsvOptions::default()
+        }
+    }
+}
+
+/// Infers a typed value from a CSV field: booleans, integers and floats are detected, empty
+/// fields become `Value::Unit` and everything else stays a string.
+fn infer(field: &str) -> Value {
+    if field.is_empty() {
+        return Value::Unit;
+    }
+    if field.eq_ignore_ascii_case("true") || field.eq_ignore_ascii_case("false") {
+        return Value::Bool(field.eq_ignore_ascii_case("true"));
+    }
+    // Leading zeros are significant (zip codes, identifiers), so such fields are kept verbatim.
+    let digits = field.trim_start_matches(['-', '+']);
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return Value::String(field.to_string());
+    }
+    if let Ok(v) = field.parse::<u64>() {
+        return Value::U64(v);
+    }
+    if let Ok(v) = field.parse::<i64>() {
+        return Value::I64(v);
+    }
+    match field.parse::<f64>() {
+        Ok(v) if field.bytes().any(|b| b.is_ascii_digit()) => Value::F64(v),
+        _ => Value::String(field.to_string()),
+    }
+}
+
+impl Context {
+    /// Serializes the sequence of records found at `path` into a CSV string.
+    ///
+    /// Each record must be a map; nested maps and sequences are flattened into dotted column
+    /// names (`address.city`, `tags.0`). Unless `options.columns` is set, the columns are the
+    /// union of all record columns, in order of first appearance.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if `path` does not lead to any value.
+    /// - Returns an `Error::Csv` variant if the value is not a sequence of maps or if writing fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "json")] {
+    /// let json = r#"{"users": [{"name": "Alice", "address": {"city": "Paris"}}, {"name": "Bob", "age": 30}]}"#;
+    /// let context = oxidex::Context::from_json(json).unwrap();
+    ///
+    /// let csv = context.to_csv("users", &oxidex::CsvOptions::default()).unwrap();
+    /// assert_eq!(csv, "address.city,name,age\nParis,Alice,\n,Bob,30\n");
+    /// # }
+    /// ```
+    pub fn to_csv(&self, path: &str, options: &crate::CsvOptions) -> crate::Result<String> {
+        let found = path.parse::<Path>().ok();
+        let Some(node) = found.and_then(|found| path::find_in(&self.inner, found.segments()))
+        else {
+            return Err(Error::Path(format!("no value at '{}'", path)));
+        };
+        let value = node.as_value();
+        let Value::Seq(records) = &*value else {
+            return Err(Error::Csv(format!("value at '{}' is not a sequence", path)));
+        };
+        let mut rows = Vec::with_capacity(records.len());
+        let mut columns: Vec<String> = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            if !matches!(record, Value::Map(_)) {
+                return Err(Error::Csv(format!(
+                    "record {} at '{}' is not a map",
+                    index, path
+                )));
+            }
+            let mut fields = Vec::new();
+            flatten_value(
+                &mut Vec::new(),
+                record,
+                &FlattenOptions::default(),
+                &mut fields,
+            );
+            for (column, _) in &fields {
+                if !columns.contains(column) {
+                    columns.push(column.clone());
+                }
+            }
+            rows.push(
+                fields
+                    .into_iter()
+                    .map(|(column, field)| (column, scalar_to_string(&field)))
+                    .collect::<BTreeMap<String, String>>(),
+            );
+        }
+        let columns = options.columns.clone().unwrap_or(columns);
+
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .quote(options.quote)
+            .quote_style(match options.always_quote {
+                true => csv::QuoteStyle::Always,
+                false => csv::QuoteStyle::Necessary,
+            })
+            .from_writer(Vec::new());
+        if options.has_headers {
+            writer.write_record(&columns)?;
+        }
+        for row in rows {
+            writer.write_record(
+                columns
+                    .iter()
+                    .map(|column| row.get(column).map_or("", String::as_str)),
+            )?;
+        }
+        let data = writer
+            .into_inner()
+            .map_err(|err| Error::Csv(err.to_string()))?;
+        String::from_utf8(data).map_err(|err| Error::Csv(err.to_string()))
+    }
+
+    /// Serializes the sequence of records found at `path` into a CSV string, with secret values
+    /// masked (see [`Context::mark_secret`]).
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if `path` does not lead to any value.
+    /// - Returns an `Error::Csv` variant if the value is not a sequence of maps or if writing fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// let csv = "name,password\nAlice,hunter2\n";
+    /// let mut context = oxidex::Context::from_csv(csv, "users", &oxidex::CsvOptions::default()).unwrap();
+    /// context.mark_secret_keys("password");
+    ///
+    /// let csv = context.to_csv_redacted("users", &oxidex::CsvOptions::default()).unwrap();
+    /// assert_eq!(csv, "name,password\nAlice,[REDACTED]\n");
+    /// ```
+    pub fn to_csv_redacted(
+        &self,
+        path: &str,
+        options: &crate::CsvOptions,
+    ) -> crate::Result<String> {
+        self.redacted().to_csv(path, options)
+    }
+
+    /// Creates a `Context` holding the records of a CSV string as a sequence stored at `path`.
+    ///
+    /// Dotted column names are expanded back into nested maps, and fields are typed: booleans,
+    /// integers and floats are detected, empty fields become `Value::Unit`. Without a header
+    /// row, records are named after `options.columns`, or stored as plain sequences if unset.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Csv` variant if the CSV parsing fails.
+    /// - Returns an `Error::Path` variant if `path` or a column name is not a valid path.
+    ///
+    /// # Example
+    /// ```rust
+    /// let csv = "name\tage\tactive\nAlice\t30\ttrue\n";
+    /// let context = oxidex::Context::from_csv(csv, "users", &oxidex::CsvOptions::tsv()).unwrap();
+    ///
+    /// assert_eq!(context.get_path("users[0].name").unwrap(), &serde_value::Value::String("Alice".to_string()));
+    /// assert_eq!(context.get_path("users[0].age").unwrap(), &serde_value::Value::U64(30));
+    /// assert_eq!(context.get_path("users[0].active").unwrap(), &serde_value::Value::Bool(true));
+    /// ```
+    pub fn from_csv(csv: &str, path: &str, options: &crate::CsvOptions) -> crate::Result<Context> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .quote(options.quote)
+            .has_headers(options.has_headers)
+            .from_reader(csv.as_bytes());
+        let headers = match options.has_headers {
+            true => Some(
+                reader
+                    .headers()?
+                    .iter()
+                    .map(str::to_string)
+                    .collect::<Vec<String>>(),
+            ),
+            false => options.columns.clone(),
+        };
+        // Both `tags.0` and `tags[0]` columns are read back as sequences.
+        let columns = FlattenOptions {
+            indices: IndexStyle::Bracketed,
+            ..FlattenOptions::default()
+        };
+        let mut records = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let Some(headers) = &headers else {
+                records.push(Value::Seq(record.iter().map(infer).collect()));
+                continue;
+            };
+            let fields: BTreeMap<&String, Value> = headers
+                .iter()
+                .zip(record.iter())
+                .filter(
+                    |(column, _)| match (&options.columns, options.has_headers) {
+                        (Some(columns), true) => columns.contains(column),
+                        _ => true,
+                    },
+                )
+                .map(|(column, field)| (column, infer(field)))
+                .collect();
+            records.push(unflatten_value(fields, &columns)?);
+        }
+        let mut context = Context::new();
+        path::insert_in(
+            &mut context.inner,
+            path.parse::<Path>()?.segments(),
+            Value::Seq(records),
+        )?;
+        Ok(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer() {
+        assert_eq!(infer(""), Value::Unit);
+        assert_eq!(infer("TRUE"), Value::Bool(true));
+        assert_eq!(infer("42"), Value::U64(42));
+        assert_eq!(infer("-42"), Value::I64(-42));
+        assert_eq!(infer("0.5"), Value::F64(0.5));
+        assert_eq!(infer("00042"), Value::String("00042".to_string()));
+        assert_eq!(infer("inf"), Value::String("inf".to_string()));
+    }
+
+    #[test]
+    fn test_roundtrip_nested() {
+        let mut context = Context::new();
+        let mut record = BTreeMap::new();
+        let mut address = BTreeMap::new();
+        address.insert(
+            Value::String("city".to_string()),
+            Value::String("Lyon, FR".to_string()),
+        );
+        record.insert(Value::String("address".to_string()), Value::Map(address));
+        record.insert(Value::String("id".to_string()), Value::U64(7));
+        context.insert("rows".to_string(), Value::Seq(vec![Value::Map(record)]));
+
+        let csv = context.to_csv("rows", &CsvOptions::default()).unwrap();
+        assert_eq!(csv, "address.city,id\n\"Lyon, FR\",7\n");
+
+        let imported = Context::from_csv(&csv, "rows", &CsvOptions::default()).unwrap();
+        assert_eq!(imported.get_path("rows"), context.get_path("rows"));
+    }
+
+    #[test]
+    fn test_columns_without_headers() {
+        let options = CsvOptions {
+            has_headers: false,
+            columns: Some(vec!["name".to_string()]),
+            ..CsvOptions::default()
+        };
+        let context = Context::from_csv("Alice\nBob\n", "people", &options).unwrap();
+        assert_eq!(
+            context.get_path("people[1].name"),
+            Some(&Value::String("Bob".to_string()))
+        );
+
+        let csv = context.to_csv("people", &options).unwrap();
+        assert_eq!(csv, "Alice\nBob\n");
+    }
+}
@@ -0,0 +1,574 @@
+use crate::diff::key_segment;
+use crate::entries::Node;
+use crate::path::Path;
+use crate::value::{as_float, as_integer, is_null, parse_duration, to_inline, unwrap, unwrap_mut};
+use crate::Context;
+use serde_value::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// A value converted to the type declared for its location by [`Context::apply_defaults`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coercion {
+    /// The location of the value.
+    pub path: Path,
+    /// The value before the coercion.
+    pub from: Value,
+    /// The value after the coercion.
+    pub to: Value,
+}
+
+impl fmt::Display for Coercion {
+    /// Renders the coercion as `server.port: "8080" -> 8080`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            to_inline(&self.from),
+            to_inline(&self.to)
+        )
+    }
+}
+
+/// What [`Context::apply_defaults`] changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DefaultsReport {
+    /// The locations of the missing (or null) values that were filled, in document order.
+    pub filled: Vec<Path>,
+    /// The values converted to their declared type, in document order.
+    pub coerced: Vec<Coercion>,
+}
+
+impl DefaultsReport {
+    /// Returns `true` if nothing was filled nor coerced.
+    pub fn is_empty(&self) -> bool {
+        self.filled.is_empty() && self.coerced.is_empty()
+    }
+}
+
+/// A source of default values and declared types for [`Context::apply_defaults`].
+///
+/// It is implemented by `Context`, whose values are both the defaults and the declared types, and
+/// by [`crate::Schema`] (`schema` feature), which reads the `default`, `type` and `format`
+/// keywords.
+pub trait Defaults {
+    /// Fills the missing values of `context` and coerces the others to their declared type.
+    fn apply_to(&self, context: &mut Context) -> DefaultsReport;
+}
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+/// Returns `true` if a value is a serialized `std::time::Duration`.
+fn is_duration(value: &Value) -> bool {
+    match unwrap(value) {
+        Value::Map(map) => {
+            map.len() == 2
+                && map.contains_key(&string("secs"))
+                && map.contains_key(&string("nanos"))
+        }
+        _ => false,
+    }
+}
+
+/// Serializes a duration the way `serde` does, so that it deserializes back into a `Duration`.
+fn duration(duration: Duration) -> Value {
+    Value::Map(BTreeMap::from([
+        (string("secs"), Value::U64(duration.as_secs())),
+        (string("nanos"), Value::U32(duration.subsec_nanos())),
+    ]))
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::String(text) => text.trim().parse().ok(),
+        value => as_integer(value).or_else(|| {
+            as_float(value)
+                .filter(|v| v.is_finite() && v.fract() == 0.0)
+                .map(|v| v as i128)
+        }),
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(text) => text.trim().parse().ok(),
+        value => as_float(value),
+    }
+}
+
+/// Converts `value` to the type of `template`. Returns `None` if it already has that type or
+/// cannot be converted.
+fn coerce(value: &Value, template: &Value) -> Option<Value> {
+    let value = unwrap(value);
+    if is_duration(template) {
+        return match value {
+            value if is_duration(value) => None,
+            Value::String(text) => parse_duration(text).map(duration),
+            value => as_float(value)
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .map(duration),
+        };
+    }
+    if std::mem::discriminant(value) == std::mem::discriminant(template) {
+        return None;
+    }
+    match template {
+        Value::Bool(_) => match value {
+            Value::String(text) => parse_bool(text),
+            value => match as_integer(value) {
+                Some(0) => Some(false),
+                Some(1) => Some(true),
+                _ => None,
+            },
+        }
+        .map(Value::Bool),
+        Value::String(_) => match value {
+            Value::Char(c) => Some(Value::String(c.to_string())),
+            Value::Bool(_) => Some(Value::String(to_inline(value))),
+            value => as_float(value).map(|_| Value::String(to_inline(value))),
+        },
+        Value::F32(_) => number(value).map(|v| Value::F32(v as f32)),
+        Value::F64(_) => number(value).map(Value::F64),
+        template => {
+            let v = integer(value)?;
+            match template {
+                Value::U8(_) => u8::try_from(v).ok().map(Value::U8),
+                Value::U16(_) => u16::try_from(v).ok().map(Value::U16),
+                Value::U32(_) => u32::try_from(v).ok().map(Value::U32),
+                Value::U64(_) => u64::try_from(v).ok().map(Value::U64),
+                Value::I8(_) => i8::try_from(v).ok().map(Value::I8),
+                Value::I16(_) => i16::try_from(v).ok().map(Value::I16),
+                Value::I32(_) => i32::try_from(v).ok().map(Value::I32),
+                Value::I64(_) => i64::try_from(v).ok().map(Value::I64),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Coerces `value` to the type of `template`, recording the change.
+fn coerce_at(value: &mut Value, template: &Value, path: &Path, report: &mut DefaultsReport) {
+    if let Some(coerced) = coerce(value, template) {
+        report.coerced.push(Coercion {
+            path: path.clone(),
+            from: std::mem::replace(value, coerced.clone()),
+            to: coerced,
+        });
+    }
+}
+
+/// Fills `target` with the values of `defaults` it lacks and coerces its values to their types.
+fn fill(target: &mut Value, defaults: &Value, path: &Path, report: &mut DefaultsReport) {
+    if let (Value::Map(defaults), false) = (unwrap(defaults), is_duration(defaults)) {
+        if let Value::Map(target) = unwrap_mut(target) {
+            for (key, default) in defaults {
+                let child = path.join(key_segment(key));
+                match target.get_mut(key) {
+                    Some(value) if !is_null(value) => fill(value, default, &child, report),
+                    _ => {
+                        target.insert(key.clone(), default.clone());
+                        report.filled.push(child);
+                    }
+                }
+            }
+        }
+        return;
+    }
+    coerce_at(target, defaults, path, report)
+}
+
+/// Fills `target` like [`fill`], copying only the nodes it changes. Maps and sequences are never
+/// coerced, so only their items are visited.
+fn fill_node(target: &mut Node, defaults: &Value, path: &Path, report: &mut DefaultsReport) {
+    if let Node::Leaf(value) = target {
+        return fill(value, defaults, path, report);
+    }
+    let (Value::Map(defaults), false) = (unwrap(defaults), is_duration(defaults)) else {
+        return;
+    };
+    if target.map().is_none() {
+        return;
+    }
+    for (key, default) in defaults {
+        let child = path.join(key_segment(key));
+        if is_present(target, key) {
+            target.update_entry(key, |item| fill_node(item, default, &child, report));
+        } else {
+            let map = target.map_mut().expect("the node is a map");
+            map.insert(key.clone(), Node::from(default.clone()));
+            report.filled.push(child);
+        }
+    }
+}
+
+/// Returns `true` if a map node holds a value other than null under `key`.
+fn is_present(node: &Node, key: &Value) -> bool {
+    node.map()
+        .and_then(|map| map.get(key))
+        .is_some_and(|item| !item.is_null())
+}
+
+impl Defaults for Context {
+    /// Uses the values of this `Context` as defaults, and their types as the declared types.
+    fn apply_to(&self, context: &mut Context) -> DefaultsReport {
+        let mut report = DefaultsReport::default();
+        let mut root = context.to_node();
+        fill_node(&mut root, &self.to_value(), &Path::root(), &mut report);
+        if let Ok(filled) = Context::from_node(root) {
+            context.inner = filled.inner;
+        }
+        report
+    }
+}
+
+#[cfg(feature = "schema")]
+mod schema {
+    use super::*;
+    use crate::path::Segment;
+    use crate::schema::resolve;
+    use crate::Schema;
+
+    /// The maximum number of nested `$ref` defaults are looked up through. It guards against
+    /// cycles, and bounds the missing objects created for a recursive schema.
+    const MAX_REF_DEPTH: usize = 64;
+
+    /// Returns the keyword `name` of a schema.
+    fn keyword<'a>(schema: &'a Value, name: &str) -> Option<&'a Value> {
+        match unwrap(schema) {
+            Value::Map(map) => map.get(&string(name)).map(unwrap),
+            _ => None,
+        }
+    }
+
+    /// Returns a value of the type declared by a schema, to coerce `value` into.
+    fn template(schema: &Value, value: &Value) -> Option<Value> {
+        if let Some(Value::String(format)) = keyword(schema, "format") {
+            if format == "duration" {
+                return Some(duration(Duration::ZERO));
+            }
+        }
+        let Some(Value::String(name)) = keyword(schema, "type") else {
+            return None;
+        };
+        match name.as_str() {
+            "boolean" => Some(Value::Bool(false)),
+            "string" => Some(string("")),
+            "integer" if as_integer(unwrap(value)).is_none() => match integer(unwrap(value)) {
+                Some(v) if v < 0 => Some(Value::I64(0)),
+                _ => Some(Value::U64(0)),
+            },
+            "number" if as_float(unwrap(value)).is_none() => Some(Value::F64(0.0)),
+            _ => None,
+        }
+    }
+
+    /// Returns the `default` keyword of a schema, following references.
+    fn default_of<'a>(root: &'a Value, schema: &'a Value, depth: usize) -> Option<&'a Value> {
+        keyword(schema, "default").or_else(|| match keyword(schema, "$ref") {
+            Some(Value::String(reference)) if depth < MAX_REF_DEPTH => {
+                default_of(root, resolve(root, reference)?, depth + 1)
+            }
+            _ => None,
+        })
+    }
+
+    fn fill_schema(
+        root: &Value,
+        schema: &Value,
+        target: &mut Value,
+        path: &Path,
+        depth: usize,
+        report: &mut DefaultsReport,
+    ) {
+        if let Some(Value::String(reference)) = keyword(schema, "$ref") {
+            if let (Some(referenced), true) = (resolve(root, reference), depth < MAX_REF_DEPTH) {
+                fill_schema(root, referenced, target, path, depth + 1, report);
+            }
+        }
+        if let Some(template) = template(schema, target) {
+            coerce_at(target, &template, path, report);
+        }
+        let properties = match keyword(schema, "properties") {
+            Some(Value::Map(properties)) => Some(properties),
+            _ => None,
+        };
+        match unwrap_mut(target) {
+            Value::Map(object) => {
+                for (key, property) in properties.into_iter().flatten() {
+                    let child = path.join(key_segment(key));
+                    match object.get_mut(key) {
+                        Some(value) if !is_null(value) => {
+                            fill_schema(root, property, value, &child, depth, report)
+                        }
+                        _ => {
+                            if let Some(default) = default_of(root, property, 0) {
+                                object.insert(key.clone(), default.clone());
+                                report.filled.push(child);
+                                continue;
+                            }
+                            // Missing objects are created when one of their properties has a default.
+                            let mut value = Value::Map(BTreeMap::new());
+                            let filled = report.filled.len();
+                            fill_schema(root, property, &mut value, &child, depth, report);
+                            if report.filled.len() > filled {
+                                object.insert(key.clone(), value);
+                            }
+                        }
+                    }
+                }
+                if let Some(additional) = keyword(schema, "additionalProperties") {
+                    for (key, value) in object.iter_mut() {
+                        if !properties.is_some_and(|properties| properties.contains_key(key)) {
+                            let child = path.join(key_segment(key));
+                            fill_schema(root, additional, value, &child, depth, report);
+                        }
+                    }
+                }
+            }
+            Value::Seq(items) => {
+                if let Some(item_schema) = keyword(schema, "items") {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        let child = path.join(Segment::Index(index));
+                        fill_schema(root, item_schema, item, &child, depth, report);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Fills `target` like [`fill_schema`], copying only the nodes it changes. Maps and sequences
+    /// are never coerced, so only their items are visited.
+    fn fill_schema_node(
+        root: &Value,
+        schema: &Value,
+        target: &mut Node,
+        path: &Path,
+        depth: usize,
+        report: &mut DefaultsReport,
+    ) {
+        if let Node::Leaf(value) = target {
+            return fill_schema(root, schema, value, path, depth, report);
+        }
+        if let Some(Value::String(reference)) = keyword(schema, "$ref") {
+            if let (Some(referenced), true) = (resolve(root, reference), depth < MAX_REF_DEPTH) {
+                fill_schema_node(root, referenced, target, path, depth + 1, report);
+            }
+        }
+        let properties = match keyword(schema, "properties") {
+            Some(Value::Map(properties)) => Some(properties),
+            _ => None,
+        };
+        if target.map().is_none() {
+            if let Some(item_schema) = keyword(schema, "items") {
+                target.update_items(|segment, item| {
+                    let child = path.join(segment);
+                    fill_schema_node(root, item_schema, item, &child, depth, report)
+                });
+            }
+            return;
+        }
+        for (key, property) in properties.into_iter().flatten() {
+            let child = path.join(key_segment(key));
+            if is_present(target, key) {
+                target.update_entry(key, |item| {
+                    fill_schema_node(root, property, item, &child, depth, report)
+                });
+                continue;
+            }
+            let value = match default_of(root, property, 0) {
+                Some(default) => {
+                    report.filled.push(child);
+                    default.clone()
+                }
+                None => {
+                    // Missing objects are created when one of their properties has a default.
+                    let mut value = Value::Map(BTreeMap::new());
+                    let filled = report.filled.len();
+                    fill_schema(root, property, &mut value, &child, depth, report);
+                    if report.filled.len() == filled {
+                        continue;
+                    }
+                    value
+                }
+            };
+            let map = target.map_mut().expect("the node is a map");
+            map.insert(key.clone(), Node::from(value));
+        }
+        if let Some(additional) = keyword(schema, "additionalProperties") {
+            let keys: Vec<Value> = target
+                .map()
+                .into_iter()
+                .flat_map(|map| map.keys())
+                .filter(|key| !properties.is_some_and(|properties| properties.contains_key(key)))
+                .cloned()
+                .collect();
+            for key in keys {
+                let child = path.join(key_segment(&key));
+                target.update_entry(&key, |item| {
+                    fill_schema_node(root, additional, item, &child, depth, report)
+                });
+            }
+        }
+    }
+
+    impl Defaults for Schema {
+        /// Fills missing properties from their `default` keyword and coerces values to their
+        /// declared `type`; strings with the `duration` format become durations.
+        fn apply_to(&self, context: &mut Context) -> DefaultsReport {
+            let mut report = DefaultsReport::default();
+            let mut root = context.to_node();
+            fill_schema_node(
+                &self.root,
+                &self.root,
+                &mut root,
+                &Path::root(),
+                0,
+                &mut report,
+            );
+            if let Ok(filled) = Context::from_node(root) {
+                context.inner = filled.inner;
+            }
+            report
+        }
+    }
+}
+
+impl Context {
+    /// Fills in missing values from defaults, at any depth, and coerces the existing values to
+    /// their declared types.
+    ///
+    /// With a `Context` as defaults, each default value declares the type of its location: a
+    /// string `"8080"` becomes a `U16` if the default is a `U16`, `"true"` becomes a `Bool`, and
+    /// `"30s"` becomes a duration if the default is a serialized `std::time::Duration`. With a
+    /// [`crate::Schema`] (`schema` feature), the `default`, `type` and `format: duration`
+    /// keywords are used instead. Null values are treated as missing; values that cannot be
+    /// coerced are left untouched.
+    ///
+    /// `defaults`: A `Context` of default values, or a `Schema`.
+    ///
+    /// Returns a report of every filled and coerced value.
+    ///
+    /// Example:
+    /// ```
+    /// use oxidex::Context;
+    /// use serde_value::Value;
+    /// use std::time::Duration;
+    ///
+    /// let mut defaults = Context::new();
+    /// defaults.set_path("server.port", Value::U16(80)).unwrap();
+    /// defaults.set_path("server.tls", Value::Bool(false)).unwrap();
+    /// defaults.set_path("server.timeout", serde_value::to_value(Duration::from_secs(5)).unwrap()).unwrap();
+    ///
+    /// let mut config = Context::new();
+    /// config.set_path("server.port", Value::String("8080".to_string())).unwrap();
+    /// config.set_path("server.timeout", Value::String("1m 30s".to_string())).unwrap();
+    ///
+    /// let report = config.apply_defaults(&defaults);
+    /// assert_eq!(config.get_path("server.port").unwrap(), &Value::U16(8080));
+    /// assert_eq!(config.get_path("server.tls").unwrap(), &Value::Bool(false));
+    /// let timeout: Duration = config.get_path("server.timeout").unwrap().clone().deserialize_into().unwrap();
+    /// assert_eq!(timeout, Duration::from_secs(90));
+    ///
+    /// assert_eq!(report.filled.len(), 1);
+    /// assert_eq!(report.coerced[0].to_string(), r#"server.port: "8080" -> 8080"#);
+    /// ```
+    pub fn apply_defaults<D: Defaults + ?Sized>(&mut self, defaults: &D) -> DefaultsReport {
+        self.audited(|context| defaults.apply_to(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coerce() {
+        let text = |t: &str| Value::String(t.to_string());
+        assert_eq!(coerce(&text(" 42 "), &Value::U8(0)), Some(Value::U8(42)));
+        assert_eq!(coerce(&text("300"), &Value::U8(0)), None);
+        assert_eq!(coerce(&text("-3"), &Value::I32(0)), Some(Value::I32(-3)));
+        assert_eq!(
+            coerce(&Value::F64(3.0), &Value::U64(0)),
+            Some(Value::U64(3))
+        );
+        assert_eq!(
+            coerce(&text("off"), &Value::Bool(true)),
+            Some(Value::Bool(false))
+        );
+        assert_eq!(
+            coerce(&text("1.5"), &Value::F64(0.0)),
+            Some(Value::F64(1.5))
+        );
+        assert_eq!(
+            coerce(&Value::U16(8), &Value::String(String::new())),
+            Some(text("8"))
+        );
+        assert_eq!(coerce(&text("x"), &Value::U64(0)), None);
+        assert_eq!(coerce(&Value::U64(1), &Value::U64(0)), None);
+        assert_eq!(
+            coerce(&text("250ms"), &duration(Duration::ZERO)),
+            Some(duration(Duration::from_millis(250)))
+        );
+        let overflowing = format!("{}d 1d", "9".repeat(400));
+        assert_eq!(coerce(&text(&overflowing), &duration(Duration::ZERO)), None);
+        assert_eq!(
+            coerce(&text("213503d 213503d"), &duration(Duration::ZERO)),
+            None
+        );
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn test_schema_defaults() {
+        let schema = crate::Schema::from_context(
+            &Context::from_args([
+                "--set",
+                "properties.db.properties.port.type=integer",
+                "--set",
+                "properties.db.properties.port.default=5432",
+                "--set",
+                "properties.db.properties.timeout.format=duration",
+                "--set",
+                "properties.cache.properties.ttl.$ref=#/$defs/ttl",
+                "--set",
+                "properties.tags.items.type=string",
+                "--set",
+                "$defs.ttl.default=60",
+            ])
+            .unwrap(),
+        )
+        .unwrap();
+        let mut context = Context::new();
+        context
+            .set_path("db.timeout", Value::String("2s".to_string()))
+            .unwrap();
+        context.set_path("tags[0]", Value::I64(7)).unwrap();
+
+        let report = context.apply_defaults(&schema);
+        let filled: Vec<String> = report.filled.iter().map(Path::to_string).collect();
+        assert_eq!(filled, vec!["cache.ttl".to_string(), "db.port".to_string()]);
+        let coerced: Vec<String> = report.coerced.iter().map(|c| c.path.to_string()).collect();
+        assert_eq!(
+            coerced,
+            vec!["db.timeout".to_string(), "tags[0]".to_string()]
+        );
+        assert_eq!(context.get_path("cache.ttl").unwrap(), &Value::U64(60));
+        assert_eq!(
+            context.get_path("tags[0]").unwrap(),
+            &Value::String("7".to_string())
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use crate::entries::Node;
+use crate::path::{Path, Segment};
+use crate::value::{equivalent, to_inline, unwrap};
+use crate::Context;
+use serde::Serialize;
+use serde_value::Value;
+use std::fmt;
+
+/// A single difference between two contexts.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Change {
+    /// A value present only in the new context.
+    Added {
+        /// The location of the value.
+        path: Path,
+        /// The added value.
+        value: Value,
+    },
+    /// A value present only in the old context.
+    Removed {
+        /// The location of the value.
+        path: Path,
+        /// The removed value.
+        value: Value,
+    },
+    /// A value present in both contexts with different contents.
+    Changed {
+        /// The location of the value.
+        path: Path,
+        /// The value in the old context.
+        old: Value,
+        /// The value in the new context.
+        new: Value,
+    },
+}
+
+impl Change {
+    /// Returns the location of the changed value.
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// The structural differences between two contexts, as returned by [`Context::diff`].
+///
+/// It renders as a unified, human-readable diff with `Display`, and serializes as a list of
+/// `{"op": ..., "path": ...}` entries for machine consumption.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Diff {
+    changes: Vec<Change>,
+}
+
+impl Diff {
+    /// Returns the changes, ordered by path.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns `true` if both contexts are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the number of changes.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Serializes the changes into a JSON array.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Json` variant if serialization fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// let old = oxidex::Context::from_json(r#"{"port": 80}"#).unwrap();
+    /// let new = oxidex::Context::from_json(r#"{"port": 8080}"#).unwrap();
+    ///
+    /// assert_eq!(
+    ///     old.diff(&new).to_json(false).unwrap(),
+    ///     r#"[{"op":"changed","path":"port","old":80,"new":8080}]"#
+    /// );
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, pretty: bool) -> crate::Result<String> {
+        match pretty {
+            true => Ok(serde_json::to_string_pretty(self)?),
+            false => Ok(serde_json::to_string(self)?),
+        }
+    }
+}
+
+impl IntoIterator for Diff {
+    type Item = Change;
+    type IntoIter = std::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Diff {
+    type Item = &'a Change;
+    type IntoIter = std::slice::Iter<'a, Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.iter()
+    }
+}
+
+impl fmt::Display for Diff {
+    /// Renders one line per removed value (`-`) and added value (`+`); a changed value shows both.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            match change {
+                Change::Added { path, value } => writeln!(f, "+ {}: {}", path, to_inline(value))?,
+                Change::Removed { path, value } => writeln!(f, "- {}: {}", path, to_inline(value))?,
+                Change::Changed { path, old, new } => {
+                    writeln!(f, "- {}: {}", path, to_inline(old))?;
+                    writeln!(f, "+ {}: {}", path, to_inline(new))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends to `changes` the differences between `old` and `new`, both located at `path`.
+pub(crate) fn diff_values(path: &Path, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (unwrap(old), unwrap(new)) {
+        (Value::Map(old), Value::Map(new)) => {
+            for (key, value) in old {
+                let child = path.join(key_segment(key));
+                match new.get(key) {
+                    Some(other) => diff_values(&child, value, other, changes),
+                    None => changes.push(Change::Removed {
+                        path: child,
+                        value: value.clone(),
+                    }),
+                }
+            }
+            for (key, value) in new.iter().filter(|(key, _)| !old.contains_key(key)) {
+                changes.push(Change::Added {
+                    path: path.join(key_segment(key)),
+                    value: value.clone(),
+                });
+            }
+        }
+        (Value::Seq(old), Value::Seq(new)) => {
+            for (index, value) in old.iter().enumerate() {
+                let child = path.join(Segment::Index(index));
+                match new.get(index) {
+                    Some(other) => diff_values(&child, value, other, changes),
+                    None => changes.push(Change::Removed {
+                        path: child,
+                        value: value.clone(),
+                    }),
+                }
+            }
+            for (index, value) in new.iter().enumerate().skip(old.len()) {
+                changes.push(Change::Added {
+                    path: path.join(Segment::Index(index)),
+                    value: value.clone(),
+                });
+            }
+        }
+        (a, b) if !equivalent(a, b) => changes.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Appends to `changes` the differences between the nodes `old` and `new`, both located at
+/// `path`, skipping the maps and sequences they share.
+fn diff_nodes(path: &Path, old: &Node, new: &Node, changes: &mut Vec<Change>) {
+    if old.ptr_eq(new) {
+        return;
+    }
+    if let (Some(old), Some(new)) = (old.map(), new.map()) {
+        for (key, node) in old {
+            let child = path.join(key_segment(key));
+            match new.get(key) {
+                Some(other) => diff_nodes(&child, node, other, changes),
+                None => changes.push(Change::Removed {
+                    path: child,
+                    value: node.to_value(),
+                }),
+            }
+        }
+        for (key, node) in new.iter().filter(|(key, _)| !old.contains_key(key)) {
+            changes.push(Change::Added {
+                path: path.join(key_segment(key)),
+                value: node.to_value(),
+            });
+        }
+    } else if let (Some(old), Some(new)) = (old.seq(), new.seq()) {
+        for (index, node) in old.iter().enumerate() {
+            let child = path.join(Segment::Index(index));
+            match new.get(index) {
+                Some(other) => diff_nodes(&child, node, other, changes),
+                None => changes.push(Change::Removed {
+                    path: child,
+                    value: node.to_value(),
+                }),
+            }
+        }
+        for (index, node) in new.iter().enumerate().skip(old.len()) {
+            changes.push(Change::Added {
+                path: path.join(Segment::Index(index)),
+                value: node.to_value(),
+            });
+        }
+    } else {
+        diff_values(path, &old.as_value(), &new.as_value(), changes);
+    }
+}
+
+/// Returns the path segment designating a map key.
+pub(crate) fn key_segment(key: &Value) -> Segment {
+    match key {
+        Value::String(key) => Segment::Key(key.clone()),
+        key => Segment::Key(to_inline(key)),
+    }
+}
+
+impl Context {
+    /// Computes the structural differences between this `Context` and `other`.
+    ///
+    /// Maps are compared key by key and sequences index by index, down to the leaves. Values are
+    /// compared regardless of the format they were loaded from, so `30` read from TOML (`I64`)
+    /// equals `30` read from JSON (`U64`).
+    ///
+    /// `other`: The new version of the context.
+    ///
+    /// Example:
+    /// ```
+    /// let mut old = oxidex::Context::new();
+    /// old.set_path("db.host", serde_value::Value::String("localhost".to_string())).unwrap();
+    /// old.set_path("db.port", serde_value::Value::U64(5432)).unwrap();
+    ///
+    /// let mut new = old.clone();
+    /// new.set_path("db.host", serde_value::Value::String("db.internal".to_string())).unwrap();
+    /// new.set_path("debug", serde_value::Value::Bool(true)).unwrap();
+    ///
+    /// let diff = old.diff(&new);
+    /// assert_eq!(diff.len(), 2);
+    /// assert_eq!(
+    ///     diff.to_string(),
+    ///     "- db.host: \"localhost\"\n+ db.host: \"db.internal\"\n+ debug: true\n"
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Context) -> Diff {
+        let mut changes = Vec::new();
+        for (key, node) in self.inner.nodes() {
+            let path = Path::root().join(Segment::Key(key.clone()));
+            match other.inner.node(key) {
+                Some(new) => diff_nodes(&path, node, new, &mut changes),
+                None => changes.push(Change::Removed {
+                    path,
+                    value: node.to_value(),
+                }),
+            }
+        }
+        for (key, node) in other
+            .inner
+            .nodes()
+            .filter(|(key, _)| !self.inner.contains_key(key))
+        {
+            changes.push(Change::Added {
+                path: Path::root().join(Segment::Key(key.clone())),
+                value: node.to_value(),
+            });
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        Diff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_format_numbers() {
+        let mut old = Context::new();
+        old.insert("port".to_string(), Value::I64(30));
+        old.insert("ratio".to_string(), Value::F64(1.0));
+        let mut new = Context::new();
+        new.insert("port".to_string(), Value::U64(30));
+        new.insert("ratio".to_string(), Value::U8(1));
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn test_sequences() {
+        let mut old = Context::new();
+        old.insert(
+            "list".to_string(),
+            Value::Seq(vec![Value::U8(1), Value::U8(2)]),
+        );
+        let mut new = Context::new();
+        new.insert("list".to_string(), Value::Seq(vec![Value::U8(3)]));
+        assert_eq!(
+            old.diff(&new).changes(),
+            &[
+                Change::Changed {
+                    path: "list[0]".parse().unwrap(),
+                    old: Value::U8(1),
+                    new: Value::U8(3),
+                },
+                Change::Removed {
+                    path: "list[1]".parse().unwrap(),
+                    value: Value::U8(2),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,550 @@
+use crate::diff::key_segment;
+use crate::entries::Node;
+use crate::path::{Path, Segment};
+use crate::{Context, Error};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use serde_value::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+const SYMMETRIC_KEY_PREFIX: &str = "OXIDEX-SYMMETRIC-KEY-";
+const SECRET_KEY_PREFIX: &str = "OXIDEX-SECRET-KEY-";
+const RECIPIENT_PREFIX: &str = "oxidex-recipient-";
+const HKDF_INFO: &[u8] = b"oxidex x25519 chacha20poly1305";
+
+/// The keys used to encrypt and decrypt the `ENC[...]` values of a `Context`.
+///
+/// A keyring holds symmetric keys, X25519 identities (private keys) and X25519 recipients (public
+/// keys), usually loaded from local key files. Key files hold one key per line, in the format
+/// written by [`Keyring::generate_symmetric_key`] and [`Keyring::generate_identity`]; empty lines
+/// and lines starting with `#` are ignored.
+///
+/// Values are encrypted for the first recipient of the keyring (the public keys of its identities
+/// included) or, failing that, with its first symmetric key.
+///
+/// # Example
+/// ```
+/// let identity = oxidex::Keyring::generate_identity();
+/// let keyring = oxidex::Keyring::from_keys(&identity).unwrap();
+///
+/// let recipient = keyring.recipients().remove(0);
+/// assert!(identity.contains(&recipient));
+/// assert!(oxidex::Keyring::from_keys(&recipient).is_ok());
+/// ```
+#[derive(Clone, Default)]
+pub struct Keyring {
+    symmetric: Vec<[u8; 32]>,
+    identities: Vec<StaticSecret>,
+    recipients: Vec<PublicKey>,
+}
+
+impl fmt::Debug for Keyring {
+    /// Formats the number of keys of each kind, never the keys themselves.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("symmetric", &self.symmetric.len())
+            .field("identities", &self.identities.len())
+            .field("recipients", &self.recipients.len())
+            .finish()
+    }
+}
+
+/// Returns a short identifier of a key, stored in envelopes to find the key to decrypt them.
+fn key_id(key: &[u8]) -> String {
+    Sha256::digest(key)[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode(field: &str, text: &str) -> crate::Result<Vec<u8>> {
+    STANDARD
+        .decode(text)
+        .map_err(|err| Error::Encryption(format!("invalid {}: {}", field, err)))
+}
+
+fn decode_key(field: &str, text: &str) -> crate::Result<[u8; 32]> {
+    decode(field, text)?
+        .try_into()
+        .map_err(|_| Error::Encryption(format!("invalid {}: expected 32 bytes", field)))
+}
+
+/// Derives the symmetric key shared with a recipient from an X25519 exchange.
+fn derive(shared: &[u8], ephemeral: &PublicKey, recipient: &PublicKey) -> [u8; 32] {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes()].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(HKDF_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+impl Keyring {
+    /// Creates an empty `Keyring`.
+    pub fn new() -> Keyring {
+        Keyring::default()
+    }
+
+    /// Generates a random symmetric key, formatted as a key file.
+    pub fn generate_symmetric_key() -> String {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        format!("{}{}\n", SYMMETRIC_KEY_PREFIX, STANDARD.encode(key))
+    }
+
+    /// Generates a random X25519 identity, formatted as a key file whose comment gives the
+    /// recipient (public key) to share with the people encrypting values for it.
+    pub fn generate_identity() -> String {
+        let identity = StaticSecret::random_from_rng(OsRng);
+        format!(
+            "# public key: {}{}\n{}{}\n",
+            RECIPIENT_PREFIX,
+            STANDARD.encode(PublicKey::from(&identity).as_bytes()),
+            SECRET_KEY_PREFIX,
+            STANDARD.encode(identity.to_bytes())
+        )
+    }
+
+    /// Creates a `Keyring` from the contents of a key file.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Encryption` variant if a line is not a valid key.
+    pub fn from_keys(keys: &str) -> crate::Result<Keyring> {
+        let mut keyring = Keyring::new();
+        keyring.add_keys(keys)?;
+        Ok(keyring)
+    }
+
+    /// Creates a `Keyring` from a local key file.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Io` variant if the file cannot be read.
+    /// - Returns an `Error::Encryption` variant if a line is not a valid key.
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> crate::Result<Keyring> {
+        Keyring::from_keys(&std::fs::read_to_string(path)?)
+    }
+
+    /// Adds the keys found in the contents of a key file.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Encryption` variant if a line is not a valid key; no key is added then.
+    pub fn add_keys(&mut self, keys: &str) -> crate::Result<()> {
+        let mut keyring = self.clone();
+        for (number, line) in keys.lines().enumerate() {
+            let line = line.trim();
+            if let Some(key) = line.strip_prefix(SYMMETRIC_KEY_PREFIX) {
+                keyring.symmetric.push(decode_key("symmetric key", key)?);
+            } else if let Some(key) = line.strip_prefix(SECRET_KEY_PREFIX) {
+                let identity = StaticSecret::from(decode_key("secret key", key)?);
+                keyring.identities.push(identity);
+            } else if let Some(key) = line.strip_prefix(RECIPIENT_PREFIX) {
+                let recipient = PublicKey::from(decode_key("recipient", key)?);
+                keyring.recipients.push(recipient);
+            } else if !line.is_empty() && !line.starts_with('#') {
+                return Err(Error::Encryption(format!(
+                    "unrecognized key on line {}",
+                    number + 1
+                )));
+            }
+        }
+        *self = keyring;
+        Ok(())
+    }
+
+    /// Returns the recipients values can be encrypted for, the public keys of the identities
+    /// included, formatted as in key files.
+    pub fn recipients(&self) -> Vec<String> {
+        self.recipients
+            .iter()
+            .cloned()
+            .chain(self.identities.iter().map(PublicKey::from))
+            .map(|recipient| {
+                format!(
+                    "{}{}",
+                    RECIPIENT_PREFIX,
+                    STANDARD.encode(recipient.as_bytes())
+                )
+            })
+            .collect()
+    }
+
+    /// Encrypts the text form of a value into an `ENC[...]` envelope bound to `aad`.
+    fn seal(&self, plaintext: &str, kind: &str, aad: &str) -> crate::Result<String> {
+        let recipient = self
+            .recipients
+            .first()
+            .cloned()
+            .or_else(|| self.identities.first().map(PublicKey::from));
+        let (key, header) = match (recipient, self.symmetric.first()) {
+            (Some(recipient), _) => {
+                let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+                let public = PublicKey::from(&ephemeral);
+                let shared = ephemeral.diffie_hellman(&recipient);
+                let header = format!(
+                    "x25519,epk:{},kid:{}",
+                    STANDARD.encode(public.as_bytes()),
+                    key_id(recipient.as_bytes())
+                );
+                (derive(shared.as_bytes(), &public, &recipient), header)
+            }
+            (None, Some(key)) => (*key, format!("chacha20poly1305,kid:{}", key_id(key))),
+            (None, None) => {
+                return Err(Error::Encryption(
+                    "the keyring has no key to encrypt with".to_string(),
+                ))
+            }
+        };
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let data = ChaCha20Poly1305::new(&key.into())
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: format!("{}:{}", aad, kind).as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Encryption("encryption failed".to_string()))?;
+        Ok(format!(
+            "ENC[{},nonce:{},data:{},type:{}]",
+            header,
+            STANDARD.encode(nonce),
+            STANDARD.encode(data),
+            kind
+        ))
+    }
+
+    /// Decrypts an `ENC[...]` envelope bound to `aad`, returning the text form and type of the value.
+    fn open(&self, envelope: &str, aad: &str) -> crate::Result<(String, String)> {
+        let malformed = || Error::Encryption(format!("malformed envelope at '{}'", aad));
+        let inner = envelope
+            .strip_prefix("ENC[")
+            .and_then(|inner| inner.strip_suffix(']'))
+            .ok_or_else(malformed)?;
+        let mut parts = inner.split(',');
+        let scheme = parts.next().ok_or_else(malformed)?;
+        let fields: Vec<(&str, &str)> = parts
+            .map(|part| part.split_once(':').ok_or_else(malformed))
+            .collect::<crate::Result<_>>()?;
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+                .ok_or_else(malformed)
+        };
+        let (kid, kind) = (field("kid")?, field("type")?);
+        let missing = || Error::Encryption(format!("no key '{}' to decrypt '{}'", kid, aad));
+        let key = match scheme {
+            "chacha20poly1305" => *self
+                .symmetric
+                .iter()
+                .find(|key| key_id(key.as_slice()) == kid)
+                .ok_or_else(missing)?,
+            "x25519" => {
+                let identity = self
+                    .identities
+                    .iter()
+                    .find(|identity| key_id(PublicKey::from(*identity).as_bytes()) == kid)
+                    .ok_or_else(missing)?;
+                let ephemeral = PublicKey::from(decode_key("ephemeral key", field("epk")?)?);
+                let shared = identity.diffie_hellman(&ephemeral);
+                derive(shared.as_bytes(), &ephemeral, &PublicKey::from(identity))
+            }
+            scheme => {
+                return Err(Error::Encryption(format!(
+                    "unsupported scheme '{}' at '{}'",
+                    scheme, aad
+                )))
+            }
+        };
+        let nonce: [u8; 12] = decode("nonce", field("nonce")?)?
+            .try_into()
+            .map_err(|_| malformed())?;
+        let plaintext = ChaCha20Poly1305::new(&key.into())
+            .decrypt(
+                &nonce.into(),
+                Payload {
+                    msg: &decode("data", field("data")?)?,
+                    aad: format!("{}:{}", aad, kind).as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Encryption(format!("cannot decrypt the value at '{}'", aad)))?;
+        let plaintext = String::from_utf8(plaintext).map_err(|_| malformed())?;
+        Ok((plaintext, kind.to_string()))
+    }
+}
+
+/// Returns `true` if a value is an `ENC[...]` envelope.
+fn is_envelope(value: &Value) -> bool {
+    matches!(value, Value::String(text) if is_envelope_str(text))
+}
+
+/// Returns `true` if a text is an `ENC[...]` envelope.
+fn is_envelope_str(text: &str) -> bool {
+    text.starts_with("ENC[") && text.ends_with(']')
+}
+
+/// Returns the text form and type of a scalar value.
+fn encode(value: &Value) -> Option<(String, &'static str)> {
+    Some(match value {
+        Value::String(text) => (text.clone(), "str"),
+        Value::Char(c) => (c.to_string(), "str"),
+        Value::Bool(v) => (v.to_string(), "bool"),
+        Value::F32(v) => (v.to_string(), "float"),
+        Value::F64(v) => (v.to_string(), "float"),
+        Value::Unit | Value::Option(None) => (String::new(), "null"),
+        Value::Bytes(bytes) => (STANDARD.encode(bytes), "bytes"),
+        value => (crate::value::as_integer(value)?.to_string(), "int"),
+    })
+}
+
+/// Rebuilds a scalar value from its text form and type.
+fn restore(text: String, kind: &str, path: &str) -> crate::Result<Value> {
+    let invalid = || Error::Encryption(format!("invalid {} value at '{}'", kind, path));
+    Ok(match kind {
+        "str" => Value::String(text),
+        "bool" => Value::Bool(text.parse().map_err(|_| invalid())?),
+        "float" => Value::F64(text.parse().map_err(|_| invalid())?),
+        "null" => Value::Unit,
+        "bytes" => Value::Bytes(decode("bytes", &text)?),
+        "int" => match text.parse::<u64>() {
+            Ok(v) => Value::U64(v),
+            Err(_) => Value::I64(text.parse().map_err(|_| invalid())?),
+        },
+        _ => return Err(invalid()),
+    })
+}
+
+/// Encrypts the leaves of `value` selected by `patterns`, or below a selected value.
+fn encrypt_value(
+    value: &mut Value,
+    path: &Path,
+    selected: bool,
+    patterns: &[&str],
+    keyring: &Keyring,
+    count: &mut usize,
+) -> crate::Result<()> {
+    let selected = selected || patterns.iter().any(|pattern| path.matches(pattern));
+    match value {
+        Value::Map(map) => map.iter_mut().try_for_each(|(key, value)| {
+            let path = path.join(key_segment(key));
+            encrypt_value(value, &path, selected, patterns, keyring, count)
+        }),
+        Value::Seq(seq) => seq.iter_mut().enumerate().try_for_each(|(index, value)| {
+            let path = path.join(Segment::Index(index));
+            encrypt_value(value, &path, selected, patterns, keyring, count)
+        }),
+        Value::Option(Some(inner)) | Value::Newtype(inner) => {
+            encrypt_value(inner, path, selected, patterns, keyring, count)
+        }
+        value if selected && !is_envelope(value) => {
+            let Some((text, kind)) = encode(value) else {
+                return Ok(());
+            };
+            *value = Value::String(keyring.seal(&text, kind, &path.to_string())?);
+            *count += 1;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Decrypts the envelopes found in `value`, recording their paths.
+fn decrypt_value(
+    value: &mut Value,
+    path: &Path,
+    keyring: &Keyring,
+    decrypted: &mut Vec<Path>,
+) -> crate::Result<()> {
+    match value {
+        Value::Map(map) => map.iter_mut().try_for_each(|(key, value)| {
+            decrypt_value(value, &path.join(key_segment(key)), keyring, decrypted)
+        }),
+        Value::Seq(seq) => seq.iter_mut().enumerate().try_for_each(|(index, value)| {
+            decrypt_value(value, &path.join(Segment::Index(index)), keyring, decrypted)
+        }),
+        Value::Option(Some(inner)) | Value::Newtype(inner) => {
+            decrypt_value(inner, path, keyring, decrypted)
+        }
+        Value::String(envelope) if is_envelope_str(envelope) => {
+            let location = path.to_string();
+            let (text, kind) = keyring.open(envelope, &location)?;
+            *value = restore(text, &kind, &location)?;
+            decrypted.push(path.clone());
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Encrypts the leaves of `node` like [`encrypt_value`], copying only the nodes it changes.
+fn encrypt_node(
+    node: &mut Node,
+    path: &Path,
+    selected: bool,
+    patterns: &[&str],
+    keyring: &Keyring,
+    count: &mut usize,
+) -> crate::Result<()> {
+    if let Node::Leaf(value) = node {
+        return encrypt_value(value, path, selected, patterns, keyring, count);
+    }
+    let selected = selected || patterns.iter().any(|pattern| path.matches(pattern));
+    let mut result = Ok(());
+    node.update_items(|segment, item| {
+        if result.is_ok() {
+            let path = path.join(segment);
+            result = encrypt_node(item, &path, selected, patterns, keyring, count);
+        }
+    });
+    result
+}
+
+/// Decrypts the envelopes found in `node` like [`decrypt_value`], copying only the nodes it
+/// changes.
+fn decrypt_node(
+    node: &mut Node,
+    path: &Path,
+    keyring: &Keyring,
+    decrypted: &mut Vec<Path>,
+) -> crate::Result<()> {
+    if let Node::Leaf(value) = node {
+        return decrypt_value(value, path, keyring, decrypted);
+    }
+    let mut result = Ok(());
+    node.update_items(|segment, item| {
+        if result.is_ok() {
+            result = decrypt_node(item, &path.join(segment), keyring, decrypted);
+        }
+    });
+    result
+}
+
+impl Context {
+    /// Encrypts the leaf values selected by glob patterns into `ENC[...]` envelopes.
+    ///
+    /// Keys stay in plaintext so that diffs of encrypted files remain readable, and a pattern
+    /// selecting a map or a sequence encrypts every leaf below it. Values already encrypted are
+    /// left untouched. Each envelope is bound to its path: moving an encrypted value elsewhere
+    /// makes it undecryptable.
+    ///
+    /// `keyring`: The keys to encrypt with.
+    /// `patterns`: The glob patterns of the values to encrypt (see [`Path::matches`]).
+    ///
+    /// Returns the number of encrypted values.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Encryption` variant if the keyring has no key to encrypt with; the
+    ///   `Context` is left untouched then.
+    ///
+    /// Example:
+    /// ```
+    /// let keyring = oxidex::Keyring::from_keys(&oxidex::Keyring::generate_symmetric_key()).unwrap();
+    /// let mut context = oxidex::Context::from_args(["--set", "db.host=localhost", "--set", "db.password=hunter2"]).unwrap();
+    ///
+    /// assert_eq!(context.encrypt_paths(&keyring, &["**.password"]).unwrap(), 1);
+    /// let serde_value::Value::String(envelope) = context.get_path("db.password").unwrap() else { panic!() };
+    /// assert!(envelope.starts_with("ENC[chacha20poly1305,"));
+    ///
+    /// context.decrypt(&keyring).unwrap();
+    /// assert_eq!(context.get_path("db.password").unwrap(), &serde_value::Value::String("hunter2".to_string()));
+    /// assert!(context.is_secret("db.password"));
+    /// ```
+    pub fn encrypt_paths(&mut self, keyring: &Keyring, patterns: &[&str]) -> crate::Result<usize> {
+        self.audited(|context| {
+            let mut root = context.to_node();
+            let mut count = 0;
+            encrypt_node(
+                &mut root,
+                &Path::root(),
+                false,
+                patterns,
+                keyring,
+                &mut count,
+            )?;
+            context.inner = Context::from_node(root)?.inner;
+            Ok(count)
+        })
+    }
+
+    /// Decrypts every `ENC[...]` envelope of the `Context` and marks the decrypted values as
+    /// secret (see [`Context::mark_secret`]).
+    ///
+    /// `keyring`: The keys to decrypt with.
+    ///
+    /// Returns the number of decrypted values.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Encryption` variant if an envelope is malformed, if no key of the
+    ///   keyring can open it or if it was tampered with; the `Context` is left untouched then.
+    pub fn decrypt(&mut self, keyring: &Keyring) -> crate::Result<usize> {
+        self.audited(|context| {
+            let mut root = context.to_node();
+            let mut decrypted = Vec::new();
+            decrypt_node(&mut root, &Path::root(), keyring, &mut decrypted)?;
+            context.inner = Context::from_node(root)?.inner;
+            for path in &decrypted {
+                context.mark_secret(&path.to_string());
+            }
+            Ok(decrypted.len())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipient_roundtrip_keeps_types() {
+        let identity = Keyring::from_keys(&Keyring::generate_identity()).unwrap();
+        let recipient = Keyring::from_keys(&identity.recipients()[0]).unwrap();
+        let mut context = Context::from_args([
+            "--set",
+            "s.port=8080",
+            "--set",
+            "s.ratio=-0.5",
+            "--set",
+            "s.tls=true",
+            "--set",
+            "s.name=\"a, b]\"",
+            "--set",
+            "s.none=null",
+            "--set",
+            "s.offset=-3",
+        ])
+        .unwrap();
+        let original = context.clone();
+
+        assert_eq!(context.encrypt_paths(&recipient, &["s"]).unwrap(), 6);
+        assert_eq!(context.encrypt_paths(&recipient, &["s"]).unwrap(), 0);
+        assert!(matches!(
+            context.decrypt(&recipient),
+            Err(Error::Encryption(_))
+        ));
+        assert_eq!(context.decrypt(&identity).unwrap(), 6);
+        assert!(context.diff(&original).is_empty());
+    }
+
+    #[test]
+    fn test_envelopes_are_bound_to_their_path() {
+        let keyring = Keyring::from_keys(&Keyring::generate_symmetric_key()).unwrap();
+        let mut context = Context::from_args(["--set", "a=1", "--set", "b=2"]).unwrap();
+        context.encrypt_paths(&keyring, &["*"]).unwrap();
+        let a = context.get("a").unwrap().clone();
+        context.insert("b".to_string(), a);
+
+        assert!(matches!(
+            context.decrypt(&keyring),
+            Err(Error::Encryption(_))
+        ));
+        assert!(matches!(
+            Keyring::from_keys("OXIDEX-SYMMETRIC-KEY-AAAA"),
+            Err(Error::Encryption(_))
+        ));
+    }
+}
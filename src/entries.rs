@@ -0,0 +1,634 @@
+use crate::diff::key_segment;
+use crate::path::{resolve_key, Segment};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_value::Value;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// The items of a map or a sequence [`Node`], with their `Value` form once a reference to it has
+/// been lent.
+pub(crate) struct Branch<T> {
+    items: T,
+    value: OnceLock<Value>,
+}
+
+impl<T: Clone> Clone for Branch<T> {
+    /// Copies the items, which only copies the pointers to their nodes; the copy is about to be
+    /// modified, so the `Value` form is not copied.
+    fn clone(&self) -> Self {
+        Branch {
+            items: self.items.clone(),
+            value: OnceLock::new(),
+        }
+    }
+}
+
+impl<T> Branch<T> {
+    fn new(items: T) -> Arc<Branch<T>> {
+        Arc::new(Branch {
+            items,
+            value: OnceLock::new(),
+        })
+    }
+
+    /// Returns the items of a branch to modify, copying it first if it is shared.
+    fn items_mut(branch: &mut Arc<Branch<T>>) -> &mut T
+    where
+        T: Clone,
+    {
+        let branch = Arc::make_mut(branch);
+        branch.value.take();
+        &mut branch.items
+    }
+}
+
+/// A value stored in a `Context`.
+///
+/// Maps and sequences are shared between clones node by node: modifying a nested value copies
+/// the nodes on its path (one pointer per item) and leaves every other node shared. Their `Value`
+/// form is only built and kept by the accessors lending a `&Value` of a map or a sequence, such as
+/// [`Context::get`](crate::Context::get); everything else works on the nodes.
+#[derive(Clone)]
+pub(crate) enum Node {
+    /// A scalar, or a value wrapped in an option or a newtype.
+    Leaf(Value),
+    /// A map.
+    Map(Arc<Branch<BTreeMap<Value, Node>>>),
+    /// A sequence.
+    Seq(Arc<Branch<Vec<Node>>>),
+}
+
+impl From<Value> for Node {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Map(map) => Node::Map(Branch::new(
+                map.into_iter()
+                    .map(|(key, value)| (key, Node::from(value)))
+                    .collect(),
+            )),
+            Value::Seq(seq) => Node::Seq(Branch::new(seq.into_iter().map(Node::from).collect())),
+            value => Node::Leaf(value),
+        }
+    }
+}
+
+impl From<Node> for Value {
+    /// Moves the items out of the nodes that are not shared, and copies the others.
+    fn from(node: Node) -> Self {
+        match node {
+            Node::Leaf(value) => value,
+            Node::Map(branch) => match Arc::try_unwrap(branch) {
+                Ok(Branch { items, value }) => value.into_inner().unwrap_or_else(|| {
+                    Value::Map(
+                        items
+                            .into_iter()
+                            .map(|(key, node)| (key, Value::from(node)))
+                            .collect(),
+                    )
+                }),
+                Err(branch) => Node::Map(branch).to_value(),
+            },
+            Node::Seq(branch) => match Arc::try_unwrap(branch) {
+                Ok(Branch { items, value }) => value
+                    .into_inner()
+                    .unwrap_or_else(|| Value::Seq(items.into_iter().map(Value::from).collect())),
+                Err(branch) => Node::Seq(branch).to_value(),
+            },
+        }
+    }
+}
+
+impl FromIterator<(Value, Node)> for Node {
+    fn from_iter<I: IntoIterator<Item = (Value, Node)>>(iter: I) -> Self {
+        Node::Map(Branch::new(iter.into_iter().collect()))
+    }
+}
+
+impl Node {
+    /// Returns the `Value` form of the node, kept to lend it.
+    pub(crate) fn value(&self) -> &Value {
+        match self {
+            Node::Leaf(value) => value,
+            Node::Map(branch) => branch.value.get_or_init(|| self.to_value()),
+            Node::Seq(branch) => branch.value.get_or_init(|| self.to_value()),
+        }
+    }
+
+    /// Returns the `Value` form of the node without keeping it: borrowed from a leaf, or built.
+    pub(crate) fn as_value(&self) -> Cow<'_, Value> {
+        match self {
+            Node::Leaf(value) => Cow::Borrowed(value),
+            Node::Map(branch) => branch
+                .value
+                .get()
+                .map_or_else(|| Cow::Owned(self.to_value()), Cow::Borrowed),
+            Node::Seq(branch) => branch
+                .value
+                .get()
+                .map_or_else(|| Cow::Owned(self.to_value()), Cow::Borrowed),
+        }
+    }
+
+    /// Returns a copy of the `Value` form of the node, without keeping it.
+    pub(crate) fn to_value(&self) -> Value {
+        match self {
+            Node::Leaf(value) => value.clone(),
+            Node::Map(branch) => branch.value.get().cloned().unwrap_or_else(|| {
+                Value::Map(
+                    branch
+                        .items
+                        .iter()
+                        .map(|(key, node)| (key.clone(), node.to_value()))
+                        .collect(),
+                )
+            }),
+            Node::Seq(branch) => {
+                branch.value.get().cloned().unwrap_or_else(|| {
+                    Value::Seq(branch.items.iter().map(Node::to_value).collect())
+                })
+            }
+        }
+    }
+
+    /// Returns the `Value` form of the node to modify it in place.
+    ///
+    /// A map or a sequence is turned into a leaf first: its nodes stop being shared.
+    pub(crate) fn value_mut(&mut self) -> &mut Value {
+        if !matches!(self, Node::Leaf(_)) {
+            let node = std::mem::replace(self, Node::Leaf(Value::Unit));
+            *self = Node::Leaf(node.into());
+        }
+        match self {
+            Node::Leaf(value) => value,
+            _ => unreachable!("the node was just turned into a leaf"),
+        }
+    }
+
+    /// Returns `true` if both nodes are the same shared map or sequence.
+    pub(crate) fn ptr_eq(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Node::Map(a), Node::Map(b)) => Arc::ptr_eq(a, b),
+            (Node::Seq(a), Node::Seq(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if both nodes are the same shared map or sequence, or equal leaves.
+    pub(crate) fn same(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Node::Leaf(a), Node::Leaf(b)) => a == b,
+            (a, b) => a.ptr_eq(b),
+        }
+    }
+
+    /// Returns `true` if the node is a null leaf.
+    pub(crate) fn is_null(&self) -> bool {
+        matches!(self, Node::Leaf(value) if crate::value::is_null(value))
+    }
+
+    /// Returns the items of a map node.
+    pub(crate) fn map(&self) -> Option<&BTreeMap<Value, Node>> {
+        match self {
+            Node::Map(branch) => Some(&branch.items),
+            _ => None,
+        }
+    }
+
+    /// Returns the items of a map node to modify, copying the node first if it is shared.
+    pub(crate) fn map_mut(&mut self) -> Option<&mut BTreeMap<Value, Node>> {
+        match self {
+            Node::Map(branch) => Some(Branch::items_mut(branch)),
+            _ => None,
+        }
+    }
+
+    /// Returns the items of a sequence node.
+    pub(crate) fn seq(&self) -> Option<&[Node]> {
+        match self {
+            Node::Seq(branch) => Some(&branch.items),
+            _ => None,
+        }
+    }
+
+    /// Returns the items of a sequence node to modify, copying the node first if it is shared.
+    pub(crate) fn seq_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self {
+            Node::Seq(branch) => Some(Branch::items_mut(branch)),
+            _ => None,
+        }
+    }
+
+    /// Returns the item of a map or a sequence node designated by `segment`.
+    pub(crate) fn child(&self, segment: &Segment) -> Option<&Node> {
+        match self {
+            Node::Leaf(_) => None,
+            Node::Map(branch) => branch.items.get(&resolve_key(&branch.items, segment)),
+            Node::Seq(branch) => branch.items.get(seq_index(segment)?),
+        }
+    }
+
+    /// Calls `f` on a copy of the item stored under `key` in a map node, and stores the copy back
+    /// if `f` changed it: this node is only copied if one of its items changes.
+    pub(crate) fn update_entry<T>(
+        &mut self,
+        key: &Value,
+        f: impl FnOnce(&mut Node) -> T,
+    ) -> Option<T> {
+        let item = self.map()?.get(key)?;
+        let mut copy = item.clone();
+        let result = f(&mut copy);
+        if !copy.same(item) {
+            self.map_mut()?.insert(key.clone(), copy);
+        }
+        Some(result)
+    }
+
+    /// Calls `f` on a copy of the item at `index` in a sequence node, and stores the copy back if
+    /// `f` changed it: this node is only copied if one of its items changes.
+    pub(crate) fn update_index<T>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut Node) -> T,
+    ) -> Option<T> {
+        let item = self.seq()?.get(index)?;
+        let mut copy = item.clone();
+        let result = f(&mut copy);
+        if !copy.same(item) {
+            self.seq_mut()?[index] = copy;
+        }
+        Some(result)
+    }
+
+    /// Returns the items of a map or a sequence node, along with their segments.
+    pub(crate) fn items(&self) -> Vec<(Segment, &Node)> {
+        match self {
+            Node::Leaf(_) => Vec::new(),
+            Node::Map(branch) => branch
+                .items
+                .iter()
+                .map(|(key, node)| (key_segment(key), node))
+                .collect(),
+            Node::Seq(branch) => branch
+                .items
+                .iter()
+                .enumerate()
+                .map(|(index, node)| (Segment::Index(index), node))
+                .collect(),
+        }
+    }
+
+    /// Calls `f` on a copy of each item of a map or a sequence node, along with its segment, and
+    /// stores back the copies `f` changed (see [`Node::update_entry`]).
+    pub(crate) fn update_items(&mut self, mut f: impl FnMut(Segment, &mut Node)) {
+        if let Some(map) = self.map() {
+            for key in map.keys().cloned().collect::<Vec<_>>() {
+                self.update_entry(&key, |item| f(key_segment(&key), item));
+            }
+        } else if let Some(seq) = self.seq() {
+            for index in 0..seq.len() {
+                self.update_index(index, |item| f(Segment::Index(index), item));
+            }
+        }
+    }
+
+    /// Returns the item designated by `segment` to modify, copying this node first if it is
+    /// shared.
+    pub(crate) fn child_mut(&mut self, segment: &Segment) -> Option<&mut Node> {
+        self.child(segment)?;
+        match self {
+            Node::Leaf(_) => None,
+            Node::Map(branch) => {
+                let key = resolve_key(&branch.items, segment);
+                Branch::items_mut(branch).get_mut(&key)
+            }
+            Node::Seq(branch) => Branch::items_mut(branch).get_mut(seq_index(segment)?),
+        }
+    }
+}
+
+/// Returns the position designating `segment` in a sequence.
+fn seq_index(segment: &Segment) -> Option<usize> {
+    match segment {
+        Segment::Index(index) => Some(*index),
+        Segment::Key(key) => key.parse().ok(),
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        match (self, other) {
+            (Node::Leaf(a), Node::Leaf(b)) => a == b,
+            (Node::Map(a), Node::Map(b)) => Arc::ptr_eq(a, b) || a.items == b.items,
+            (Node::Seq(a), Node::Seq(b)) => Arc::ptr_eq(a, b) || a.items == b.items,
+            // A leaf may hold a map or a sequence, as left by a merge.
+            (a @ Node::Leaf(_), b) | (a, b @ Node::Leaf(_)) => a.as_value() == b.as_value(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Node {
+    /// Formats the node like its `Value` form.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Leaf(value) => value.fmt(f),
+            Node::Map(branch) => f.debug_tuple("Map").field(&branch.items).finish(),
+            Node::Seq(branch) => f.debug_tuple("Seq").field(&branch.items).finish(),
+        }
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Node::Leaf(value) => value.serialize(serializer),
+            Node::Map(branch) => serializer.collect_map(&branch.items),
+            Node::Seq(branch) => serializer.collect_seq(&branch.items),
+        }
+    }
+}
+
+/// The copy-on-write map of the top-level entries of a `Context`.
+///
+/// The map and each of its values are shared between clones, down to the items of nested maps
+/// and sequences (see [`Node`]): cloning is O(1), and a modification only copies the map of
+/// top-level entries and the nodes on the path to the modified value.
+#[derive(Clone, Default, PartialEq)]
+pub(crate) struct Entries(Arc<BTreeMap<String, Node>>);
+
+impl Entries {
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key).map(Node::value)
+    }
+
+    pub(crate) fn node(&self, key: &str) -> Option<&Node> {
+        self.0.get(key)
+    }
+
+    /// Returns the node of `key` to modify, copying the map of entries first if it is shared.
+    pub(crate) fn node_mut(&mut self, key: &str) -> Option<&mut Node> {
+        if !self.0.contains_key(key) {
+            return None;
+        }
+        Arc::make_mut(&mut self.0).get_mut(key)
+    }
+
+    pub(crate) fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    pub(crate) fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        self.insert_node(key, Node::from(value)).map(Value::from)
+    }
+
+    pub(crate) fn insert_node(&mut self, key: String, node: Node) -> Option<Node> {
+        Arc::make_mut(&mut self.0).insert(key, node)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<Value> {
+        if !self.0.contains_key(key) {
+            return None;
+        }
+        Arc::make_mut(&mut self.0).remove(key).map(Value::from)
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, Cow<'_, Value>)> {
+        self.into_iter()
+    }
+
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = (&String, &Node)> {
+        self.0.iter()
+    }
+
+    /// Returns `true` if both maps share the nested map or sequence at `path`, for tests.
+    #[cfg(test)]
+    pub(crate) fn shares(&self, other: &Entries, path: &str) -> bool {
+        let path: crate::Path = path.parse().unwrap();
+        let (first, rest) = path.segments().split_first().unwrap();
+        let Segment::Key(key) = first else {
+            return false;
+        };
+        fn find<'a>(entries: &'a Entries, key: &str, rest: &[Segment]) -> Option<&'a Node> {
+            rest.iter()
+                .try_fold(entries.0.get(key)?, |node, segment| node.child(segment))
+        }
+        match (find(self, key, rest), find(other, key, rest)) {
+            (Some(node), Some(other)) => node.ptr_eq(other),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Entries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.nodes()).finish()
+    }
+}
+
+impl FromIterator<(String, Value)> for Entries {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Self {
+        Entries(Arc::new(
+            iter.into_iter()
+                .map(|(key, value)| (key, Node::from(value)))
+                .collect(),
+        ))
+    }
+}
+
+impl Extend<(String, Value)> for Entries {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        Arc::make_mut(&mut self.0).extend(
+            iter.into_iter()
+                .map(|(key, value)| (key, Node::from(value))),
+        );
+    }
+}
+
+impl IntoIterator for Entries {
+    type Item = (String, Value);
+    type IntoIter = std::iter::Map<
+        std::collections::btree_map::IntoIter<String, Node>,
+        fn((String, Node)) -> (String, Value),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Arc::unwrap_or_clone(self.0)
+            .into_iter()
+            .map(|(key, node)| (key, Value::from(node)))
+    }
+}
+
+impl<'a> IntoIterator for &'a Entries {
+    type Item = (&'a String, Cow<'a, Value>);
+    type IntoIter = std::iter::Map<
+        std::collections::btree_map::Iter<'a, String, Node>,
+        fn((&'a String, &'a Node)) -> (&'a String, Cow<'a, Value>),
+    >;
+
+    /// Iterates over the entries without keeping the `Value` form of their nodes.
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter().map(|(key, node)| (key, node.as_value()))
+    }
+}
+
+impl From<Entries> for BTreeMap<String, Value> {
+    fn from(entries: Entries) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl Serialize for Entries {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.nodes())
+    }
+}
+
+impl<'de> Deserialize<'de> for Entries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(BTreeMap::<String, Value>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Node;
+    use crate::{Context, Op, StringFunction, Transform, TransformStep};
+    use serde_value::Value;
+
+    /// Returns `true` if the `Value` form of the map or sequence under `key` was built.
+    fn is_cached(context: &Context, key: &str) -> bool {
+        match context.inner.node(key) {
+            Some(Node::Map(branch)) => branch.value.get().is_some(),
+            Some(Node::Seq(branch)) => branch.value.get().is_some(),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_clones_share_untouched_values() {
+        let global = Context::from_args([
+            "--set",
+            "db.host=h",
+            "--set",
+            "db.pool.size=4",
+            "--set",
+            "db.replicas[0].host=r",
+            "--set",
+            "cache.size=1",
+        ])
+        .unwrap();
+        let mut request = global.clone();
+        request
+            .set_path("db.user", Value::String("u".to_string()))
+            .unwrap();
+        request
+            .set_path("db.replicas[1].host", Value::String("s".to_string()))
+            .unwrap();
+
+        assert!(request.inner.shares(&global.inner, "cache"));
+        assert!(request.inner.shares(&global.inner, "db.pool"));
+        assert!(request.inner.shares(&global.inner, "db.replicas[0]"));
+        assert!(!request.inner.shares(&global.inner, "db"));
+        assert!(!request.inner.shares(&global.inner, "db.replicas"));
+        assert!(global.get_path("db.user").is_none());
+        assert!(global.get_path("db.replicas[1]").is_none());
+        assert_eq!(request.get_path("db.host"), global.get_path("db.host"));
+
+        request.remove_path("db.pool.size").unwrap();
+        assert!(request.inner.shares(&global.inner, "db.replicas[0]"));
+        assert_eq!(global.get_path("db.pool.size"), Some(&Value::U64(4)));
+
+        let mut merged = global.clone();
+        merged.merge(Context::from_args(["--set", "db.pool.size=8"]).unwrap());
+        merged.apply_merge_patch(&Context::from_args(["--set", "db.host=null"]).unwrap());
+        assert!(merged.inner.shares(&global.inner, "db.replicas"));
+        assert!(!merged.inner.shares(&global.inner, "db.pool"));
+        assert!(global.diff(&merged).len() == 2);
+    }
+
+    #[test]
+    fn test_sharing_survives_patches_and_transforms() {
+        let string = |text: &str| Value::String(text.to_string());
+        let mut global = Context::from_args([
+            "--set",
+            "db.host=h",
+            "--set",
+            "db.pool.size=4",
+            "--set",
+            "cache.size=1",
+            "--set",
+            "tags[0]=a",
+        ])
+        .unwrap();
+        global.mark_secret("db.pool.size");
+
+        let mut patched = global.clone();
+        patched
+            .apply_json_patch(&[
+                Op::Replace {
+                    path: "db.host".parse().unwrap(),
+                    value: string("p"),
+                },
+                Op::Add {
+                    path: "tags[1]".parse().unwrap(),
+                    value: string("b"),
+                },
+                Op::Test {
+                    path: "cache".parse().unwrap(),
+                    value: Context::from_args(["--set", "size=1"]).unwrap().to_value(),
+                },
+            ])
+            .unwrap();
+        assert!(patched.inner.shares(&global.inner, "cache"));
+        assert!(patched.inner.shares(&global.inner, "db.pool"));
+        assert!(!patched.inner.shares(&global.inner, "db"));
+
+        let mut transformed = global.clone();
+        let transform = Transform::new()
+            .then(TransformStep::Rename {
+                path: "db.host".to_string(),
+                to: "hostname".to_string(),
+            })
+            .then(TransformStep::MapStrings {
+                path: "tags".to_string(),
+                function: StringFunction::Uppercase,
+            })
+            .then(TransformStep::Drop {
+                pattern: "**.missing".to_string(),
+            });
+        transformed.apply_transform(&transform).unwrap();
+        assert!(transformed.inner.shares(&global.inner, "cache"));
+        assert!(transformed.inner.shares(&global.inner, "db.pool"));
+        assert_eq!(transformed.get_path("tags[0]"), Some(&string("A")));
+
+        let mut defaults = Context::new();
+        defaults.set_path("db.port", Value::U16(5432)).unwrap();
+        defaults.set_path("cache.size", Value::U64(2)).unwrap();
+        let mut filled = global.clone();
+        filled.apply_defaults(&defaults);
+        assert!(filled.inner.shares(&global.inner, "cache"));
+        assert!(filled.inner.shares(&global.inner, "db.pool"));
+        assert_eq!(filled.get_path("db.port"), Some(&Value::U16(5432)));
+
+        let redacted = global.redacted();
+        assert!(redacted.inner.shares(&global.inner, "cache"));
+        assert!(redacted.inner.shares(&global.inner, "tags"));
+        assert!(!redacted.inner.shares(&global.inner, "db.pool"));
+
+        // Comparing and printing do not build the `Value` form of the branches.
+        assert!(filled.inner != global.inner && !format!("{:?}", global).is_empty());
+        assert!(["db", "cache", "tags"]
+            .iter()
+            .all(|key| !is_cached(&global, key)));
+        global.get("db");
+        assert!(is_cached(&global, "db"));
+    }
+}
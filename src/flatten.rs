@@ -0,0 +1,354 @@
+use crate::diff::key_segment;
+use crate::path::{insert, Segment};
+use crate::value::normalize_sequences;
+use crate::{Context, Error};
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// How sequence indices are written in flattened keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexStyle {
+    /// Indices are separated like keys: `servers.0.host`.
+    #[default]
+    Dotted,
+    /// Indices are written between brackets: `servers[0].host`.
+    Bracketed,
+}
+
+/// Options controlling how a `Context` is flattened into, and rebuilt from, single-level keys.
+///
+/// The default options join keys with `.`, write indices as keys (`a.0.b`) and do not escape
+/// anything.
+///
+/// # Example
+/// ```rust
+/// let options = oxidex::FlattenOptions {
+///     indices: oxidex::IndexStyle::Bracketed,
+///     escape: Some('\\'),
+///     ..oxidex::FlattenOptions::new("__")
+/// };
+/// assert_eq!(options.separator, "__");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlattenOptions {
+    /// The separator between keys (`.` by default).
+    pub separator: String,
+    /// How sequence indices are written.
+    pub indices: IndexStyle,
+    /// The character escaping separators (and itself) inside keys. Keys are written verbatim
+    /// when `None`, so a key holding the separator does not survive a round trip.
+    pub escape: Option<char>,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions {
+            separator: ".".to_string(),
+            indices: IndexStyle::Dotted,
+            escape: None,
+        }
+    }
+}
+
+impl FlattenOptions {
+    /// Creates options using `separator` between keys, keeping all other defaults.
+    pub fn new(separator: &str) -> FlattenOptions {
+        FlattenOptions {
+            separator: separator.to_string(),
+            ..FlattenOptions::default()
+        }
+    }
+
+    /// Escapes the separator, the escape character and, with bracketed indices, `[` in a key.
+    fn escape_key(&self, key: &str) -> String {
+        let Some(escape) = self.escape else {
+            return key.to_string();
+        };
+        let mut escaped = String::with_capacity(key.len());
+        let mut rest = key;
+        while let Some(c) = rest.chars().next() {
+            if c == escape || (c == '[' && self.indices == IndexStyle::Bracketed) {
+                escaped.push(escape);
+                escaped.push(c);
+                rest = &rest[c.len_utf8()..];
+            } else if !self.separator.is_empty() && rest.starts_with(&self.separator) {
+                escaped.push(escape);
+                escaped.push_str(&self.separator);
+                rest = &rest[self.separator.len()..];
+            } else {
+                escaped.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        escaped
+    }
+
+    /// Joins the location of a value into a flattened key.
+    fn join(&self, segments: &[Segment]) -> String {
+        let mut key = String::new();
+        for (position, segment) in segments.iter().enumerate() {
+            match (segment, self.indices) {
+                (Segment::Index(index), IndexStyle::Bracketed) => {
+                    key.push_str(&format!("[{}]", index))
+                }
+                (segment, _) => {
+                    if position > 0 {
+                        key.push_str(&self.separator);
+                    }
+                    match segment {
+                        Segment::Key(name) => key.push_str(&self.escape_key(name)),
+                        Segment::Index(index) => key.push_str(&index.to_string()),
+                    }
+                }
+            }
+        }
+        key
+    }
+
+    /// Splits a flattened key into the location it designates. Indices are returned as keys and
+    /// turned into sequences once every key is inserted.
+    fn split(&self, key: &str) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut current = String::new();
+        let mut rest = key;
+        while let Some(c) = rest.chars().next() {
+            if Some(c) == self.escape && rest.len() > c.len_utf8() {
+                rest = &rest[c.len_utf8()..];
+                let next = match rest.starts_with(&self.separator) {
+                    true => self.separator.len(),
+                    false => rest.chars().next().map_or(0, char::len_utf8),
+                };
+                current.push_str(&rest[..next]);
+                rest = &rest[next..];
+            } else if rest.starts_with(&self.separator) {
+                segments.push(Segment::Key(std::mem::take(&mut current)));
+                rest = &rest[self.separator.len()..];
+            } else if let Some(index) = self.bracketed_index(rest) {
+                if !current.is_empty() {
+                    segments.push(Segment::Key(std::mem::take(&mut current)));
+                }
+                segments.push(Segment::Key(index.to_string()));
+                rest = &rest[index.len() + 2..];
+                // The separator following an index only closes it.
+                rest = rest.strip_prefix(self.separator.as_str()).unwrap_or(rest);
+                if rest.is_empty() {
+                    return segments;
+                }
+            } else {
+                current.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        segments.push(Segment::Key(current));
+        segments
+    }
+
+    /// Returns the digits of a `[n]` index starting `text`, with bracketed indices only.
+    fn bracketed_index<'a>(&self, text: &'a str) -> Option<&'a str> {
+        if self.indices != IndexStyle::Bracketed {
+            return None;
+        }
+        let digits = &text.strip_prefix('[')?[..text[1..].find(']')?];
+        (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())).then_some(digits)
+    }
+}
+
+/// Flattens the maps and sequences of `value`, located at `segments`, into `entries`, in
+/// depth-first order. Empty maps and sequences are kept as leaves so that they survive a round
+/// trip.
+pub(crate) fn flatten_value(
+    segments: &mut Vec<Segment>,
+    value: &Value,
+    options: &FlattenOptions,
+    entries: &mut Vec<(String, Value)>,
+) {
+    match value {
+        Value::Map(map) if !map.is_empty() => {
+            for (key, value) in map {
+                segments.push(key_segment(key));
+                flatten_value(segments, value, options, entries);
+                segments.pop();
+            }
+        }
+        Value::Seq(seq) if !seq.is_empty() => {
+            for (index, value) in seq.iter().enumerate() {
+                segments.push(Segment::Index(index));
+                flatten_value(segments, value, options, entries);
+                segments.pop();
+            }
+        }
+        Value::Option(Some(inner)) | Value::Newtype(inner) => {
+            flatten_value(segments, inner, options, entries)
+        }
+        value => entries.push((options.join(segments), value.clone())),
+    }
+}
+
+/// Rebuilds nested maps from flattened keys; maps indexed `0..n` become sequences.
+pub(crate) fn unflatten_value<I, K>(entries: I, options: &FlattenOptions) -> crate::Result<Value>
+where
+    I: IntoIterator<Item = (K, Value)>,
+    K: AsRef<str>,
+{
+    if options.separator.is_empty() {
+        return Err(Error::Path("the separator cannot be empty".to_string()));
+    }
+    let mut root = Value::Map(BTreeMap::new());
+    for (key, value) in entries {
+        let key = key.as_ref();
+        insert(&mut root, &options.split(key), value)
+            .map_err(|err| Error::Path(format!("cannot unflatten '{}': {}", key, err)))?;
+    }
+    Ok(normalize_sequences(root))
+}
+
+impl Context {
+    /// Flattens the `Context` into a single-level map whose keys join the nested keys with
+    /// `separator`, sequence indices included (`servers.0.host`).
+    ///
+    /// `separator`: The separator between keys.
+    ///
+    /// Example:
+    /// ```
+    /// let context = oxidex::Context::from_args(["--set", "db.hosts[0]=a", "--set", "db.port=5432"]).unwrap();
+    ///
+    /// let flat = context.flatten("_");
+    /// assert_eq!(flat.get("db_hosts_0").unwrap(), &serde_value::Value::String("a".to_string()));
+    /// assert_eq!(flat.get("db_port").unwrap(), &serde_value::Value::U64(5432));
+    /// ```
+    pub fn flatten(&self, separator: &str) -> Context {
+        self.flatten_with(&FlattenOptions::new(separator))
+    }
+
+    /// Flattens the `Context` into a single-level map, as described by `options`.
+    ///
+    /// `options`: How keys are joined, escaped and indexed.
+    ///
+    /// Example:
+    /// ```
+    /// use oxidex::{Context, FlattenOptions, IndexStyle};
+    /// use serde_value::Value;
+    ///
+    /// let mut context = Context::new();
+    /// context.set_path("servers[0]", Value::Map(
+    ///     [(Value::String("os.name".to_string()), Value::String("linux".to_string()))].into(),
+    /// )).unwrap();
+    ///
+    /// let options = FlattenOptions { indices: IndexStyle::Bracketed, escape: Some('\\'), ..FlattenOptions::default() };
+    /// let flat = context.flatten_with(&options);
+    /// assert!(flat.get(r"servers[0].os\.name").is_some());
+    /// assert!(flat.unflatten_with(&options).unwrap().diff(&context).is_empty());
+    /// ```
+    pub fn flatten_with(&self, options: &FlattenOptions) -> Context {
+        let mut entries = Vec::new();
+        for (key, value) in &self.inner {
+            flatten_value(
+                &mut vec![Segment::Key(key.clone())],
+                &value,
+                options,
+                &mut entries,
+            );
+        }
+        let mut context = Context::new();
+        context.inner.extend(entries);
+        context
+    }
+
+    /// Rebuilds nested maps and sequences from keys joined with `separator`, reversing
+    /// [`Context::flatten`]. Maps whose keys are exactly `0..n` become sequences.
+    ///
+    /// `separator`: The separator between keys.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the separator is empty or if a key designates a value
+    ///   inside another one (`a=1` and `a.b=2`).
+    ///
+    /// Example:
+    /// ```
+    /// let mut flat = oxidex::Context::new();
+    /// flat.insert("db.hosts.0".to_string(), serde_value::Value::String("a".to_string()));
+    /// flat.insert("db.port".to_string(), serde_value::Value::U64(5432));
+    ///
+    /// let context = flat.unflatten(".").unwrap();
+    /// assert_eq!(context.get_path("db.hosts[0]").unwrap(), &serde_value::Value::String("a".to_string()));
+    /// ```
+    pub fn unflatten(&self, separator: &str) -> crate::Result<Context> {
+        self.unflatten_with(&FlattenOptions::new(separator))
+    }
+
+    /// Rebuilds nested maps and sequences from single-level keys, as described by `options`.
+    ///
+    /// `options`: How keys are joined, escaped and indexed.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the separator is empty or if a key designates a value
+    ///   inside another one.
+    pub fn unflatten_with(&self, options: &FlattenOptions) -> crate::Result<Context> {
+        let entries = self.inner.iter().map(|(key, value)| (key, value.into_owned()));
+        Context::from_value(unflatten_value(entries, options)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_join() {
+        let options = FlattenOptions {
+            indices: IndexStyle::Bracketed,
+            escape: Some('\\'),
+            ..FlattenOptions::new("::")
+        };
+        let segments = vec![
+            Segment::Key("a::b".to_string()),
+            Segment::Index(10),
+            Segment::Index(2),
+            Segment::Key(r"c\[d]".to_string()),
+        ];
+        let key = options.join(&segments);
+        assert_eq!(key, r"a\::b[10][2]::c\\\[d]");
+        assert_eq!(
+            options.split(&key),
+            vec![
+                Segment::Key("a::b".to_string()),
+                Segment::Key("10".to_string()),
+                Segment::Key("2".to_string()),
+                Segment::Key(r"c\[d]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_keeps_order_and_empty_containers() {
+        let mut context = Context::new();
+        for index in 0..12 {
+            context
+                .set_path(&format!("list[{}]", index), Value::U64(index as u64))
+                .unwrap();
+        }
+        context
+            .set_path("empty", Value::Map(BTreeMap::new()))
+            .unwrap();
+
+        let mut entries = Vec::new();
+        flatten_value(
+            &mut Vec::new(),
+            &context.to_value(),
+            &FlattenOptions::default(),
+            &mut entries,
+        );
+        assert_eq!(entries[2].0, "list.1");
+        assert_eq!(entries[11].0, "list.10");
+
+        let flat = context.flatten(".");
+        assert!(flat.unflatten(".").unwrap().diff(&context).is_empty());
+        assert!(matches!(
+            Context::from_args(["--set", "a=1"])
+                .unwrap()
+                .flatten(".")
+                .unflatten(""),
+            Err(Error::Path(_))
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{Context, Op, Transform};
+use serde_value::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Deref;
+
+/// A saved state of a `Context`, as returned by [`Context::checkpoint`].
+///
+/// Since clones of a `Context` share their values, a checkpoint costs O(1) to take and only keeps
+/// alive the values modified after it.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    context: Context,
+}
+
+impl Context {
+    /// Saves the current state of the `Context`, to restore it with [`Context::rollback_to`].
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "db.port=5432"]).unwrap();
+    /// let checkpoint = context.checkpoint();
+    ///
+    /// context.set_path("db.port", serde_value::Value::U64(6432)).unwrap();
+    /// context.set_path("db.user", serde_value::Value::String("admin".to_string())).unwrap();
+    /// context.rollback_to(&checkpoint);
+    ///
+    /// assert_eq!(context.get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+    /// assert!(context.get_path("db.user").is_none());
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            context: self.clone(),
+        }
+    }
+
+    /// Restores the state saved by [`Context::checkpoint`], secret marks included.
+    ///
+    /// `checkpoint`: The state to restore.
+    pub fn rollback_to(&mut self, checkpoint: &Checkpoint) {
+        self.audited(|context| *context = checkpoint.context.clone())
+    }
+}
+
+/// A recorded mutation, as the JSON Patches undoing and redoing it.
+#[derive(Debug, Clone)]
+struct Edit {
+    undo: Vec<Op>,
+    redo: Vec<Op>,
+}
+
+/// A `Context` recording its mutations in a bounded undo/redo history.
+///
+/// Each mutation is recorded as the pair of JSON Patches undoing and redoing it, so the history
+/// grows with the size of the edits rather than with the size of the `Context`. Once the history
+/// holds `limit` edits, the oldest one is forgotten; a new edit discards the edits that were
+/// undone. Reads go through `Deref` to the wrapped `Context`.
+///
+/// Example:
+/// ```
+/// let mut context = oxidex::UndoableContext::new(oxidex::Context::new(), 100);
+/// context.set_path("db.host", serde_value::Value::String("localhost".to_string())).unwrap();
+/// context.set_path("db.port", serde_value::Value::U64(5432)).unwrap();
+///
+/// assert!(context.undo().unwrap());
+/// assert!(context.get_path("db.port").is_none());
+/// assert!(context.redo().unwrap());
+/// assert_eq!(context.get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+/// assert!(!context.redo().unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct UndoableContext {
+    context: Context,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    limit: usize,
+}
+
+impl Deref for UndoableContext {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl UndoableContext {
+    /// Wraps a `Context`, with an empty history.
+    ///
+    /// `context`: The initial state.
+    /// `limit`: The largest number of edits that can be undone.
+    pub fn new(context: Context, limit: usize) -> UndoableContext {
+        UndoableContext {
+            context,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Returns the wrapped `Context`, dropping the history.
+    pub fn into_inner(self) -> Context {
+        self.context
+    }
+
+    /// Returns the number of edits that can be undone.
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Returns the number of edits that can be redone.
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Forgets every recorded edit.
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn record(&mut self, old: &Context) {
+        let redo = old.json_patch_to(&self.context);
+        if redo.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        if self.limit == 0 {
+            return;
+        }
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(Edit {
+            undo: self.context.json_patch_to(old),
+            redo,
+        });
+    }
+
+    /// Applies several mutations as a single edit.
+    ///
+    /// `f`: The function modifying the `Context`.
+    pub fn transaction<T, F>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Context) -> T,
+    {
+        let old = self.context.clone();
+        let result = f(&mut self.context);
+        self.record(&old);
+        result
+    }
+
+    /// Applies several mutations as a single edit, if `f` succeeds.
+    ///
+    /// `f`: The function modifying the `Context`.
+    ///
+    /// # Errors
+    /// - Returns the error of `f`; the `Context` is restored and nothing is recorded then.
+    pub fn try_transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Context) -> Result<T, E>,
+    {
+        let old = self.context.clone();
+        let result = self.context.try_audited(f)?;
+        self.record(&old);
+        Ok(result)
+    }
+
+    /// Reverts the last edit.
+    ///
+    /// Returns `false` if there was nothing to undo.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Patch` variant if the recorded patch cannot be replayed; the `Context`
+    ///   and the history are left unchanged then.
+    pub fn undo(&mut self) -> crate::Result<bool> {
+        let Some(edit) = self.undo.back() else {
+            return Ok(false);
+        };
+        self.context.apply_json_patch(&edit.undo)?;
+        self.redo.extend(self.undo.pop_back());
+        Ok(true)
+    }
+
+    /// Applies the last undone edit again.
+    ///
+    /// Returns `false` if there was nothing to redo.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Patch` variant if the recorded patch cannot be replayed; the `Context`
+    ///   and the history are left unchanged then.
+    pub fn redo(&mut self) -> crate::Result<bool> {
+        let Some(edit) = self.redo.last() else {
+            return Ok(false);
+        };
+        self.context.apply_json_patch(&edit.redo)?;
+        self.undo.extend(self.redo.pop());
+        Ok(true)
+    }
+
+    /// Inserts a key-value pair, like [`Context::insert`].
+    pub fn insert(&mut self, k: String, v: Value) {
+        self.transaction(|context| context.insert(k, v))
+    }
+
+    /// Adds key-value pairs, like [`Context::extend`].
+    pub fn extend(&mut self, data: BTreeMap<String, Value>) {
+        self.transaction(|context| context.extend(data))
+    }
+
+    /// Sets the value at a path, like [`Context::set_path`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid or leads through a scalar value.
+    pub fn set_path(&mut self, path: &str, v: Value) -> crate::Result<Option<Value>> {
+        self.try_transaction(|context| context.set_path(path, v))
+    }
+
+    /// Removes the value at a path, like [`Context::remove_path`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid or designates the root.
+    pub fn remove_path(&mut self, path: &str) -> crate::Result<Option<Value>> {
+        self.try_transaction(|context| context.remove_path(path))
+    }
+
+    /// Merges another `Context`, like [`Context::merge`].
+    pub fn merge(&mut self, other: Context) {
+        self.transaction(|context| context.merge(other))
+    }
+
+    /// Applies a JSON Merge Patch, like [`Context::apply_merge_patch`].
+    pub fn apply_merge_patch(&mut self, patch: &Context) {
+        self.transaction(|context| context.apply_merge_patch(patch))
+    }
+
+    /// Applies a JSON Patch, like [`Context::apply_json_patch`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Patch` variant if an operation fails; nothing is recorded then.
+    pub fn apply_json_patch(&mut self, patch: &[Op]) -> crate::Result<()> {
+        self.try_transaction(|context| context.apply_json_patch(patch))
+    }
+
+    /// Applies a transform, like [`Context::apply_transform`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Transform` variant if a step fails; nothing is recorded then.
+    pub fn apply_transform(&mut self, transform: &Transform) -> crate::Result<()> {
+        self.try_transaction(|context| context.apply_transform(transform))
+    }
+
+    /// Restores a checkpoint, like [`Context::rollback_to`], as an edit that can be undone.
+    pub fn rollback_to(&mut self, checkpoint: &Checkpoint) {
+        self.transaction(|context| context.rollback_to(checkpoint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_bounded_and_replays_edits() {
+        let mut context = UndoableContext::new(
+            Context::from_args(["--set", "tags[0]=a", "--set", "tags[1]=b"]).unwrap(),
+            2,
+        );
+        let start = context.checkpoint();
+        context.remove_path("tags[0]").unwrap();
+        context.merge(Context::from_args(["--set", "db.port=1"]).unwrap());
+        context.insert("debug".to_string(), Value::Bool(true));
+        let end = context.checkpoint();
+        assert!(context.set_path("debug.x", Value::Unit).is_err());
+        assert_eq!(context.undo_len(), 2);
+
+        assert!(context.undo().unwrap() && context.undo().unwrap() && !context.undo().unwrap());
+        assert_eq!(
+            context.get_path("tags[0]").unwrap(),
+            &Value::String("b".to_string())
+        );
+        assert!(context.get("db").is_none());
+        assert!(context.redo().unwrap() && context.redo().unwrap() && !context.redo().unwrap());
+        assert!(context.diff(&end.context).is_empty());
+
+        context.rollback_to(&start);
+        assert!(context.diff(&start.context).is_empty());
+        assert_eq!(context.redo_len(), 0);
+        assert!(context.undo().unwrap());
+        assert!(context.diff(&end.context).is_empty());
+    }
+
+    #[test]
+    fn test_undo_with_non_string_keys() {
+        let mut m = BTreeMap::new();
+        m.insert(Value::U64(1), Value::String("a".to_string()));
+        m.insert(Value::U64(2), Value::String("b".to_string()));
+        let mut start = Context::new();
+        start.insert("m".to_string(), Value::Map(m.clone()));
+
+        let mut context = UndoableContext::new(start.clone(), 10);
+        context.transaction(|context| {
+            m.insert(Value::U64(3), Value::String("c".to_string()));
+            m.remove(&Value::U64(1));
+            context.insert("m".to_string(), Value::Map(m.clone()));
+        });
+        let end = context.checkpoint();
+        assert!(context.undo().unwrap());
+        assert_eq!(context.get("m"), start.get("m"));
+        assert!(context.redo().unwrap());
+        assert_eq!(context.get("m"), end.context.get("m"));
+    }
+}
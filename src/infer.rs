@@ -0,0 +1,211 @@
+use crate::schema::type_name;
+use crate::value::{equivalent, unwrap};
+use crate::Context;
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// The largest set of distinct strings inferred as an `enum`.
+const ENUM_LIMIT: usize = 5;
+
+/// The JSON types, in the order they are listed by an inferred `type` keyword.
+const TYPES: [&str; 7] = [
+    "null", "boolean", "integer", "number", "string", "array", "object",
+];
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+/// Infers the schema describing every value of `values`.
+fn infer(values: &[&Value]) -> Value {
+    let values: Vec<&Value> = values.iter().map(|value| unwrap(value)).collect();
+    let mut schema = BTreeMap::new();
+
+    let mut types: Vec<&str> = TYPES
+        .into_iter()
+        .filter(|name| values.iter().any(|value| type_name(value) == *name))
+        .collect();
+    if types.contains(&"number") {
+        types.retain(|name| *name != "integer");
+    }
+    match types.as_slice() {
+        [] => {}
+        [name] => {
+            schema.insert(string("type"), string(name));
+        }
+        names => {
+            schema.insert(
+                string("type"),
+                Value::Seq(names.iter().map(|name| string(name)).collect()),
+            );
+        }
+    }
+
+    let strings: Vec<&Value> = values
+        .iter()
+        .copied()
+        .filter(|value| matches!(value, Value::String(_)))
+        .collect();
+    let mut distinct: Vec<&Value> = Vec::new();
+    for value in &strings {
+        if !distinct.iter().any(|known| equivalent(known, value)) {
+            distinct.push(value);
+        }
+    }
+    // An enum is only inferred when values repeat, so that a single sample is not over-fitted.
+    if types == ["string"] && distinct.len() <= ENUM_LIMIT && distinct.len() < strings.len() {
+        distinct.sort();
+        schema.insert(
+            string("enum"),
+            Value::Seq(distinct.into_iter().cloned().collect()),
+        );
+    }
+
+    let items: Vec<&Value> = values
+        .iter()
+        .filter_map(|value| match value {
+            Value::Seq(items) => Some(items),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    if !items.is_empty() {
+        schema.insert(string("items"), infer(&items));
+    }
+
+    let objects: Vec<&BTreeMap<Value, Value>> = values
+        .iter()
+        .filter_map(|value| match value {
+            Value::Map(map) => Some(map),
+            _ => None,
+        })
+        .collect();
+    if !objects.is_empty() {
+        schema.extend(infer_object(&objects));
+    }
+    Value::Map(schema)
+}
+
+/// Infers the `properties` and `required` keywords describing every map of `objects`.
+fn infer_object(objects: &[&BTreeMap<Value, Value>]) -> BTreeMap<Value, Value> {
+    let mut values: BTreeMap<&Value, Vec<&Value>> = BTreeMap::new();
+    for object in objects {
+        for (key, value) in object.iter() {
+            values.entry(key).or_default().push(value);
+        }
+    }
+    let required: Vec<Value> = values
+        .iter()
+        .filter(|(_, found)| found.len() == objects.len())
+        .map(|(key, _)| (*key).clone())
+        .collect();
+    let properties: BTreeMap<Value, Value> = values
+        .into_iter()
+        .map(|(key, found)| (key.clone(), infer(&found)))
+        .collect();
+
+    let mut schema = BTreeMap::new();
+    if !properties.is_empty() {
+        schema.insert(string("properties"), Value::Map(properties));
+    }
+    if !required.is_empty() {
+        schema.insert(string("required"), Value::Seq(required));
+    }
+    schema
+}
+
+impl Context {
+    /// Infers a JSON Schema describing a set of sample contexts.
+    ///
+    /// Types are inferred from the observed values (integers widen to `number` when mixed with
+    /// floats), keys present in every sample are `required`, and strings taking at most 5 distinct
+    /// values, some of them repeated, become an `enum`. The returned schema can be exported to any
+    /// format or loaded with [`crate::Schema::from_context`].
+    ///
+    /// `samples`: The contexts to describe.
+    ///
+    /// Example:
+    /// ```
+    /// use oxidex::Context;
+    ///
+    /// let samples = [
+    ///     Context::from_args(["--set", "level=info", "--set", "port=80"]).unwrap(),
+    ///     Context::from_args(["--set", "level=debug", "--set", "port=8080", "--set", "tls=true"]).unwrap(),
+    ///     Context::from_args(["--set", "level=info", "--set", "port=443"]).unwrap(),
+    /// ];
+    /// let schema = Context::infer_schema(&samples);
+    ///
+    /// assert_eq!(schema.get_path("properties.port.type").unwrap(), &serde_value::Value::String("integer".to_string()));
+    /// assert_eq!(schema.get_path("properties.level.enum[1]").unwrap(), &serde_value::Value::String("info".to_string()));
+    /// assert_eq!(schema.get_path("required[1]").unwrap(), &serde_value::Value::String("port".to_string()));
+    /// assert!(schema.get_path("required[2]").is_none());
+    ///
+    /// let schema = oxidex::Schema::from_context(&schema).unwrap();
+    /// assert!(samples.iter().all(|sample| sample.validate(&schema).is_ok()));
+    /// ```
+    pub fn infer_schema(samples: &[Context]) -> Context {
+        let roots: Vec<Value> = samples.iter().map(Context::to_value).collect();
+        let objects: Vec<&BTreeMap<Value, Value>> = roots
+            .iter()
+            .filter_map(|root| match root {
+                Value::Map(map) => Some(map),
+                _ => None,
+            })
+            .collect();
+        let mut schema = Context::new();
+        schema.insert(
+            "$schema".to_string(),
+            string("https://json-schema.org/draft/2020-12/schema"),
+        );
+        schema.insert("type".to_string(), string("object"));
+        for (key, value) in infer_object(&objects) {
+            if let Value::String(key) = key {
+                schema.insert(key, value);
+            }
+        }
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_types_and_nested_items() {
+        let mut first = Context::new();
+        first.insert("ratio".to_string(), Value::U64(1));
+        first.set_path("servers[0].name", string("a")).unwrap();
+        let mut second = Context::new();
+        second.insert("ratio".to_string(), Value::F64(0.5));
+        second.set_path("servers[0].name", Value::Unit).unwrap();
+        second.set_path("servers[1].port", Value::U64(80)).unwrap();
+
+        let schema = Context::infer_schema(&[first, second]);
+        let expected = Context::from_args([
+            "--set",
+            "properties.ratio.type=number",
+            "--set",
+            "properties.servers.type=array",
+            "--set",
+            "properties.servers.items.type=object",
+            "--set",
+            "properties.servers.items.properties.name.type[0]=\"null\"",
+            "--set",
+            "properties.servers.items.properties.name.type[1]=string",
+            "--set",
+            "properties.servers.items.properties.port.type=integer",
+            "--set",
+            "required[0]=ratio",
+            "--set",
+            "required[1]=servers",
+        ])
+        .unwrap();
+        let changes: Vec<String> = schema
+            .diff(&expected)
+            .into_iter()
+            .map(|change| change.path().to_string())
+            .collect();
+        assert_eq!(changes, vec!["$schema".to_string(), "type".to_string()]);
+    }
+}
@@ -49,6 +49,7 @@ impl Context {
                 .into_iter()
                 .map(|(key, value)| (key, serde_value::Value::deserialize(value).unwrap()))
                 .collect(),
+            ..Context::default()
         })
     }
 
@@ -78,4 +79,21 @@ impl Context {
             false => Ok(serde_json::to_string(self)?),
         }
     }
+
+    /// Serializes the `Context` into a JSON string, with secret values masked (see
+    /// [`Context::mark_secret`]).
+    ///
+    /// # Errors
+    /// - Returns an `Error::Json` variant if serialization fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut context = oxidex::Context::from_json(r#"{"user": "admin", "password": "hunter2"}"#).unwrap();
+    /// context.mark_secret("password");
+    ///
+    /// assert_eq!(context.to_json_redacted(false).unwrap(), r#"{"password":"[REDACTED]","user":"admin"}"#);
+    /// ```
+    pub fn to_json_redacted(&self, pretty: bool) -> crate::Result<String> {
+        self.redacted().to_json(pretty)
+    }
 }
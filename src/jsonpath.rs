@@ -0,0 +1,726 @@
+use crate::entries::{self, Entries};
+use crate::path::{Path, Segment};
+use crate::value::{equivalent, scalar_cmp, unwrap};
+use crate::{Context, Error};
+use serde_value::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// A value matched by [`Context::query`], along with its location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+    /// The location of the matched value.
+    pub path: Path,
+    /// The matched value.
+    pub value: &'a Value,
+}
+
+/// A step of a query: selectors applied to the children (`.`, `[]`) or to the descendants (`..`).
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(Operand),
+    Compare(Operand, CompareOp, Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Current(Vec<Step>),
+    Root(Vec<Step>),
+    Literal(Value),
+}
+
+/// A recursive descent parser over the characters of a query.
+struct Parser<'q> {
+    query: &'q str,
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl<'q> Parser<'q> {
+    fn new(query: &'q str) -> Parser<'q> {
+        Parser {
+            query,
+            chars: query.chars().collect(),
+            position: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::JsonPath(format!(
+            "{} at position {} in '{}'",
+            message, self.position, self.query
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let matches = token
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.position + offset) == Some(&c));
+        if matches {
+            self.position += token.chars().count();
+        }
+        matches
+    }
+
+    fn expect(&mut self, token: &str) -> crate::Result<()> {
+        match self.eat(token) {
+            true => Ok(()),
+            false => Err(self.error(&format!("expected '{}'", token))),
+        }
+    }
+
+    fn query(&mut self) -> crate::Result<Vec<Step>> {
+        self.skip_whitespace();
+        self.expect("$")?;
+        let steps = self.steps()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(steps),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn steps(&mut self) -> crate::Result<Vec<Step>> {
+        let mut steps = Vec::new();
+        loop {
+            if self.eat("..") {
+                steps.push(Step::Descendant(match self.peek() {
+                    Some('[') => self.bracket()?,
+                    _ => vec![self.dotted()?],
+                }));
+            } else if self.eat(".") {
+                steps.push(Step::Child(vec![self.dotted()?]));
+            } else if self.peek() == Some('[') {
+                steps.push(Step::Child(self.bracket()?));
+            } else {
+                return Ok(steps);
+            }
+        }
+    }
+
+    fn dotted(&mut self) -> crate::Result<Selector> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            self.position += 1;
+        }
+        match self.position > start {
+            true => Ok(Selector::Name(
+                self.chars[start..self.position].iter().collect(),
+            )),
+            false => Err(self.error("expected a name or '*'")),
+        }
+    }
+
+    fn bracket(&mut self) -> crate::Result<Vec<Selector>> {
+        self.expect("[")?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.selector()?);
+            self.skip_whitespace();
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn selector(&mut self) -> crate::Result<Selector> {
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(Selector::Name(self.string()?)),
+            Some('*') => {
+                self.position += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.position += 1;
+                self.skip_whitespace();
+                Ok(Selector::Filter(self.or()?))
+            }
+            _ => {
+                let start = self.integer()?;
+                self.skip_whitespace();
+                if !self.eat(":") {
+                    return start.map(Selector::Index).ok_or_else(|| {
+                        self.error("expected an index, a slice, a name or a filter")
+                    });
+                }
+                self.skip_whitespace();
+                let end = self.integer()?;
+                self.skip_whitespace();
+                let step = match self.eat(":") {
+                    true => {
+                        self.skip_whitespace();
+                        self.integer()?
+                    }
+                    false => None,
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> crate::Result<Option<i64>> {
+        let start = self.position;
+        self.eat("-");
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if self.position == start {
+            return Ok(None);
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse()
+            .map(Some)
+            .map_err(|_| self.error("invalid integer"))
+    }
+
+    fn string(&mut self) -> crate::Result<String> {
+        let quote = self.peek().ok_or_else(|| self.error("expected a string"))?;
+        self.position += 1;
+        let mut result = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => {
+                    self.position += 1;
+                    return Ok(result);
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    result.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        c => c,
+                    });
+                    self.position += 1;
+                }
+                Some(c) => {
+                    result.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn or(&mut self) -> crate::Result<Expr> {
+        let mut expr = self.and()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("||") {
+                return Ok(expr);
+            }
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+    }
+
+    fn and(&mut self) -> crate::Result<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("&&") {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> crate::Result<Expr> {
+        self.skip_whitespace();
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        let left = self.operand()?;
+        self.skip_whitespace();
+        let op = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token));
+        match (op, left) {
+            (Some((_, op)), left) => {
+                self.skip_whitespace();
+                Ok(Expr::Compare(left, op, self.operand()?))
+            }
+            (None, Operand::Literal(_)) => Err(self.error("expected a comparison")),
+            (None, left) => Ok(Expr::Exists(left)),
+        }
+    }
+
+    fn operand(&mut self) -> crate::Result<Operand> {
+        if self.eat("@") {
+            return Ok(Operand::Current(self.steps()?));
+        }
+        if self.eat("$") {
+            return Ok(Operand::Root(self.steps()?));
+        }
+        if matches!(self.peek(), Some('\'') | Some('"')) {
+            return Ok(Operand::Literal(Value::String(self.string()?)));
+        }
+        for (token, value) in [
+            ("true", Value::Bool(true)),
+            ("false", Value::Bool(false)),
+            ("null", Value::Unit),
+        ] {
+            if self.eat(token) {
+                return Ok(Operand::Literal(value));
+            }
+        }
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        if let Ok(v) = text.parse::<i64>() {
+            return Ok(Operand::Literal(Value::I64(v)));
+        }
+        match text.parse::<f64>() {
+            Ok(v) if !text.is_empty() => Ok(Operand::Literal(Value::F64(v))),
+            _ => Err(self.error("expected '@', '$' or a literal")),
+        }
+    }
+}
+
+/// A node visited while evaluating a query: the root map of the `Context`, one of its nodes or a
+/// value inside a leaf.
+#[derive(Debug, Clone, Copy)]
+enum Node<'a> {
+    Root(&'a Entries),
+    Entry(&'a entries::Node),
+    Value(&'a Value),
+}
+
+impl<'a> Node<'a> {
+    fn children(self) -> Vec<(Segment, Node<'a>)> {
+        match self {
+            Node::Root(map) => map
+                .nodes()
+                .map(|(key, node)| (Segment::Key(key.clone()), Node::Entry(node)))
+                .collect(),
+            Node::Entry(entries::Node::Leaf(value)) => Node::Value(value).children(),
+            Node::Entry(node) => node
+                .items()
+                .into_iter()
+                .map(|(segment, node)| (segment, Node::Entry(node)))
+                .collect(),
+            Node::Value(value) => match unwrap(value) {
+                Value::Map(map) => map
+                    .iter()
+                    .map(|(key, value)| (crate::diff::key_segment(key), Node::Value(value)))
+                    .collect(),
+                Value::Seq(seq) => seq
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| (Segment::Index(index), Node::Value(value)))
+                    .collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    fn get(self, name: &str) -> Option<Node<'a>> {
+        match self {
+            Node::Root(map) => map.node(name).map(Node::Entry),
+            Node::Entry(entries::Node::Leaf(value)) => Node::Value(value).get(name),
+            Node::Entry(node) => node
+                .map()?
+                .get(&Value::String(name.to_string()))
+                .map(Node::Entry),
+            Node::Value(value) => match unwrap(value) {
+                Value::Map(map) => map.get(&Value::String(name.to_string())).map(Node::Value),
+                _ => None,
+            },
+        }
+    }
+
+    fn seq(self) -> Option<Vec<Node<'a>>> {
+        match self {
+            Node::Entry(entries::Node::Leaf(value)) => Node::Value(value).seq(),
+            Node::Entry(node) => Some(node.seq()?.iter().map(Node::Entry).collect()),
+            Node::Value(value) => match unwrap(value) {
+                Value::Seq(seq) => Some(seq.iter().map(Node::Value).collect()),
+                _ => None,
+            },
+            Node::Root(_) => None,
+        }
+    }
+
+    /// Returns the value of the node, kept by the `Context` to lend it.
+    fn value(self) -> Option<&'a Value> {
+        match self {
+            Node::Entry(node) => Some(node.value()),
+            Node::Value(value) => Some(value),
+            Node::Root(_) => None,
+        }
+    }
+
+    /// Returns the value of the node, without keeping it.
+    fn as_value(self) -> Option<Cow<'a, Value>> {
+        match self {
+            Node::Entry(node) => Some(node.as_value()),
+            Node::Value(value) => Some(Cow::Borrowed(value)),
+            Node::Root(_) => None,
+        }
+    }
+}
+
+/// Returns the indices selected by a slice over a sequence of length `len` (RFC 9535 semantics).
+fn slice(len: usize, start: Option<i64>, end: Option<i64>, step: Option<i64>) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut indices = Vec::new();
+    match step.cmp(&0) {
+        Ordering::Equal => {}
+        Ordering::Greater => {
+            let lower = start.map(normalize).unwrap_or(0).clamp(0, len);
+            let upper = end.map(normalize).unwrap_or(len).clamp(0, len);
+            let mut i = lower;
+            while i < upper {
+                indices.push(i as usize);
+                i = match i.checked_add(step) {
+                    Some(next) => next,
+                    None => break,
+                };
+            }
+        }
+        Ordering::Less => {
+            let upper = start.map(normalize).unwrap_or(len - 1).clamp(-1, len - 1);
+            let lower = end.map(normalize).unwrap_or(-len - 1).clamp(-1, len - 1);
+            let mut i = upper;
+            while lower < i {
+                indices.push(i as usize);
+                i = match i.checked_add(step) {
+                    Some(next) => next,
+                    None => break,
+                };
+            }
+        }
+    }
+    indices
+}
+
+struct Evaluator<'a> {
+    root: Node<'a>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn run(&self, start: (Path, Node<'a>), steps: &[Step]) -> Vec<(Path, Node<'a>)> {
+        steps.iter().fold(vec![start], |nodes, step| {
+            let (selectors, nodes) = match step {
+                Step::Child(selectors) => (selectors, nodes),
+                Step::Descendant(selectors) => {
+                    let mut all = Vec::new();
+                    for node in nodes {
+                        Self::descendants(node, &mut all);
+                    }
+                    (selectors, all)
+                }
+            };
+            nodes
+                .into_iter()
+                .flat_map(|(path, node)| {
+                    selectors
+                        .iter()
+                        .flat_map(|selector| self.select(&path, node, selector))
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        })
+    }
+
+    fn descendants(node: (Path, Node<'a>), all: &mut Vec<(Path, Node<'a>)>) {
+        let children = node.1.children();
+        let path = node.0.clone();
+        all.push(node);
+        for (segment, child) in children {
+            Self::descendants((path.join(segment), child), all);
+        }
+    }
+
+    fn select(&self, path: &Path, node: Node<'a>, selector: &Selector) -> Vec<(Path, Node<'a>)> {
+        match selector {
+            Selector::Name(name) => node
+                .get(name)
+                .map(|child| (path.join(Segment::Key(name.clone())), child))
+                .into_iter()
+                .collect(),
+            Selector::Wildcard => node
+                .children()
+                .into_iter()
+                .map(|(segment, child)| (path.join(segment), child))
+                .collect(),
+            Selector::Index(index) => {
+                let Some(seq) = node.seq() else {
+                    return Vec::new();
+                };
+                let index = match *index >= 0 {
+                    true => *index,
+                    false => seq.len() as i64 + index,
+                };
+                match usize::try_from(index)
+                    .ok()
+                    .and_then(|i| seq.get(i).map(|item| (i, *item)))
+                {
+                    Some((i, item)) => vec![(path.join(Segment::Index(i)), item)],
+                    None => Vec::new(),
+                }
+            }
+            Selector::Slice(start, end, step) => {
+                let Some(seq) = node.seq() else {
+                    return Vec::new();
+                };
+                slice(seq.len(), *start, *end, *step)
+                    .into_iter()
+                    .map(|i| (path.join(Segment::Index(i)), seq[i]))
+                    .collect()
+            }
+            Selector::Filter(expr) => node
+                .children()
+                .into_iter()
+                .filter(|(_, child)| self.test(expr, *child))
+                .map(|(segment, child)| (path.join(segment), child))
+                .collect(),
+        }
+    }
+
+    fn test(&self, expr: &Expr, current: Node<'a>) -> bool {
+        match expr {
+            Expr::Or(a, b) => self.test(a, current) || self.test(b, current),
+            Expr::And(a, b) => self.test(a, current) && self.test(b, current),
+            Expr::Not(a) => !self.test(a, current),
+            Expr::Exists(operand) => match operand {
+                Operand::Literal(_) => true,
+                operand => self
+                    .nodes(operand, current)
+                    .iter()
+                    .any(|node| !matches!(node, Node::Root(_))),
+            },
+            Expr::Compare(left, op, right) => {
+                let left = self.operand(left, current);
+                let right = self.operand(right, current);
+                let (left, right) = match (left.as_slice(), right.as_slice()) {
+                    ([], []) => return matches!(op, CompareOp::Eq | CompareOp::Le | CompareOp::Ge),
+                    ([left], [right]) => (&**left, &**right),
+                    _ => return matches!(op, CompareOp::Ne),
+                };
+                match op {
+                    CompareOp::Eq => equivalent(left, right),
+                    CompareOp::Ne => !equivalent(left, right),
+                    CompareOp::Lt => scalar_cmp(left, right) == Some(Ordering::Less),
+                    CompareOp::Gt => scalar_cmp(left, right) == Some(Ordering::Greater),
+                    CompareOp::Le => {
+                        equivalent(left, right) || scalar_cmp(left, right) == Some(Ordering::Less)
+                    }
+                    CompareOp::Ge => {
+                        equivalent(left, right)
+                            || scalar_cmp(left, right) == Some(Ordering::Greater)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the values designated by an operand; a literal yields itself.
+    fn operand<'b>(&self, operand: &'b Operand, current: Node<'a>) -> Vec<Cow<'b, Value>>
+    where
+        'a: 'b,
+    {
+        match operand {
+            Operand::Literal(value) => vec![Cow::Borrowed(value)],
+            operand => self
+                .nodes(operand, current)
+                .into_iter()
+                .filter_map(Node::as_value)
+                .collect(),
+        }
+    }
+
+    /// Returns the nodes designated by an operand; a literal designates none.
+    fn nodes(&self, operand: &Operand, current: Node<'a>) -> Vec<Node<'a>> {
+        let (start, steps) = match operand {
+            Operand::Literal(_) => return Vec::new(),
+            Operand::Current(steps) => (current, steps),
+            Operand::Root(steps) => (self.root, steps),
+        };
+        self.run((Path::root(), start), steps)
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect()
+    }
+}
+
+impl Context {
+    /// Selects values with a JSONPath query (RFC 9535 subset).
+    ///
+    /// Supported syntax:
+    /// - `$` (the context), `.name`, `['name']` and `[0]` (negative indices count from the end);
+    /// - `*` wildcards, `..` recursive descent and `[start:end:step]` slices;
+    /// - unions such as `['a','b']` or `[0,2]`;
+    /// - filters such as `[?(@.enabled)]` or `[?@.port >= 1024 && @.name != 'admin']`, with
+    ///   existence tests, comparisons (`==`, `!=`, `<`, `<=`, `>`, `>=`), `!`, `&&`, `||`,
+    ///   parentheses and `$`-rooted operands.
+    ///
+    /// Numbers are compared regardless of their type. The context itself is never returned as a
+    /// match, only the values it contains.
+    ///
+    /// # Errors
+    /// - Returns an `Error::JsonPath` variant if the query is malformed.
+    ///
+    /// # Example
+    /// ```rust
+    /// let context = oxidex::Context::from_args([
+    ///     "--set", "services[0].name=api", "--set", "services[0].enabled=true",
+    ///     "--set", "services[1].name=worker", "--set", "services[1].enabled=false",
+    ///     "--set", "services[2].name=cron", "--set", "services[2].enabled=true",
+    /// ]).unwrap();
+    ///
+    /// let matches = context.query("$.services[?(@.enabled == true)].name").unwrap();
+    /// let names: Vec<String> = matches.iter().map(|m| m.path.to_string()).collect();
+    /// assert_eq!(names, vec!["services[0].name", "services[2].name"]);
+    /// assert_eq!(matches[1].value, &serde_value::Value::String("cron".to_string()));
+    ///
+    /// assert_eq!(context.query("$..name").unwrap().len(), 3);
+    /// assert_eq!(context.query("$.services[-1:].name").unwrap()[0].path.to_string(), "services[2].name");
+    /// ```
+    pub fn query(&self, query: &str) -> crate::Result<Vec<crate::Match<'_>>> {
+        let steps = Parser::new(query).query()?;
+        let root = Node::Root(&self.inner);
+        let evaluator = Evaluator { root };
+        Ok(evaluator
+            .run((Path::root(), root), &steps)
+            .into_iter()
+            .filter_map(|(path, node)| node.value().map(|value| Match { path, value }))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(context: &Context, query: &str) -> Vec<String> {
+        context
+            .query(query)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_slices() {
+        assert_eq!(slice(5, Some(1), Some(3), None), vec![1, 2]);
+        assert_eq!(slice(5, None, None, Some(2)), vec![0, 2, 4]);
+        assert_eq!(slice(5, None, None, Some(-1)), vec![4, 3, 2, 1, 0]);
+        assert_eq!(slice(5, Some(-2), None, None), vec![3, 4]);
+        assert!(slice(5, None, None, Some(0)).is_empty());
+        assert_eq!(slice(3, Some(1), None, Some(i64::MAX)), vec![1]);
+        assert_eq!(slice(3, None, None, Some(i64::MIN)), vec![2]);
+    }
+
+    #[test]
+    fn test_selectors() {
+        let context = Context::from_args([
+            "--set", "a.b[0]=1", "--set", "a.b[1]=2", "--set", "a.b[2]=3", "--set", "a.c=x",
+            "--set", "d.c=y",
+        ])
+        .unwrap();
+        assert_eq!(paths(&context, "$.a.*"), vec!["a.b", "a.c"]);
+        assert_eq!(
+            paths(&context, "$['a']['b'][0,-1]"),
+            vec!["a.b[0]", "a.b[2]"]
+        );
+        assert_eq!(paths(&context, "$..c"), vec!["a.c", "d.c"]);
+        assert_eq!(paths(&context, "$.a.b[?@ > 1]"), vec!["a.b[1]", "a.b[2]"]);
+        assert_eq!(
+            paths(&context, "$.*[?(@ == 'y' || @ == 'x')]"),
+            vec!["a.c", "d.c"]
+        );
+        assert_eq!(paths(&context, "$.a.b[?!(@ >= 2)]"), vec!["a.b[0]"]);
+        assert_eq!(paths(&context, "$.a.b[?@ == $.a.b[1]]"), vec!["a.b[1]"]);
+        assert_eq!(
+            paths(&context, "$.a.b[1::9223372036854775807]"),
+            vec!["a.b[1]"]
+        );
+        assert_eq!(
+            paths(&context, "$.a.b[::-9223372036854775808]"),
+            vec!["a.b[2]"]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let context = Context::new();
+        for query in ["a.b", "$.", "$[", "$[?@ ==]", "$['a"] {
+            assert!(
+                matches!(context.query(query), Err(Error::JsonPath(_))),
+                "{}",
+                query
+            );
+        }
+    }
+}
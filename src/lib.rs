@@ -13,6 +13,28 @@
 //!
 //! * **Context Manipulation**: Store, modify, and query data within a context object.
 //! * **Multiple Export Formats**: Export the context to JSON, TOML, or YAML formats.
+//! * **Encryption**: Encrypt selected values in place as `ENC[...]` envelopes with a symmetric key or an X25519 recipient from a local key file, leaving keys readable (`encryption` feature).
+//! * **Audit Log**: Attach an `AuditLog` to a context to record who changed which value and when, with an injectable clock, and export it as JSON Lines.
+//! * **History**: Take O(1) checkpoints to roll back to, or record edits in a bounded undo/redo history stored as patches with `UndoableContext`.
+//! * **Scopes**: Overlay per-request or per-task values on a shared base with `Context::child`, whose lookups fall through to the parent, and materialize them with `flatten_scope`.
+//! * **Shared Contexts**: Share a context between threads with `SharedContext`, whose readers get immutable snapshots while writers publish atomic transactions (`shared` feature).
+//! * **Change Subscriptions**: Wrap a context in an `ObservableContext` to notify subscribers of changes under patterns such as `db.*`, once per transaction.
+//! * **Versioned Store**: Commit every published version of a context to a local directory, or to SQLite (`sqlite` feature), deduplicated by the hash of its canonical form, and load or diff any of them (`store` feature).
+//! * **Hot Reload**: Watch the source files of a context and publish a new version, with its diff, whenever they change (`watch` feature).
+//! * **Secrets**: Mark paths or key patterns such as `*password*` as secret so that `Debug`, `Display` and redacted exports mask them.
+//! * **Signing**: Sign contexts with HMAC-SHA256 or Ed25519 over a canonical form, so signatures survive JSON, YAML and TOML round trips (`signing` feature).
+//! * **Defaults and Coercion**: Fill in missing values from a defaults context or a schema, and coerce strings to their declared types.
+//! * **Tabular Data**: Export a list of records to CSV or TSV, and import it back with typed fields (`csv` feature).
+//! * **Queries**: Select values with JSONPath expressions, including wildcards, recursive descent, slices and filters.
+//! * **Flattening**: Turn nested values into single-level `a.b.0.c` keys and back, with configurable index style and escaping.
+//! * **Transforms**: Rename, move, drop and rewrite values, or flatten whole contexts, with declarative steps loadable from a spec.
+//! * **Schema Validation**: Check a context against a JSON Schema and report every violation with the offending key, or infer a schema from sample contexts (`schema` feature).
+//! * **Structural Diff**: Compare two contexts, whatever format they were loaded from, and report every added, removed or changed value.
+//! * **Three-Way Merge**: Merge two versions of a context branched from a common base and report, or resolve, their conflicts.
+//! * **Patches**: Apply and generate JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7396) documents.
+//! * **Command-Line Overrides**: Turn `--set db.host=x` arguments into a context to merge over file-based configuration.
+//! * **Query Strings**: Read and write URL query strings and form bodies with bracket-style nesting (`query` feature).
+//! * **Command-Line Tool**: The `oxidex` binary (`cli` feature) converts, queries, edits, merges and compares JSON, TOML and YAML files.
 //!
 //! # Usage
 //!
@@ -26,7 +48,7 @@
 //! ```
 //!
 //! 2. **Define Context**: The core feature of `oxidex` is the context. The context acts as a container where you can store key-value pairs of data.
-//! Here’s how to create and manipulate it:
+//!    Here’s how to create and manipulate it:
 //!
 //! ```rust
 //! use oxidex::Context;
@@ -41,7 +63,7 @@
 //! ```
 //!
 //! 3. **Exporting the Context**: `oxidex` allows you to export the context into various formats like `JSON`, `TOML`, and `YAML`. You can use the
-//! following methods to serialize the context:
+//!    following methods to serialize the context:
 //!
 //! ```toml
 //! [dependencies]
@@ -56,6 +78,7 @@
 //!     let mut context = Context::new();
 //!     context.insert("name".to_string(), Value::String("John Doe".to_string()));
 //!     context.insert("age".to_string(), Value::U8(30));
+//!     # #[cfg(feature = "json")]
 //!     println!("{}", context.to_json(true).unwrap());
 //!  }
 //! ```
@@ -63,9 +86,102 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+mod path;
+pub use path::{Path, Segment};
+
+mod args;
+#[cfg(feature = "clap")]
+pub use args::Args;
+
+mod audit;
+pub use audit::{AuditEntry, AuditLog, Clock, SystemClock};
+
+#[cfg(any(feature = "signing", feature = "store"))]
+mod canonical;
+
+mod defaults;
+pub use defaults::{Coercion, Defaults, DefaultsReport};
+
+mod diff;
+
+mod entries;
+use entries::Entries;
+pub use diff::{Change, Diff};
+
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "encryption")]
+pub use encryption::Keyring;
+
+mod flatten;
+pub use flatten::{FlattenOptions, IndexStyle};
+
+mod history;
+pub use history::{Checkpoint, UndoableContext};
+
+mod jsonpath;
+pub use jsonpath::Match;
+
+mod merge;
+
+mod merge3;
+pub use merge3::{Conflict, ConflictKind, Merge3, Resolution};
+
+mod observe;
+pub use observe::{ObservableContext, SubscriptionId};
+
+mod patch;
+pub use patch::Op;
+
+#[cfg(feature = "schema")]
+mod schema;
+#[cfg(feature = "schema")]
+pub use schema::{Schema, Violation};
+
+#[cfg(feature = "schema")]
+mod infer;
+
+mod scope;
+pub use scope::Scope;
+
+mod secret;
+
+#[cfg(feature = "shared")]
+mod shared;
+#[cfg(feature = "shared")]
+pub use shared::SharedContext;
+
+#[cfg(feature = "signing")]
+mod signing;
+#[cfg(feature = "signing")]
+pub use signing::{SigningKey, SIGNATURE_KEY};
+
+#[cfg(feature = "store")]
+mod store;
+#[cfg(feature = "store")]
+pub use store::{ContextStore, Version};
+
+mod transform;
+pub use transform::{StringFunction, Transform, TransformStep};
+
+mod value;
+
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "watch")]
+pub use watch::WatchedContext;
+
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(feature = "csv")]
+pub use csv::CsvOptions;
+
 #[cfg(feature = "json")]
 mod json;
 
+#[cfg(feature = "query")]
+mod query;
+
 #[cfg(feature = "toml")]
 mod toml;
 
@@ -76,19 +192,73 @@ mod yaml;
 mod xml;
 
 /// Enum to represent various types of errors in the `oxidex` library.
+///
+/// New variants are added as the library grows, so a `match` on it needs a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// A generic error that takes a string message.
     Generic(String),
 
+    /// Error raised when a path is malformed or does not lead to the expected value.
+    Path(String),
+
+    /// Error raised when command-line overrides are malformed.
+    Args(String),
+
+    /// Error raised when reading or writing a file or a stream fails.
+    Io(String),
+
+    /// Error raised when a patch cannot be applied.
+    Patch(String),
+
+    /// Error raised when a JSONPath query is malformed.
+    JsonPath(String),
+
+    /// Error raised when a transform is malformed or one of its steps cannot be applied.
+    Transform(String),
+
+    /// Error related to CSV processing, available if the "csv" feature is enabled.
+    #[cfg(feature = "csv")]
+    Csv(String),
+
+    /// Error raised when a value cannot be encrypted or decrypted, available if the "encryption"
+    /// feature is enabled.
+    #[cfg(feature = "encryption")]
+    Encryption(String),
+
     /// Error related to JSON processing, available if the "json" feature is enabled.
     #[cfg(feature = "json")]
     Json(String),
 
+    /// Error related to query string processing, available if the "query" feature is enabled.
+    #[cfg(feature = "query")]
+    Query(String),
+
+    /// Error raised when a JSON Schema is malformed or a context does not satisfy it, available if
+    /// the "schema" feature is enabled.
+    #[cfg(feature = "schema")]
+    Schema(String),
+
+    /// Error raised when a context cannot be signed or its signature does not verify, available if
+    /// the "signing" feature is enabled.
+    #[cfg(feature = "signing")]
+    Signature(String),
+
+    /// Error raised when a version cannot be stored or loaded, available if the "store" feature is
+    /// enabled.
+    #[cfg(feature = "store")]
+    Store(String),
+
     /// Error related to TOML processing, available if the "toml" feature is enabled.
     #[cfg(feature = "toml")]
     Toml(String),
 
+    /// Error raised when the sources of a watched context cannot be loaded or watched, available if
+    /// the "watch" feature is enabled.
+    #[cfg(feature = "watch")]
+    Watch(String),
+
     /// Error related to XML processing, available if the "xml" feature is enabled.
     #[cfg(feature = "xml")]
     Xml(String),
@@ -98,6 +268,62 @@ pub enum Error {
     Yaml(String),
 }
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Generic(msg) => write!(f, "{}", msg),
+            Error::Path(msg) => write!(f, "path error: {}", msg),
+            Error::Args(msg) => write!(f, "argument error: {}", msg),
+            Error::Io(msg) => write!(f, "I/O error: {}", msg),
+            Error::Patch(msg) => write!(f, "patch error: {}", msg),
+            Error::JsonPath(msg) => write!(f, "JSONPath error: {}", msg),
+            Error::Transform(msg) => write!(f, "transform error: {}", msg),
+            #[cfg(feature = "csv")]
+            Error::Csv(msg) => write!(f, "CSV error: {}", msg),
+            #[cfg(feature = "encryption")]
+            Error::Encryption(msg) => write!(f, "encryption error: {}", msg),
+            #[cfg(feature = "json")]
+            Error::Json(msg) => write!(f, "JSON error: {}", msg),
+            #[cfg(feature = "query")]
+            Error::Query(msg) => write!(f, "query string error: {}", msg),
+            #[cfg(feature = "schema")]
+            Error::Schema(msg) => write!(f, "schema error: {}", msg),
+            #[cfg(feature = "signing")]
+            Error::Signature(msg) => write!(f, "signature error: {}", msg),
+            #[cfg(feature = "store")]
+            Error::Store(msg) => write!(f, "store error: {}", msg),
+            #[cfg(feature = "toml")]
+            Error::Toml(msg) => write!(f, "TOML error: {}", msg),
+            #[cfg(feature = "watch")]
+            Error::Watch(msg) => write!(f, "watch error: {}", msg),
+            #[cfg(feature = "xml")]
+            Error::Xml(msg) => write!(f, "XML error: {}", msg),
+            #[cfg(feature = "yaml")]
+            Error::Yaml(msg) => write!(f, "YAML error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    /// Converts a `std::io::Error` into the `oxidex::Error` enum.
+    ///
+    /// This allows automatic conversion of `std::io::Error` into `Error::Io(String)`
+    /// when using the `?` operator in functions that return `Result<T, Error>`.
+    ///
+    /// Example:
+    /// ```rust
+    /// fn read(path: &str) -> oxidex::Result<String> {
+    ///     Ok(std::fs::read_to_string(path)?)
+    /// }
+    ///
+    /// assert!(matches!(read("/does/not/exist"), Err(oxidex::Error::Io(_))));
+    /// ```
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.to_string())
+    }
+}
 
 /// A type alias for `Result<T, Error>`.
 ///
@@ -108,20 +334,47 @@ pub enum Error {
 /// # Example
 /// ```
 /// fn example() -> oxidex::Result<i32> {
-///     Err(oxidex::Error::Xml("Invalid XML".to_string()))
+///     Err(oxidex::Error::Generic("Invalid value".to_string()))
 /// }
 /// ```
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// A struct that represents a context, which stores key-value pairs in a BTreeMap.
 /// The context can be serialized and deserialized using Serde.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+///
+/// Its `Debug` and `Display` implementations mask the values marked as secret (see
+/// [`Context::mark_secret`]).
+///
+/// Cloning a `Context` is O(1): clones share their values down to nested maps and sequences, and
+/// modifying one copies only the maps and sequences on the path to the value it touches, so
+/// layering request-local data over a global context is cheap.
+#[derive(Default, Serialize, Deserialize)]
 pub struct Context {
-    /// A BTreeMap that stores the inner key-value data.
+    /// A copy-on-write map that stores the inner key-value data.
     /// The `serde(flatten)` attribute means that this map will be serialized and deserialized
     /// as if its keys and values were directly on the `Context` struct, without nesting it.
     #[serde(flatten)]
-    inner: BTreeMap<String, serde_value::Value>,
+    inner: Entries,
+
+    /// The glob patterns of the paths holding secret values.
+    #[serde(skip)]
+    secrets: Vec<String>,
+
+    /// The audit log recording the mutations; clones start without one.
+    #[serde(skip)]
+    audit: Option<AuditLog>,
+}
+
+impl Clone for Context {
+    /// Clones the values and the secret marks, but not the audit log: changes made to a working
+    /// copy, such as a per-request clone, are not changes of the audited `Context`.
+    fn clone(&self) -> Context {
+        Context {
+            inner: self.inner.clone(),
+            secrets: self.secrets.clone(),
+            audit: None,
+        }
+    }
 }
 
 impl Context {
@@ -143,7 +396,9 @@ impl Context {
     /// assert_eq!(context.get("name").unwrap(), &serde_value::Value::String("Alice".to_string()));
     /// ```
     pub fn insert(&mut self, k: String, v: serde_value::Value) {
-        self.inner.insert(k, v);
+        self.audited(|context| {
+            context.inner.insert(k, v);
+        })
     }
 
     /// Retrieves a reference to the value associated with the given key.
@@ -183,12 +438,12 @@ impl Context {
     /// assert_eq!(context.get("key2").unwrap(), &serde_value::Value::String("value2".to_string()));
     /// ```
     pub fn extend(&mut self, data: BTreeMap<String, serde_value::Value>) {
-        self.inner.extend(data);
+        self.audited(|context| context.inner.extend(data))
     }
 }
 
 
-impl Into<BTreeMap<String, serde_value::Value>> for Context {
+impl From<Context> for BTreeMap<String, serde_value::Value> {
     /// Converts a `Context` instance into a `BTreeMap<String, serde_value::Value>`.
     ///
     /// This implementation allows you to convert the `Context` directly into a `BTreeMap`
@@ -205,8 +460,8 @@ impl Into<BTreeMap<String, serde_value::Value>> for Context {
     /// // Verify the map contains the key-value pair
     /// assert_eq!(map.get("key1"), Some(&serde_value::Value::String("value1".to_string())));
     /// ```
-    fn into(self) -> BTreeMap<String, serde_value::Value> {
-        self.inner
+    fn from(context: Context) -> Self {
+        context.inner.into()
     }
 }
 
@@ -0,0 +1,254 @@
+use crate::entries::Node;
+use crate::value::{equivalent, is_null, unwrap, unwrap_mut};
+use crate::Context;
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// Merges `value` into `target`: maps are merged recursively, any other value replaces the target.
+pub(crate) fn merge_value(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Map(target), Value::Map(map)) => {
+            for (key, value) in map {
+                match target.get_mut(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+/// Merges `source` into the node `target` like [`merge_value`], sharing the nodes of `source`
+/// and copying only the maps of `target` it merges into.
+fn merge_node(target: &mut Node, source: Node) {
+    let is_map = |node: &Node| matches!(node, Node::Map(_) | Node::Leaf(Value::Map(_)));
+    match source {
+        source if target.map().is_some() && source.map().is_some() => {
+            let map = target.map_mut().expect("the target is a map");
+            for (key, node) in source.map().expect("the source is a map") {
+                match map.get_mut(key) {
+                    Some(existing) => merge_node(existing, node.clone()),
+                    None => {
+                        map.insert(key.clone(), node.clone());
+                    }
+                }
+            }
+        }
+        source if is_map(target) && is_map(&source) => {
+            merge_value(target.value_mut(), source.into())
+        }
+        source => *target = source,
+    }
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to `target`.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Map(patch) = unwrap(patch) else {
+        *target = patch.clone();
+        return;
+    };
+    if !matches!(unwrap(target), Value::Map(_)) {
+        *target = Value::Map(BTreeMap::new());
+    }
+    let Value::Map(map) = unwrap_mut(target) else {
+        unreachable!("the target was just turned into a map")
+    };
+    for (key, value) in patch {
+        match is_null(value) {
+            true => {
+                map.remove(key);
+            }
+            false => merge_patch(map.entry(key.clone()).or_insert(Value::Unit), value),
+        }
+    }
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to the node `target`, copying only the maps it changes.
+fn merge_patch_node(target: &mut Node, patch: &Value) {
+    let Value::Map(entries) = unwrap(patch) else {
+        *target = Node::from(patch.clone());
+        return;
+    };
+    let Some(map) = target.map_mut() else {
+        return merge_patch(target.value_mut(), patch);
+    };
+    for (key, value) in entries {
+        if is_null(value) {
+            map.remove(key);
+            continue;
+        }
+        match map.get_mut(key) {
+            Some(existing) => merge_patch_node(existing, value),
+            None => {
+                let mut created = Value::Unit;
+                merge_patch(&mut created, value);
+                map.insert(key.clone(), Node::from(created));
+            }
+        }
+    }
+}
+
+/// Computes the JSON Merge Patch (RFC 7396) turning `old` into `new`, or `None` if they are equivalent.
+fn merge_patch_between(old: &Value, new: &Value) -> Option<Value> {
+    match (unwrap(old), unwrap(new)) {
+        (Value::Map(old), Value::Map(new)) => {
+            let mut patch: BTreeMap<Value, Value> = old
+                .keys()
+                .filter(|key| !new.contains_key(key))
+                .map(|key| (key.clone(), Value::Unit))
+                .collect();
+            for (key, value) in new {
+                let change = match old.get(key) {
+                    Some(previous) => merge_patch_between(previous, value),
+                    None => Some(value.clone()),
+                };
+                patch.extend(change.map(|change| (key.clone(), change)));
+            }
+            (!patch.is_empty()).then_some(Value::Map(patch))
+        }
+        (a, b) if equivalent(a, b) => None,
+        _ => Some(new.clone()),
+    }
+}
+
+impl Context {
+    /// Deeply merges another `Context` into this one.
+    ///
+    /// Nested maps are merged key by key; any other value from `other` (scalars, sequences)
+    /// replaces the existing one. This is typically used to layer overrides over a base
+    /// configuration. The secret marks of `other` are kept.
+    ///
+    /// `other`: The `Context` whose values take precedence.
+    ///
+    /// Example:
+    /// ```
+    /// let mut base = oxidex::Context::new();
+    /// base.set_path("db.host", serde_value::Value::String("localhost".to_string())).unwrap();
+    /// base.set_path("db.port", serde_value::Value::U64(5432)).unwrap();
+    ///
+    /// let mut overrides = oxidex::Context::new();
+    /// overrides.set_path("db.host", serde_value::Value::String("db.internal".to_string())).unwrap();
+    ///
+    /// base.merge(overrides);
+    /// assert_eq!(base.get_path("db.host").unwrap(), &serde_value::Value::String("db.internal".to_string()));
+    /// assert_eq!(base.get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+    /// ```
+    pub fn merge(&mut self, other: Context) {
+        self.audited(|context| {
+            for pattern in &other.secrets {
+                context.mark_secret(pattern);
+            }
+            for (key, node) in other.inner.nodes() {
+                match context.inner.node_mut(key) {
+                    Some(existing) => merge_node(existing, node.clone()),
+                    None => {
+                        context.inner.insert_node(key.clone(), node.clone());
+                    }
+                }
+            }
+        })
+    }
+
+    /// Applies a JSON Merge Patch (RFC 7396) to the `Context`.
+    ///
+    /// Maps of the patch are merged recursively, `null` values delete the matching keys and any
+    /// other value replaces the existing one. Both `Value::Unit` and `Value::Option(None)` are
+    /// read as `null`, so the patch can come from any format (JSON, YAML, ...).
+    ///
+    /// `patch`: The merge patch to apply.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "db.host=localhost", "--set", "db.port=5432"]).unwrap();
+    /// let patch = oxidex::Context::from_args(["--set", "db.host=null", "--set", "db.user=admin"]).unwrap();
+    ///
+    /// context.apply_merge_patch(&patch);
+    /// assert!(context.get_path("db.host").is_none());
+    /// assert_eq!(context.get_path("db.user").unwrap(), &serde_value::Value::String("admin".to_string()));
+    /// assert_eq!(context.get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+    /// ```
+    pub fn apply_merge_patch(&mut self, patch: &Context) {
+        self.audited(|context| {
+            for (key, value) in &patch.inner {
+                match is_null(&value) {
+                    true => {
+                        context.inner.remove(key);
+                    }
+                    false => match context.inner.node_mut(key) {
+                        Some(existing) => merge_patch_node(existing, &value),
+                        None => {
+                            let mut created = Value::Unit;
+                            merge_patch(&mut created, &value);
+                            context.inner.insert(key.clone(), created);
+                        }
+                    },
+                }
+            }
+        })
+    }
+
+    /// Computes the JSON Merge Patch (RFC 7396) that turns this `Context` into `other`.
+    ///
+    /// Removed keys are set to `null` (`Value::Unit`), changed maps are described recursively and
+    /// any other changed value, sequences included, is given in full. As RFC 7396 has no way to
+    /// express it, a value that becomes `null` in `other` is removed by the patch.
+    ///
+    /// `other`: The target version of the context.
+    ///
+    /// Example:
+    /// ```
+    /// let old = oxidex::Context::from_args(["--set", "db.host=localhost", "--set", "db.port=5432"]).unwrap();
+    /// let new = oxidex::Context::from_args(["--set", "db.port=5432", "--set", "debug=true"]).unwrap();
+    ///
+    /// let patch = old.merge_patch_to(&new);
+    /// assert_eq!(patch.get_path("db.host").unwrap(), &serde_value::Value::Unit);
+    /// assert!(patch.get_path("db.port").is_none());
+    ///
+    /// let mut patched = old.clone();
+    /// patched.apply_merge_patch(&patch);
+    /// assert!(patched.diff(&new).is_empty());
+    /// ```
+    pub fn merge_patch_to(&self, other: &Context) -> Context {
+        let mut patch = Context::new();
+        for key in self
+            .inner
+            .keys()
+            .filter(|key| !other.inner.contains_key(key))
+        {
+            patch.inner.insert(key.clone(), Value::Unit);
+        }
+        for (key, value) in &other.inner {
+            let change = match self.inner.node(key) {
+                Some(previous) => merge_patch_between(&previous.as_value(), &value),
+                None => Some(value.into_owned()),
+            };
+            patch
+                .inner
+                .extend(change.map(|change| (key.clone(), change)));
+        }
+        patch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_patch_nulls_and_scalars() {
+        let mut context =
+            Context::from_args(["--set", "a=1", "--set", "b=2", "--set", "c.d=3"]).unwrap();
+        let mut patch = Context::new();
+        patch.insert("a".to_string(), Value::Option(None));
+        patch.set_path("b.e", Value::Unit).unwrap();
+        patch.set_path("b.f", Value::U8(4)).unwrap();
+        patch.insert("c".to_string(), Value::U8(5));
+
+        context.apply_merge_patch(&patch);
+        let expected = Context::from_args(["--set", "b.f=4", "--set", "c=5"]).unwrap();
+        assert!(context.diff(&expected).is_empty());
+    }
+}
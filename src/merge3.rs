@@ -0,0 +1,253 @@
+use crate::diff::key_segment;
+use crate::path::Path;
+use crate::value::{equivalent, unwrap};
+use crate::Context;
+use serde_value::Value;
+use std::collections::BTreeSet;
+
+/// The nature of a [`Conflict`] found by a three-way merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides changed (or added) the value differently.
+    BothModified,
+    /// Our side deleted the value while their side modified it.
+    DeletedByOurs,
+    /// Their side deleted the value while our side modified it.
+    DeletedByTheirs,
+}
+
+/// A value that both sides of a three-way merge changed in incompatible ways.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// The location of the conflicting value.
+    pub path: Path,
+    /// The nature of the conflict.
+    pub kind: ConflictKind,
+    /// The value in the common ancestor, if any.
+    pub base: Option<Value>,
+    /// The value on our side, if any.
+    pub ours: Option<Value>,
+    /// The value on their side, if any.
+    pub theirs: Option<Value>,
+}
+
+/// The decision taken by a resolver for a [`Conflict`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Keeps our value (or our deletion).
+    Ours,
+    /// Keeps their value (or their deletion).
+    Theirs,
+    /// Keeps the value of the common ancestor.
+    Base,
+    /// Uses the given value.
+    Value(Value),
+    /// Removes the value.
+    Remove,
+}
+
+/// The result of a three-way merge: the merged `Context` and the conflicts left unresolved.
+///
+/// Unresolved conflicts keep our side in `merged`.
+#[derive(Debug, Clone)]
+pub struct Merge3 {
+    /// The merged context.
+    pub merged: Context,
+    /// The conflicts that were not resolved, ordered by path.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merge3 {
+    /// Returns `true` if the merge has no unresolved conflict.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Returns `true` if two optional values are both absent or equivalent.
+fn same(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => equivalent(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn merge_values<F>(
+    path: &Path,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    resolver: &mut F,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value>
+where
+    F: FnMut(&Conflict) -> Option<Resolution>,
+{
+    if same(ours, theirs) || same(base, theirs) {
+        return ours.cloned();
+    }
+    if same(base, ours) {
+        return theirs.cloned();
+    }
+    if let (Some(Value::Map(our_map)), Some(Value::Map(their_map))) =
+        (ours.map(unwrap), theirs.map(unwrap))
+    {
+        let base_map = match base.map(unwrap) {
+            Some(Value::Map(map)) => Some(map),
+            _ => None,
+        };
+        let keys: BTreeSet<&Value> = our_map.keys().chain(their_map.keys()).collect();
+        let merged = keys
+            .into_iter()
+            .filter_map(|key| {
+                let value = merge_values(
+                    &path.join(key_segment(key)),
+                    base_map.and_then(|map| map.get(key)),
+                    our_map.get(key),
+                    their_map.get(key),
+                    resolver,
+                    conflicts,
+                );
+                value.map(|value| (key.clone(), value))
+            })
+            .collect();
+        return Some(Value::Map(merged));
+    }
+    let conflict = Conflict {
+        path: path.clone(),
+        kind: match (ours, theirs) {
+            (None, _) => ConflictKind::DeletedByOurs,
+            (_, None) => ConflictKind::DeletedByTheirs,
+            _ => ConflictKind::BothModified,
+        },
+        base: base.cloned(),
+        ours: ours.cloned(),
+        theirs: theirs.cloned(),
+    };
+    match resolver(&conflict) {
+        Some(Resolution::Ours) => ours.cloned(),
+        Some(Resolution::Theirs) => theirs.cloned(),
+        Some(Resolution::Base) => base.cloned(),
+        Some(Resolution::Value(value)) => Some(value),
+        Some(Resolution::Remove) => None,
+        None => {
+            conflicts.push(conflict);
+            ours.cloned()
+        }
+    }
+}
+
+impl Context {
+    /// Merges two versions of a `Context` branched from a common ancestor.
+    ///
+    /// A value changed on one side only takes that change; maps changed on both sides are merged
+    /// key by key. When both sides changed the same value differently, or one side deleted a value
+    /// the other modified, a [`Conflict`] is reported and our value is kept. Sequences are compared
+    /// as a whole.
+    ///
+    /// `base`: The common ancestor.
+    /// `ours`: Our version, which wins unresolved conflicts.
+    /// `theirs`: Their version.
+    ///
+    /// Example:
+    /// ```
+    /// let base = oxidex::Context::from_args(["--set", "db.host=a", "--set", "db.port=1"]).unwrap();
+    /// let ours = oxidex::Context::from_args(["--set", "db.host=b", "--set", "db.port=1"]).unwrap();
+    /// let theirs = oxidex::Context::from_args(["--set", "db.host=c", "--set", "db.port=2"]).unwrap();
+    ///
+    /// let result = oxidex::Context::merge3(&base, &ours, &theirs);
+    /// assert_eq!(result.merged.get_path("db.port").unwrap(), &serde_value::Value::U64(2));
+    /// assert_eq!(result.merged.get_path("db.host").unwrap(), &serde_value::Value::String("b".to_string()));
+    /// assert_eq!(result.conflicts.len(), 1);
+    /// assert_eq!(result.conflicts[0].path.to_string(), "db.host");
+    /// assert_eq!(result.conflicts[0].kind, oxidex::ConflictKind::BothModified);
+    /// ```
+    pub fn merge3(base: &Context, ours: &Context, theirs: &Context) -> Merge3 {
+        Context::merge3_with(base, ours, theirs, |_| None)
+    }
+
+    /// Merges two versions of a `Context` like [`Context::merge3`], letting `resolver` decide
+    /// conflicts.
+    ///
+    /// `resolver` is called once per conflict; returning `None` leaves the conflict unresolved
+    /// (our value is kept and the conflict is reported).
+    ///
+    /// Example:
+    /// ```
+    /// use oxidex::{Context, ConflictKind, Resolution};
+    ///
+    /// let base = Context::from_args(["--set", "a=1", "--set", "b=1"]).unwrap();
+    /// let ours = Context::from_args(["--set", "b=2"]).unwrap();
+    /// let theirs = Context::from_args(["--set", "a=3", "--set", "b=3"]).unwrap();
+    ///
+    /// let result = Context::merge3_with(&base, &ours, &theirs, |conflict| match conflict.kind {
+    ///     ConflictKind::DeletedByOurs => Some(Resolution::Theirs),
+    ///     _ => None,
+    /// });
+    /// assert_eq!(result.merged.get("a").unwrap(), &serde_value::Value::U64(3));
+    /// assert_eq!(result.conflicts.len(), 1);
+    /// ```
+    pub fn merge3_with<F>(
+        base: &Context,
+        ours: &Context,
+        theirs: &Context,
+        mut resolver: F,
+    ) -> Merge3
+    where
+        F: FnMut(&Conflict) -> Option<Resolution>,
+    {
+        let mut conflicts = Vec::new();
+        let merged = merge_values(
+            &Path::root(),
+            Some(&base.to_value()),
+            Some(&ours.to_value()),
+            Some(&theirs.to_value()),
+            &mut resolver,
+            &mut conflicts,
+        );
+        Merge3 {
+            merged: merged
+                .and_then(|merged| Context::from_value(merged).ok())
+                .unwrap_or_default(),
+            conflicts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_modify() {
+        let base = Context::from_args(["--set", "a.x=1", "--set", "b=1"]).unwrap();
+        let ours = Context::from_args(["--set", "a.x=2", "--set", "c=1"]).unwrap();
+        let theirs = Context::from_args(["--set", "b=1", "--set", "d=1"]).unwrap();
+
+        let result = Context::merge3(&base, &ours, &theirs);
+        let kinds: Vec<(String, ConflictKind)> = result
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.path.to_string(), conflict.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![("a".to_string(), ConflictKind::DeletedByTheirs)]
+        );
+        let expected =
+            Context::from_args(["--set", "a.x=2", "--set", "c=1", "--set", "d=1"]).unwrap();
+        assert!(result.merged.diff(&expected).is_empty());
+    }
+
+    #[test]
+    fn test_resolver_remove() {
+        let base = Context::from_args(["--set", "a=1"]).unwrap();
+        let ours = Context::from_args(["--set", "a=2"]).unwrap();
+        let theirs = Context::from_args(["--set", "a=3"]).unwrap();
+
+        let result = Context::merge3_with(&base, &ours, &theirs, |_| Some(Resolution::Remove));
+        assert!(result.is_clean());
+        assert!(result.merged.get("a").is_none());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::diff::key_segment;
+use crate::path::{Path, Segment};
+use crate::value::unwrap;
+use crate::{Change, Context, Op, Transform};
+use serde_value::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+
+/// Identifies a subscription of an [`ObservableContext`], to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(usize);
+
+/// A subscriber callback, invoked with the changes concerning it.
+type Callback = dyn FnMut(&[Change]);
+
+struct Subscription {
+    id: SubscriptionId,
+    pattern: String,
+    callback: Box<Callback>,
+}
+
+/// Returns `true` if `path`, one of its ancestors or a value below it (in `value`) matches the
+/// pattern.
+fn touches(pattern: &str, path: &Path, value: Option<&Value>) -> bool {
+    let segments = path.segments();
+    if (1..=segments.len()).any(|len| Path::from(segments[..len].to_vec()).matches(pattern)) {
+        return true;
+    }
+    let mut stack: Vec<(Path, &Value)> = value
+        .map(|value| (path.clone(), value))
+        .into_iter()
+        .collect();
+    while let Some((path, value)) = stack.pop() {
+        match unwrap(value) {
+            Value::Map(map) => stack.extend(
+                map.iter()
+                    .map(|(key, value)| (path.join(key_segment(key)), value)),
+            ),
+            Value::Seq(seq) => stack.extend(
+                seq.iter()
+                    .enumerate()
+                    .map(|(index, value)| (path.join(Segment::Index(index)), value)),
+            ),
+            _ => {}
+        }
+        if path.matches(pattern) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Returns `true` if a subscription to `pattern` is concerned by a change.
+fn concerns(pattern: &str, change: &Change) -> bool {
+    match change {
+        Change::Added { path, value } | Change::Removed { path, value } => {
+            touches(pattern, path, Some(value))
+        }
+        Change::Changed { path, old, new } => {
+            touches(pattern, path, Some(old)) || touches(pattern, path, Some(new))
+        }
+    }
+}
+
+/// A `Context` notifying subscribers of the changes made to it.
+///
+/// Subscribers register a glob pattern (see [`Path::matches`]) and are called with the changes
+/// concerning it: those at a matching path, below one, or replacing a value that held one. Every
+/// mutation is a transaction, so that a subscriber is called at most once per `merge`, patch or
+/// [`ObservableContext::transaction`], with all the changes it made at once. Reads go through
+/// `Deref` to the wrapped `Context`.
+///
+/// Example:
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let initial = oxidex::Context::from_args(["--set", "db.host=localhost", "--set", "db.port=0"]).unwrap();
+/// let mut context = oxidex::ObservableContext::new(initial);
+/// let events = Rc::new(RefCell::new(Vec::new()));
+/// let sink = events.clone();
+/// context.subscribe("db.*", move |changes| sink.borrow_mut().push(changes.len()));
+///
+/// context.merge(oxidex::Context::from_args(["--set", "db.host=h", "--set", "db.port=1", "--set", "debug=true"]).unwrap());
+/// context.set_path("debug", serde_value::Value::Bool(false)).unwrap();
+///
+/// assert_eq!(*events.borrow(), vec![2]);
+/// assert_eq!(context.get_path("db.port").unwrap(), &serde_value::Value::U64(1));
+/// ```
+pub struct ObservableContext {
+    context: Context,
+    subscriptions: Vec<Subscription>,
+    next_id: usize,
+}
+
+impl fmt::Debug for ObservableContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let patterns: Vec<&str> = self
+            .subscriptions
+            .iter()
+            .map(|subscription| subscription.pattern.as_str())
+            .collect();
+        f.debug_struct("ObservableContext")
+            .field("context", &self.context)
+            .field("subscriptions", &patterns)
+            .finish()
+    }
+}
+
+impl Deref for ObservableContext {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl From<Context> for ObservableContext {
+    fn from(context: Context) -> Self {
+        ObservableContext::new(context)
+    }
+}
+
+impl ObservableContext {
+    /// Wraps a `Context`, without subscribers.
+    pub fn new(context: Context) -> ObservableContext {
+        ObservableContext {
+            context,
+            subscriptions: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Returns the wrapped `Context`.
+    pub fn into_inner(self) -> Context {
+        self.context
+    }
+
+    /// Registers a callback invoked with the changes concerning a glob pattern.
+    ///
+    /// `pattern`: The glob pattern of the paths to observe, such as `db.*` or `**.password`.
+    /// `callback`: The function invoked with the changes of each transaction, ordered by path.
+    ///
+    /// Returns an identifier to cancel the subscription with [`ObservableContext::unsubscribe`].
+    pub fn subscribe<F>(&mut self, pattern: &str, callback: F) -> SubscriptionId
+    where
+        F: FnMut(&[Change]) + 'static,
+    {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscriptions.push(Subscription {
+            id,
+            pattern: pattern.to_string(),
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Cancels a subscription.
+    ///
+    /// Returns `true` if the subscription existed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let count = self.subscriptions.len();
+        self.subscriptions
+            .retain(|subscription| subscription.id != id);
+        self.subscriptions.len() != count
+    }
+
+    /// Runs a fallible transaction on the `Context`.
+    ///
+    /// If the function fails, the `Context` is restored and nobody is notified; otherwise each
+    /// subscriber concerned is notified once with all the changes made.
+    ///
+    /// `f`: The function modifying the `Context`.
+    ///
+    /// # Errors
+    /// - Returns the error of `f`.
+    pub fn try_transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Context) -> Result<T, E>,
+    {
+        let old = self.context.clone();
+        let result = self.context.try_audited(f)?;
+        self.notify(&old);
+        Ok(result)
+    }
+
+    /// Runs a transaction on the `Context`, then notifies each subscriber concerned once with all
+    /// the changes made.
+    ///
+    /// `f`: The function modifying the `Context`.
+    pub fn transaction<T, F>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Context) -> T,
+    {
+        let old = self.context.clone();
+        let result = f(&mut self.context);
+        self.notify(&old);
+        result
+    }
+
+    fn notify(&mut self, old: &Context) {
+        let diff = old.diff(&self.context);
+        if diff.is_empty() {
+            return;
+        }
+        for subscription in &mut self.subscriptions {
+            let changes: Vec<Change> = diff
+                .changes()
+                .iter()
+                .filter(|change| concerns(&subscription.pattern, change))
+                .cloned()
+                .collect();
+            if !changes.is_empty() {
+                (subscription.callback)(&changes);
+            }
+        }
+    }
+
+    /// Inserts a key-value pair, like [`Context::insert`].
+    pub fn insert(&mut self, k: String, v: Value) {
+        self.transaction(|context| context.insert(k, v))
+    }
+
+    /// Adds key-value pairs, like [`Context::extend`].
+    pub fn extend(&mut self, data: BTreeMap<String, Value>) {
+        self.transaction(|context| context.extend(data))
+    }
+
+    /// Sets the value at a path, like [`Context::set_path`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid or leads through a scalar value.
+    pub fn set_path(&mut self, path: &str, v: Value) -> crate::Result<Option<Value>> {
+        self.try_transaction(|context| context.set_path(path, v))
+    }
+
+    /// Merges another `Context`, like [`Context::merge`].
+    pub fn merge(&mut self, other: Context) {
+        self.transaction(|context| context.merge(other))
+    }
+
+    /// Applies a JSON Merge Patch, like [`Context::apply_merge_patch`].
+    pub fn apply_merge_patch(&mut self, patch: &Context) {
+        self.transaction(|context| context.apply_merge_patch(patch))
+    }
+
+    /// Applies a JSON Patch, like [`Context::apply_json_patch`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Patch` variant if an operation fails; nobody is notified then.
+    pub fn apply_json_patch(&mut self, patch: &[Op]) -> crate::Result<()> {
+        self.try_transaction(|context| context.apply_json_patch(patch))
+    }
+
+    /// Applies a transform, like [`Context::apply_transform`].
+    ///
+    /// # Errors
+    /// - Returns an `Error::Transform` variant if a step fails; nobody is notified then.
+    pub fn apply_transform(&mut self, transform: &Transform) -> crate::Result<()> {
+        self.try_transaction(|context| context.apply_transform(transform))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn recorder(context: &mut ObservableContext, pattern: &str) -> Rc<RefCell<Vec<Vec<String>>>> {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = events.clone();
+        context.subscribe(pattern, move |changes| {
+            sink.borrow_mut().push(
+                changes
+                    .iter()
+                    .map(|change| change.path().to_string())
+                    .collect(),
+            );
+        });
+        events
+    }
+
+    #[test]
+    fn test_subscribers_see_nested_and_replaced_values() {
+        let mut context = ObservableContext::new(
+            Context::from_args(["--set", "db.host=h", "--set", "db.pool.size=1"]).unwrap(),
+        );
+        let db = recorder(&mut context, "db.*");
+        let size = recorder(&mut context, "**.size");
+
+        context.set_path("db.pool.size", Value::U64(2)).unwrap();
+        assert!(context.set_path("db.host.x", Value::Unit).is_err());
+        context.insert("db".to_string(), Value::Unit);
+        context.insert("other".to_string(), Value::U64(1));
+
+        assert_eq!(
+            *db.borrow(),
+            vec![vec!["db.pool.size".to_string()], vec!["db".to_string()]]
+        );
+        assert_eq!(
+            *size.borrow(),
+            vec![vec!["db.pool.size".to_string()], vec!["db".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_failed_transactions_roll_back_silently() {
+        let mut context = ObservableContext::new(Context::new());
+        let all = recorder(&mut context, "**");
+        let result: Result<(), &str> = context.try_transaction(|context| {
+            context.insert("a".to_string(), Value::U64(1));
+            Err("nope")
+        });
+
+        assert!(result.is_err());
+        assert!(context.get("a").is_none());
+        let id = context.subscribe("a", |_| panic!("unsubscribed"));
+        assert!(context.unsubscribe(id));
+        context.insert("a".to_string(), Value::U64(1));
+        assert_eq!(*all.borrow(), vec![vec!["a".to_string()]]);
+    }
+
+    #[test]
+    fn test_failed_transactions_are_not_audited() {
+        let log = crate::AuditLog::new("ops");
+        let mut inner = Context::new();
+        inner.attach_audit(log.clone());
+        let mut context = ObservableContext::new(inner);
+        let result: Result<(), &str> = context.try_transaction(|context| {
+            context.insert("a".to_string(), Value::U64(1));
+            Err("nope")
+        });
+
+        assert!(result.is_err());
+        assert!(log.is_empty());
+        context.insert("b".to_string(), Value::U64(1));
+        assert_eq!(log.len(), 1);
+    }
+}
@@ -0,0 +1,614 @@
+use crate::diff::key_segment;
+use crate::entries::Node;
+use crate::path::{contains_node, descend_mut, find_node, lookup_mut, resolve_key, Path, Segment};
+use crate::secret::carry_secrets;
+use crate::value::{equivalent, unwrap, unwrap_mut};
+use crate::{Context, Error};
+use serde::{Deserialize, Serialize};
+use serde_value::Value;
+
+/// Serializes the paths of an [`Op`] as JSON Pointers, as RFC 6902 requires.
+mod pointer {
+    use crate::path::Path;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&path.to_pointer())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Path, D::Error> {
+        Path::from_pointer(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A JSON Patch (RFC 6902) operation.
+///
+/// Operations (de)serialize to the RFC representation, with JSON Pointer paths, so a patch can be
+/// read with `serde_json` (or any other serde format) as a `Vec<Op>`.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "json")] {
+/// let patch: Vec<oxidex::Op> = serde_json::from_str(r#"[
+///     {"op": "replace", "path": "/db/port", "value": 5433},
+///     {"op": "add", "path": "/features/-", "value": "beta"}
+/// ]"#).unwrap();
+///
+/// assert_eq!(patch[0], oxidex::Op::Replace { path: "db.port".parse().unwrap(), value: serde_value::Value::U64(5433) });
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Op {
+    /// Adds a value to a map, or inserts it into a sequence (`-` appends).
+    Add {
+        /// The location of the new value.
+        #[serde(with = "pointer")]
+        path: Path,
+        /// The value to add.
+        value: Value,
+    },
+    /// Removes an existing value.
+    Remove {
+        /// The location of the value to remove.
+        #[serde(with = "pointer")]
+        path: Path,
+    },
+    /// Replaces an existing value.
+    Replace {
+        /// The location of the value to replace.
+        #[serde(with = "pointer")]
+        path: Path,
+        /// The new value.
+        value: Value,
+    },
+    /// Removes a value and adds it at another location.
+    Move {
+        /// The location of the value to move.
+        #[serde(with = "pointer")]
+        from: Path,
+        /// The new location of the value.
+        #[serde(with = "pointer")]
+        path: Path,
+    },
+    /// Adds a copy of a value at another location.
+    Copy {
+        /// The location of the value to copy.
+        #[serde(with = "pointer")]
+        from: Path,
+        /// The location of the copy.
+        #[serde(with = "pointer")]
+        path: Path,
+    },
+    /// Checks that a value equals the expected one.
+    Test {
+        /// The location of the value to check.
+        #[serde(with = "pointer")]
+        path: Path,
+        /// The expected value.
+        value: Value,
+    },
+}
+
+/// Returns the sequence index designated by a segment; `-` designates the end of the sequence.
+fn index(segment: &Segment, len: usize) -> Option<usize> {
+    match segment {
+        Segment::Index(index) => Some(*index),
+        Segment::Key(key) if key == "-" => Some(len),
+        Segment::Key(key) if key == "0" || !key.starts_with('0') => key.parse().ok(),
+        Segment::Key(_) => None,
+    }
+}
+
+/// The parent of a location: a map or a sequence node, or a value inside a leaf.
+enum Parent<'a> {
+    Node(&'a mut Node),
+    Value(&'a mut Value),
+}
+
+/// Returns the parent of `path` along with the last segment of `path`.
+fn parent<'a, 'b>(root: &'a mut Node, path: &'b Path) -> crate::Result<(Parent<'a>, &'b Segment)> {
+    let (last, parent) = path
+        .segments()
+        .split_last()
+        .ok_or_else(|| Error::Patch("the root of a context cannot be removed".to_string()))?;
+    let missing = || {
+        Error::Patch(format!(
+            "no value at '{}'",
+            path.parent().unwrap_or_default().to_pointer()
+        ))
+    };
+    let parent = match descend_mut(root, parent).ok_or_else(missing)? {
+        (Node::Leaf(value), rest) => {
+            Parent::Value(unwrap_mut(lookup_mut(value, rest).ok_or_else(missing)?))
+        }
+        (node, _) => Parent::Node(node),
+    };
+    Ok((parent, last))
+}
+
+/// Inserts `item` into `seq` at the position designated by `segment`.
+fn insert_item<T>(seq: &mut Vec<T>, segment: &Segment, item: T, path: &Path) -> crate::Result<()> {
+    let Some(index) = index(segment, seq.len()).filter(|index| *index <= seq.len()) else {
+        return Err(Error::Patch(format!(
+            "invalid index at '{}'",
+            path.to_pointer()
+        )));
+    };
+    seq.insert(index, item);
+    Ok(())
+}
+
+/// Removes the item of `seq` at the position designated by `segment`.
+fn remove_item<T>(seq: &mut Vec<T>, segment: &Segment) -> Option<T> {
+    match index(segment, seq.len()) {
+        Some(index) if index < seq.len() => Some(seq.remove(index)),
+        _ => None,
+    }
+}
+
+fn add(root: &mut Node, path: &Path, value: Node) -> crate::Result<()> {
+    if path.is_root() {
+        *root = value;
+        return Ok(());
+    }
+    match parent(root, path)? {
+        (Parent::Node(node), segment) => match node.map_mut() {
+            Some(map) => {
+                map.insert(resolve_key(map, segment), value);
+            }
+            None => {
+                let seq = node.seq_mut().expect("the parent node is a sequence");
+                insert_item(seq, segment, value, path)?
+            }
+        },
+        (Parent::Value(Value::Map(map)), segment) => {
+            map.insert(resolve_key(map, segment), value.into());
+        }
+        (Parent::Value(Value::Seq(seq)), segment) => insert_item(seq, segment, value.into(), path)?,
+        _ => {
+            return Err(Error::Patch(format!(
+                "cannot add a value inside a scalar at '{}'",
+                path.to_pointer()
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn remove(root: &mut Node, path: &Path) -> crate::Result<Node> {
+    let missing = || Error::Patch(format!("no value at '{}'", path.to_pointer()));
+    // Shared values are only copied when there is something to remove.
+    if !path.is_root() && !contains_node(root, path.segments()) {
+        return Err(missing());
+    }
+    match parent(root, path)? {
+        (Parent::Node(node), segment) => match node.map_mut() {
+            Some(map) => map.remove(&resolve_key(map, segment)),
+            None => remove_item(
+                node.seq_mut().expect("the parent node is a sequence"),
+                segment,
+            ),
+        },
+        (Parent::Value(Value::Map(map)), segment) => {
+            map.remove(&resolve_key(map, segment)).map(Node::from)
+        }
+        (Parent::Value(Value::Seq(seq)), segment) => remove_item(seq, segment).map(Node::from),
+        _ => None,
+    }
+    .ok_or_else(missing)
+}
+
+/// Returns the location of a value just added at `path`, with a trailing `-` replaced by the index
+/// of the last item of the sequence.
+fn placed(root: &Node, path: &Path) -> Path {
+    let len = match path.segments().split_last() {
+        Some((Segment::Key(key), parent)) if key == "-" => match find_node(root, parent) {
+            Some(Node::Leaf(value)) => match unwrap(&value) {
+                Value::Seq(seq) => seq.len(),
+                _ => 0,
+            },
+            Some(node) => node.seq().map_or(0, <[Node]>::len),
+            None => 0,
+        },
+        _ => 0,
+    };
+    match (len, path.parent()) {
+        (1.., Some(parent)) => parent.join(Segment::Index(len - 1)),
+        _ => path.clone(),
+    }
+}
+
+fn apply(root: &mut Node, op: &Op) -> crate::Result<()> {
+    match op {
+        Op::Add { path, value } => add(root, path, Node::from(value.clone())),
+        Op::Remove { path } => remove(root, path).map(drop),
+        Op::Replace { path, value } => {
+            let missing = || Error::Patch(format!("no value at '{}'", path.to_pointer()));
+            let (target, rest) = descend_mut(root, path.segments()).ok_or_else(missing)?;
+            match rest.is_empty() {
+                true => *target = Node::from(value.clone()),
+                // The target lies inside a leaf.
+                false => *lookup_mut(target.value_mut(), rest).ok_or_else(missing)? = value.clone(),
+            }
+            Ok(())
+        }
+        Op::Move { from, path } => {
+            if from != path && from.contains(path) {
+                return Err(Error::Patch(format!(
+                    "cannot move '{}' into one of its children",
+                    from.to_pointer()
+                )));
+            }
+            let value = remove(root, from)?;
+            add(root, path, value)
+        }
+        Op::Copy { from, path } => {
+            let value = find_node(root, from.segments())
+                .ok_or_else(|| Error::Patch(format!("no value at '{}'", from.to_pointer())))?;
+            add(root, path, value)
+        }
+        Op::Test { path, value } => match find_node(root, path.segments()) {
+            Some(actual) if equivalent(&actual.as_value(), value) => Ok(()),
+            _ => Err(Error::Patch(format!(
+                "test failed at '{}'",
+                path.to_pointer()
+            ))),
+        },
+    }
+}
+
+/// Appends to `patch` the operations turning `old` into `new`, both located at `path`.
+fn patch_values(path: &Path, old: &Value, new: &Value, patch: &mut Vec<Op>) {
+    match (unwrap(old), unwrap(new)) {
+        (Value::Map(old), Value::Map(new)) => {
+            // A JSON Pointer only designates string keys, so a map gaining another kind of key
+            // (e.g. an integer key read from YAML) is replaced as a whole.
+            if new
+                .keys()
+                .any(|key| !matches!(key, Value::String(_)) && !old.contains_key(key))
+            {
+                patch.push(Op::Replace {
+                    path: path.clone(),
+                    value: Value::Map(new.clone()),
+                });
+                return;
+            }
+            for (key, value) in old {
+                let child = path.join(key_segment(key));
+                match new.get(key) {
+                    Some(other) => patch_values(&child, value, other, patch),
+                    None => patch.push(Op::Remove { path: child }),
+                }
+            }
+            for (key, value) in new.iter().filter(|(key, _)| !old.contains_key(key)) {
+                patch.push(Op::Add {
+                    path: path.join(key_segment(key)),
+                    value: value.clone(),
+                });
+            }
+        }
+        (Value::Seq(old), Value::Seq(new)) => patch_seqs(path, old, new, patch),
+        (a, b) if !equivalent(a, b) => patch.push(Op::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// The largest number of cells in the table aligning two sequences, beyond which
+/// [`patch_seqs`] replaces the whole sequence instead.
+const MAX_ALIGNMENT_CELLS: usize = 1 << 20;
+
+/// Appends to `patch` the operations turning the sequence `old` into `new`, located at `path`.
+///
+/// Both sequences are aligned with the fewest item removals, additions and replacements, so that
+/// inserting or removing an item anywhere is a single operation; replaced items are patched in
+/// place. Past their common prefix and suffix, sequences too long to be aligned within
+/// [`MAX_ALIGNMENT_CELLS`] are replaced as a whole.
+fn patch_seqs(path: &Path, old: &[Value], new: &[Value], patch: &mut Vec<Op>) {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| equivalent(a, b))
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| equivalent(a, b))
+        .count();
+    let (old, whole) = (&old[prefix..old.len() - suffix], new);
+    let new = &whole[prefix..whole.len() - suffix];
+    if (old.len() + 1).saturating_mul(new.len() + 1) > MAX_ALIGNMENT_CELLS {
+        patch.push(Op::Replace {
+            path: path.clone(),
+            value: Value::Seq(whole.to_vec()),
+        });
+        return;
+    }
+    // `cost[i][j]` is the number of edits turning `old[i..]` into `new[j..]`.
+    let mut cost = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..=old.len()).rev() {
+        for j in (0..=new.len()).rev() {
+            cost[i][j] = match (i < old.len(), j < new.len()) {
+                (true, true) => (cost[i + 1][j + 1] + usize::from(!equivalent(&old[i], &new[j])))
+                    .min(cost[i + 1][j] + 1)
+                    .min(cost[i][j + 1] + 1),
+                (true, false) => old.len() - i,
+                (false, _) => new.len() - j,
+            };
+        }
+    }
+    // The patched sequence is `new[..j]` followed by `old[i..]`, so the next edit is at `j`.
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let child = path.join(Segment::Index(prefix + j));
+        if i < old.len()
+            && j < new.len()
+            && cost[i][j] == cost[i + 1][j + 1] + usize::from(!equivalent(&old[i], &new[j]))
+        {
+            patch_values(&child, &old[i], &new[j], patch);
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && cost[i][j] == cost[i + 1][j] + 1 {
+            patch.push(Op::Remove { path: child });
+            i += 1;
+        } else {
+            patch.push(Op::Add {
+                path: child,
+                value: new[j].clone(),
+            });
+            j += 1;
+        }
+    }
+}
+
+impl Context {
+    /// Applies a JSON Patch (RFC 6902) to the `Context`.
+    ///
+    /// The patch is applied atomically: if any operation fails, the `Context` is left unchanged.
+    /// `test` operations compare values regardless of their numeric type, and a path segment
+    /// such as `3` also designates a map key that is not a string, like an integer key read from
+    /// YAML or TOML. Secret values that are moved or copied are also secret at their new location
+    /// (see [`Context::mark_secret`]).
+    ///
+    /// # Errors
+    /// - Returns an `Error::Patch` variant if an operation targets a missing value, an invalid
+    ///   sequence index, if a `test` fails or if the result is not a map.
+    ///
+    /// # Example
+    /// ```rust
+    /// use oxidex::Op;
+    /// use serde_value::Value;
+    ///
+    /// let mut context = oxidex::Context::from_args(["--set", "db.port=5432", "--set", "tags[0]=a"]).unwrap();
+    /// context.apply_json_patch(&[
+    ///     Op::Test { path: "db.port".parse().unwrap(), value: Value::U64(5432) },
+    ///     Op::Replace { path: "db.port".parse().unwrap(), value: Value::U64(5433) },
+    ///     Op::Add { path: oxidex::Path::from_pointer("/tags/-").unwrap(), value: Value::String("b".to_string()) },
+    /// ]).unwrap();
+    /// assert_eq!(context.get_path("db.port").unwrap(), &Value::U64(5433));
+    /// assert_eq!(context.get_path("tags[1]").unwrap(), &Value::String("b".to_string()));
+    ///
+    /// let failing = [
+    ///     Op::Remove { path: "db.port".parse().unwrap() },
+    ///     Op::Remove { path: "db.missing".parse().unwrap() },
+    /// ];
+    /// assert!(context.apply_json_patch(&failing).is_err());
+    /// assert_eq!(context.get_path("db.port").unwrap(), &Value::U64(5433));
+    /// ```
+    pub fn apply_json_patch(&mut self, patch: &[Op]) -> crate::Result<()> {
+        self.audited(|context| {
+            let mut root = context.to_node();
+            let mut secrets = context.secrets.clone();
+            for op in patch {
+                apply(&mut root, op)?;
+                if let Op::Move { from, path } | Op::Copy { from, path } = op {
+                    let path = placed(&root, path);
+                    if let Some(node) = find_node(&root, path.segments()) {
+                        carry_secrets(&mut secrets, from, &path, &node);
+                    }
+                }
+            }
+            context.inner = Context::from_node(root)
+                .map_err(|_| Error::Patch("the patched context is not a map".to_string()))?
+                .inner;
+            context.secrets = secrets;
+            Ok(())
+        })
+    }
+
+    /// Computes a JSON Patch (RFC 6902) that turns this `Context` into `other`.
+    ///
+    /// The patch only touches the values that differ, using `add`, `remove` and `replace`
+    /// operations. Maps are compared key by key, and sequences are aligned with the fewest item
+    /// removals, additions and replacements, so that inserting or removing an item anywhere is a
+    /// single operation; a sequence whose changed part is too long to be aligned cheaply (over a
+    /// thousand items on both sides) is replaced as a whole. A map gaining a key that is not a string, such as an integer key read
+    /// from YAML, is replaced as a whole, since a JSON Pointer cannot tell `3` from `"3"`.
+    ///
+    /// Example:
+    /// ```
+    /// let old = oxidex::Context::from_args(["--set", "tags[0]=a", "--set", "tags[1]=b", "--set", "tags[2]=c"]).unwrap();
+    /// let new = oxidex::Context::from_args(["--set", "tags[0]=b", "--set", "tags[1]=c", "--set", "debug=true"]).unwrap();
+    ///
+    /// let patch = old.json_patch_to(&new);
+    /// assert_eq!(patch, [
+    ///     oxidex::Op::Remove { path: "tags[0]".parse().unwrap() },
+    ///     oxidex::Op::Add { path: "debug".parse().unwrap(), value: serde_value::Value::Bool(true) },
+    /// ]);
+    ///
+    /// let mut patched = old.clone();
+    /// patched.apply_json_patch(&patch).unwrap();
+    /// assert!(patched.diff(&new).is_empty());
+    /// ```
+    pub fn json_patch_to(&self, other: &Context) -> Vec<Op> {
+        let mut patch = Vec::new();
+        for (key, value) in &self.inner {
+            let path = Path::root().join(Segment::Key(key.clone()));
+            match other.inner.node(key) {
+                Some(new) => patch_values(&path, &value, &new.as_value(), &mut patch),
+                None => patch.push(Op::Remove { path }),
+            }
+        }
+        for (key, value) in other
+            .inner
+            .iter()
+            .filter(|(key, _)| !self.inner.contains_key(key))
+        {
+            patch.push(Op::Add {
+                path: Path::root().join(Segment::Key(key.clone())),
+                value: value.into_owned(),
+            });
+        }
+        patch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(args: &[&str]) -> Context {
+        Context::from_args(args).unwrap()
+    }
+
+    #[test]
+    fn test_move_and_copy() {
+        let mut context = ctx(&["--set", "a.b=1", "--set", "list[0]=x"]);
+        context
+            .apply_json_patch(&[
+                Op::Copy {
+                    from: Path::from_pointer("/a/b").unwrap(),
+                    path: Path::from_pointer("/list/0").unwrap(),
+                },
+                Op::Move {
+                    from: Path::from_pointer("/a").unwrap(),
+                    path: Path::from_pointer("/c").unwrap(),
+                },
+            ])
+            .unwrap();
+        assert!(context
+            .diff(&ctx(&[
+                "--set",
+                "c.b=1",
+                "--set",
+                "list[0]=1",
+                "--set",
+                "list[1]=x"
+            ]))
+            .is_empty());
+
+        let into_child = Op::Move {
+            from: Path::from_pointer("/c").unwrap(),
+            path: Path::from_pointer("/c/d").unwrap(),
+        };
+        assert!(matches!(
+            context.apply_json_patch(&[into_child]),
+            Err(Error::Patch(_))
+        ));
+    }
+
+    #[test]
+    fn test_patch_roundtrip_with_sequences() {
+        let old = ctx(&[
+            "--set", "s[0]=1", "--set", "s[1]=2", "--set", "s[2]=3", "--set", "t[0]=1", "--set",
+            "t[1]=2",
+        ]);
+        let new = ctx(&[
+            "--set", "s[0]=9", "--set", "t[0]=1", "--set", "t[1]=2", "--set", "t[2]=3",
+        ]);
+        let patch = old.json_patch_to(&new);
+        let mut patched = old.clone();
+        patched.apply_json_patch(&patch).unwrap();
+        assert!(patched.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn test_patch_aligns_sequences() {
+        let list = |items: &[&str]| {
+            let args: Vec<String> = items
+                .iter()
+                .enumerate()
+                .flat_map(|(index, item)| ["--set".to_string(), format!("l[{}]={}", index, item)])
+                .collect();
+            Context::from_args(args).unwrap()
+        };
+        let cases: [(&[&str], &[&str], usize); 5] = [
+            (&["a", "b", "c", "d"], &["b", "c", "d"], 1),
+            (&["a", "b", "c"], &["a", "x", "b", "c"], 1),
+            (&["a", "b", "c", "d"], &["a", "x", "c", "y"], 2),
+            (&["a", "b", "c"], &["c", "b", "a"], 2),
+            (&["a", "b", "c"], &["c"], 2),
+        ];
+        for (old, new, len) in cases {
+            let (old, new) = (list(old), list(new));
+            let patch = old.json_patch_to(&new);
+            assert_eq!(patch.len(), len, "{:?}", patch);
+            let mut patched = old.clone();
+            patched.apply_json_patch(&patch).unwrap();
+            assert!(patched.diff(&new).is_empty(), "{:?}", patch);
+        }
+        let patch = list(&["a", "b", "c", "d"]).json_patch_to(&list(&["b", "c", "d"]));
+        assert_eq!(
+            patch,
+            [Op::Remove {
+                path: "l[0]".parse().unwrap()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_patch_roundtrip_with_non_string_keys() {
+        let map = |entries: &[(u64, &str)]| {
+            Value::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| (Value::U64(*key), Value::String(value.to_string())))
+                    .collect(),
+            )
+        };
+        let mut old = Context::new();
+        old.insert("m".to_string(), map(&[(1, "a"), (2, "b"), (4, "d")]));
+        for entries in [&[(1, "x"), (4, "d")][..], &[(1, "a"), (2, "b"), (3, "c")]] {
+            let mut new = Context::new();
+            new.insert("m".to_string(), map(entries));
+            for (from, to) in [(&old, &new), (&new, &old)] {
+                let mut patched = from.clone();
+                patched.apply_json_patch(&from.json_patch_to(to)).unwrap();
+                assert_eq!(patched.get("m"), to.get("m"));
+            }
+        }
+        assert_eq!(old.get_path("m.4"), Some(&Value::String("d".to_string())));
+    }
+
+    #[test]
+    fn test_long_sequences_are_replaced() {
+        let list = |range: std::ops::Range<u64>| {
+            let mut context = Context::new();
+            context.insert("l".to_string(), Value::Seq(range.map(Value::U64).collect()));
+            context
+        };
+        let (old, new) = (list(0..2000), list(5000..7000));
+        let patch = old.json_patch_to(&new);
+        assert!(matches!(patch.as_slice(), [Op::Replace { .. }]));
+        let mut patched = old.clone();
+        patched.apply_json_patch(&patch).unwrap();
+        assert!(patched.diff(&new).is_empty());
+
+        let patch = list(0..100_000).json_patch_to(&list(1..100_000));
+        assert_eq!(patch.len(), 1);
+    }
+
+    #[test]
+    fn test_root_must_stay_a_map() {
+        let mut context = ctx(&["--set", "a=1"]);
+        let patch = [Op::Replace {
+            path: Path::root(),
+            value: Value::U8(1),
+        }];
+        assert!(context.apply_json_patch(&patch).is_err());
+        assert_eq!(context.get("a"), Some(&Value::U64(1)));
+    }
+}
@@ -0,0 +1,861 @@
+use crate::entries::{Entries, Node};
+use crate::value::{to_inline, unwrap_mut};
+use crate::{Context, Error};
+use serde_value::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A single step of a [`Path`]: either a map key or a sequence index.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Segment {
+    /// A key inside a `Value::Map` (or the `Context` itself).
+    Key(String),
+    /// A position inside a `Value::Seq`.
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Key(key) if needs_quotes(key) => {
+                write!(
+                    f,
+                    "[\"{}\"]",
+                    key.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            }
+            Segment::Key(key) => write!(f, "{}", key),
+            Segment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+/// A location inside a `Context`, written as dotted keys with bracketed indices (`db.hosts[0].name`).
+///
+/// A key made only of digits also matches a sequence index, so `db.hosts.0.name` resolves to the
+/// same value as the example above. Keys that are empty or contain `.`, `[` or `]` are written
+/// quoted inside brackets, with `"` and `\` escaped by a backslash (`labels["app.kubernetes.io"]`).
+///
+/// # Example
+/// ```
+/// let path: oxidex::Path = "db.hosts[0].name".parse().unwrap();
+/// assert_eq!(path.segments().len(), 4);
+/// assert_eq!(path.to_string(), "db.hosts[0].name");
+///
+/// let path: oxidex::Path = r#"labels["app.kubernetes.io"].name"#.parse().unwrap();
+/// assert_eq!(path.segments()[1], oxidex::Segment::Key("app.kubernetes.io".to_string()));
+/// assert_eq!(path.to_string(), r#"labels["app.kubernetes.io"].name"#);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    /// Creates an empty `Path`, which designates the root of a `Context`.
+    pub fn root() -> Path {
+        Path::default()
+    }
+
+    /// Returns the segments composing this path.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns `true` if the path designates the root of a `Context`.
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Appends a segment at the end of the path.
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    /// Returns a new path made of this path followed by `segment`.
+    pub fn join(&self, segment: Segment) -> Path {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// Returns the path of the parent value, or `None` for the root.
+    pub fn parent(&self) -> Option<Path> {
+        let (_, parent) = self.segments.split_last()?;
+        Some(Path::from(parent.to_vec()))
+    }
+
+    /// Returns `true` if `other` designates a value nested inside (or equal to) this one.
+    pub fn contains(&self, other: &Path) -> bool {
+        other.segments.starts_with(&self.segments)
+    }
+
+    /// Returns `true` if the path matches a glob pattern.
+    ///
+    /// Patterns are dotted paths where `*` matches any part of a key (`*password*`), `?` matches a
+    /// single character and a `**` segment matches any number of segments. Indices can be written
+    /// `items[0]`, `items.0` or `items[*]`.
+    ///
+    /// # Example
+    /// ```
+    /// let path: oxidex::Path = "db.primary.password".parse().unwrap();
+    /// assert!(path.matches("db.*.password"));
+    /// assert!(path.matches("**.*pass*"));
+    /// assert!(!path.matches("db.password"));
+    /// ```
+    pub fn matches(&self, pattern: &str) -> bool {
+        let Ok(pattern) = parts(pattern) else {
+            return false;
+        };
+        let pattern: Vec<&str> = pattern
+            .iter()
+            .map(|part| match part {
+                Part::Key(key) | Part::Bracket(key) => key.as_str(),
+            })
+            .collect();
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => key.clone(),
+                Segment::Index(index) => index.to_string(),
+            })
+            .collect();
+        glob(&pattern, &segments)
+    }
+
+    /// Parses a JSON Pointer (RFC 6901) such as `/db/hosts/0`.
+    ///
+    /// Every reference token becomes a [`Segment::Key`], which also matches sequence indices.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the pointer is not empty and does not start with `/`.
+    ///
+    /// # Example
+    /// ```
+    /// let path = oxidex::Path::from_pointer("/a~1b/c~0d/0").unwrap();
+    /// assert_eq!(path.to_pointer(), "/a~1b/c~0d/0");
+    /// assert_eq!(path.segments()[0], oxidex::Segment::Key("a/b".to_string()));
+    /// ```
+    pub fn from_pointer(pointer: &str) -> crate::Result<Path> {
+        if pointer.is_empty() {
+            return Ok(Path::root());
+        }
+        let tokens = pointer.strip_prefix('/').ok_or_else(|| {
+            Error::Path(format!("JSON pointer '{}' must start with '/'", pointer))
+        })?;
+        Ok(tokens
+            .split('/')
+            .map(|token| Segment::Key(token.replace("~1", "/").replace("~0", "~")))
+            .collect::<Vec<Segment>>()
+            .into())
+    }
+
+    /// Formats the path as a JSON Pointer (RFC 6901).
+    pub fn to_pointer(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+                Segment::Index(index) => format!("/{}", index),
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<Segment>> for Path {
+    fn from(segments: Vec<Segment>) -> Self {
+        Path { segments }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, segment) in self.segments.iter().enumerate() {
+            if position > 0 && matches!(segment, Segment::Key(key) if !needs_quotes(key)) {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+impl serde::Serialize for Path {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Path {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    /// Parses a dotted path such as `a.b[0].c` or `labels["app.kubernetes.io"]`.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if a bracket or a quote is not closed, an index is not a
+    ///   number, a key is empty or a character is out of place (such as the `]` of `a.b]`).
+    fn from_str(path: &str) -> crate::Result<Path> {
+        parts(path)?
+            .into_iter()
+            .map(|part| match part {
+                Part::Key(key) => Ok(Segment::Key(key)),
+                Part::Bracket(index) => index.parse::<usize>().map(Segment::Index).map_err(|_| {
+                    Error::Path(format!("invalid index '{}' in path '{}'", index, path))
+                }),
+            })
+            .collect::<crate::Result<Vec<Segment>>>()
+            .map(Path::from)
+    }
+}
+
+/// Returns `true` if a key must be quoted to be written in a path.
+fn needs_quotes(key: &str) -> bool {
+    key.is_empty() || key.contains(['.', '[', ']'])
+}
+
+/// A lexical part of a written path or pattern.
+enum Part {
+    /// A bare or quoted key.
+    Key(String),
+    /// The unquoted content of brackets, such as an index or `*`.
+    Bracket(String),
+}
+
+/// Splits a written path or pattern into keys and brackets.
+fn parts(path: &str) -> crate::Result<Vec<Part>> {
+    let error = |message: &str| Error::Path(format!("{} in path '{}'", message, path));
+    let mut parts = Vec::new();
+    if path.is_empty() {
+        return Ok(parts);
+    }
+    let mut chars = path.chars().peekable();
+    loop {
+        // Only the first key can be omitted, for paths starting with brackets.
+        if !(parts.is_empty() && chars.peek() == Some(&'[')) {
+            let mut key = String::new();
+            while let Some(c) = chars.next_if(|c| !matches!(c, '.' | '[' | ']')) {
+                key.push(c);
+            }
+            if key.is_empty() {
+                return Err(error("empty key"));
+            }
+            parts.push(Part::Key(key));
+        }
+        while chars.next_if_eq(&'[').is_some() {
+            parts.push(bracket(&mut chars).ok_or_else(|| error("unclosed bracket"))?);
+        }
+        match chars.next() {
+            None => return Ok(parts),
+            Some('.') => continue,
+            Some(c) => return Err(error(&format!("unexpected '{}'", c))),
+        }
+    }
+}
+
+/// Reads the content of brackets up to the closing `]`, or `None` if it is missing.
+fn bracket(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<Part> {
+    let mut content = String::new();
+    if chars.next_if_eq(&'"').is_none() {
+        while let Some(c) = chars.next_if(|c| *c != ']') {
+            content.push(c);
+        }
+        chars.next()?;
+        return Some(Part::Bracket(content));
+    }
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => content.push(chars.next()?),
+            c => content.push(c),
+        }
+    }
+    chars.next_if_eq(&']')?;
+    Some(Part::Key(content))
+}
+
+/// Matches path segments against glob pattern segments, `**` matching any number of segments.
+fn glob(pattern: &[&str], segments: &[String]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((&"**", rest)) => (0..=segments.len()).any(|skip| glob(rest, &segments[skip..])),
+        Some((part, rest)) => segments
+            .split_first()
+            .is_some_and(|(segment, others)| wildcard(part, segment) && glob(rest, others)),
+    }
+}
+
+/// Matches a text against a pattern where `*` matches any characters and `?` a single one.
+fn wildcard(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns the value designated by `segments` inside `value`.
+pub(crate) fn lookup<'a>(value: &'a Value, segments: &[Segment]) -> Option<&'a Value> {
+    segments
+        .iter()
+        .try_fold(value, |current, segment| match (current, segment) {
+            (Value::Map(map), segment) => map.get(&resolve_key(map, segment)),
+            (Value::Seq(seq), Segment::Index(index)) => seq.get(*index),
+            (Value::Seq(seq), Segment::Key(key)) => {
+                key.parse::<usize>().ok().and_then(|index| seq.get(index))
+            }
+            (Value::Option(Some(inner)), _) | (Value::Newtype(inner), _) => {
+                lookup(inner, std::slice::from_ref(segment))
+            }
+            _ => None,
+        })
+}
+
+/// Returns a mutable reference to the value designated by `segments` inside `value`.
+pub(crate) fn lookup_mut<'a>(value: &'a mut Value, segments: &[Segment]) -> Option<&'a mut Value> {
+    segments
+        .iter()
+        .try_fold(value, |current, segment| match (current, segment) {
+            (Value::Map(map), segment) => map.get_mut(&resolve_key(map, segment)),
+            (Value::Seq(seq), Segment::Index(index)) => seq.get_mut(*index),
+            (Value::Seq(seq), Segment::Key(key)) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| seq.get_mut(index)),
+            (Value::Option(Some(inner)), _) | (Value::Newtype(inner), _) => {
+                lookup_mut(inner, std::slice::from_ref(segment))
+            }
+            _ => None,
+        })
+}
+
+/// Returns the value designated by `segments` inside the root map of a `Context`.
+pub(crate) fn lookup_in<'a>(root: &'a Entries, segments: &[Segment]) -> Option<&'a Value> {
+    let (first, rest) = segments.split_first()?;
+    lookup_node(root.node(&map_key(first))?, rest)
+}
+
+/// Returns the node designated by `segments` inside the root map of a `Context` (see
+/// [`find_node`]).
+#[cfg(feature = "csv")]
+pub(crate) fn find_in(root: &Entries, segments: &[Segment]) -> Option<Node> {
+    let (first, rest) = segments.split_first()?;
+    find_node(root.node(&map_key(first))?, rest)
+}
+
+/// Returns `true` if `segments` designate a value inside the root map of a `Context`.
+fn contains_in(root: &Entries, segments: &[Segment]) -> bool {
+    let Some((first, rest)) = segments.split_first() else {
+        return false;
+    };
+    root.node(&map_key(first))
+        .is_some_and(|node| contains_node(node, rest))
+}
+
+/// Returns the value designated by `segments` inside `node`.
+fn lookup_node<'a>(node: &'a Node, segments: &[Segment]) -> Option<&'a Value> {
+    match descend(node, segments)? {
+        (Node::Leaf(value), rest) => lookup(value, rest),
+        (node, _) => Some(node.value()),
+    }
+}
+
+/// Follows `segments` through the maps and sequences of `node`, and returns the node reached,
+/// along with the segments left to follow inside its value if it is a leaf.
+fn descend<'a, 'b>(node: &'a Node, segments: &'b [Segment]) -> Option<(&'a Node, &'b [Segment])> {
+    match (node, segments.split_first()) {
+        (Node::Leaf(_), _) | (_, None) => Some((node, segments)),
+        (node, Some((first, rest))) => descend(node.child(first)?, rest),
+    }
+}
+
+/// Follows `segments` like [`descend`], copying the nodes on the way if they are shared.
+pub(crate) fn descend_mut<'a, 'b>(
+    node: &'a mut Node,
+    segments: &'b [Segment],
+) -> Option<(&'a mut Node, &'b [Segment])> {
+    match segments.split_first() {
+        Some((first, rest)) if !matches!(node, Node::Leaf(_)) => {
+            descend_mut(node.child_mut(first)?, rest)
+        }
+        _ => Some((node, segments)),
+    }
+}
+
+/// Returns `true` if `segments` designate a value inside `node`.
+pub(crate) fn contains_node(node: &Node, segments: &[Segment]) -> bool {
+    match descend(node, segments) {
+        Some((Node::Leaf(value), rest)) => lookup(value, rest).is_some(),
+        found => found.is_some(),
+    }
+}
+
+/// Returns the node designated by `segments` inside `node`: the map or sequence node itself,
+/// which stays shared, or a copy of a value found in a leaf.
+pub(crate) fn find_node(node: &Node, segments: &[Segment]) -> Option<Node> {
+    match descend(node, segments)? {
+        (Node::Leaf(value), rest) => lookup(value, rest).cloned().map(Node::from),
+        (node, _) => Some(node.clone()),
+    }
+}
+
+/// Returns the key designating `segment` inside a map.
+pub(crate) fn map_key(segment: &Segment) -> String {
+    match segment {
+        Segment::Key(key) => key.clone(),
+        Segment::Index(index) => index.to_string(),
+    }
+}
+
+/// Returns the key of `map` designated by `segment`.
+///
+/// Keys are matched as strings first, then by their inline form, so that `3` also designates the
+/// integer key read from YAML or TOML. A missing key is returned as a string key.
+pub(crate) fn resolve_key<V>(map: &BTreeMap<Value, V>, segment: &Segment) -> Value {
+    let key = Value::String(map_key(segment));
+    if map.contains_key(&key) {
+        return key;
+    }
+    map.keys()
+        .find(|other| !matches!(other, Value::String(_)) && Value::String(to_inline(other)) == key)
+        .cloned()
+        .unwrap_or(key)
+}
+
+/// Returns the position designated by `segment` inside a sequence of `len` items, where `len`
+/// itself appends.
+fn seq_index(segment: &Segment, len: usize) -> crate::Result<usize> {
+    match segment {
+        Segment::Index(index) => Some(*index),
+        Segment::Key(key) => key.parse::<usize>().ok(),
+    }
+    .filter(|index| *index <= len)
+    .ok_or_else(|| Error::Path(format!("invalid sequence index '{}'", segment)))
+}
+
+/// Checks that [`insert`] can store a value at the location designated by `segments` inside
+/// `target`, `None` standing for a value that does not exist yet.
+fn check_insert(target: Option<&Value>, segments: &[Segment]) -> crate::Result<()> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Ok(());
+    };
+    match (target, first) {
+        // Missing values become a map for a key and an empty sequence for an index.
+        (None | Some(Value::Unit | Value::Option(None)), Segment::Key(_)) => {
+            check_insert(None, rest)
+        }
+        (None | Some(Value::Unit | Value::Option(None)), segment) => {
+            seq_index(segment, 0)?;
+            check_insert(None, rest)
+        }
+        (Some(Value::Option(Some(inner)) | Value::Newtype(inner)), _) => {
+            check_insert(Some(inner), segments)
+        }
+        (Some(Value::Map(map)), segment) => check_insert(map.get(&resolve_key(map, segment)), rest),
+        (Some(Value::Seq(seq)), segment) => {
+            check_insert(seq.get(seq_index(segment, seq.len())?), rest)
+        }
+        (Some(_), segment) => Err(Error::Path(format!(
+            "cannot descend into a scalar value with '{}'",
+            segment
+        ))),
+    }
+}
+
+/// Inserts `value` at the location designated by `segments` inside `value`, creating missing
+/// maps and sequences along the way, and returns the value previously stored there.
+///
+/// The location is checked first, so that a failing insertion leaves `target` untouched.
+pub(crate) fn insert(
+    target: &mut Value,
+    segments: &[Segment],
+    value: Value,
+) -> crate::Result<Option<Value>> {
+    check_insert(Some(target), segments)?;
+    Ok(insert_checked(target, segments, value))
+}
+
+/// Inserts `value` at a location validated by [`check_insert`].
+fn insert_checked(target: &mut Value, segments: &[Segment], value: Value) -> Option<Value> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Some(std::mem::replace(target, value));
+    };
+    if matches!(target, Value::Unit | Value::Option(None)) {
+        *target = match first {
+            Segment::Key(_) => Value::Map(BTreeMap::new()),
+            Segment::Index(_) => Value::Seq(Vec::new()),
+        };
+    }
+    match target {
+        Value::Option(Some(inner)) | Value::Newtype(inner) => {
+            insert_checked(inner, segments, value)
+        }
+        Value::Map(map) => {
+            let key = resolve_key(map, first);
+            match rest.is_empty() {
+                true => map.insert(key, value),
+                false => insert_checked(map.entry(key).or_insert(Value::Unit), rest, value),
+            }
+        }
+        Value::Seq(seq) => {
+            let index = seq_index(first, seq.len()).expect("the location was checked");
+            if index == seq.len() {
+                seq.push(Value::Unit);
+                insert_checked(&mut seq[index], rest, value);
+                return None;
+            }
+            insert_checked(&mut seq[index], rest, value)
+        }
+        _ => unreachable!("the location was checked"),
+    }
+}
+
+/// Checks that [`insert_node`] can store a value at the location designated by `segments` inside
+/// `node`.
+fn check_node(node: &Node, segments: &[Segment]) -> crate::Result<()> {
+    match (node, segments.split_first()) {
+        (Node::Leaf(value), _) => check_insert(Some(value), segments),
+        (_, None) => Ok(()),
+        (node, Some((first, rest))) => match node.child(first) {
+            Some(child) => check_node(child, rest),
+            None => {
+                if let Some(seq) = node.seq() {
+                    seq_index(first, seq.len())?;
+                }
+                check_insert(None, rest)
+            }
+        },
+    }
+}
+
+/// Inserts `value` at a location inside `node` validated by [`check_node`], copying only the
+/// nodes on the way.
+fn insert_node(node: &mut Node, segments: &[Segment], value: Node) -> Option<Node> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Some(std::mem::replace(node, value));
+    };
+    if let Node::Leaf(target) = node {
+        match target {
+            Value::Option(Some(_)) | Value::Newtype(_) => {
+                return insert_checked(target, segments, value.into()).map(Node::from)
+            }
+            // Missing values become a map for a key and an empty sequence for an index.
+            Value::Unit | Value::Option(None) => {
+                *node = Node::from(match first {
+                    Segment::Key(_) => Value::Map(BTreeMap::new()),
+                    Segment::Index(_) => Value::Seq(Vec::new()),
+                })
+            }
+            // Maps and sequences held by a leaf become nodes, to be shared from now on.
+            target => *node = Node::from(std::mem::replace(target, Value::Unit)),
+        }
+    }
+    if let Some(child) = node.child_mut(first) {
+        return insert_node(child, rest, value);
+    }
+    let mut created = Node::Leaf(Value::Unit);
+    insert_node(&mut created, rest, value);
+    match node {
+        Node::Map(_) => {
+            let map = node.map_mut().expect("the node is a map");
+            map.insert(Value::String(map_key(first)), created);
+        }
+        _ => node
+            .seq_mut()
+            .expect("the node is a sequence")
+            .push(created),
+    }
+    None
+}
+
+/// Removes the value designated by `parents` followed by `last` inside `node`, copying only the
+/// nodes on the way.
+fn remove_node(node: &mut Node, parents: &[Segment], last: &Segment) -> Option<Node> {
+    let Node::Leaf(value) = node else {
+        return match parents.split_first() {
+            Some((first, rest)) => remove_node(node.child_mut(first)?, rest, last),
+            None if node.child(last).is_none() => None,
+            None => match node.map_mut() {
+                Some(map) => map.remove(&resolve_key(map, last)),
+                None => {
+                    let seq = node.seq_mut()?;
+                    Some(seq.remove(seq_index(last, seq.len()).ok()?))
+                }
+            },
+        };
+    };
+    match lookup_mut(value, parents).map(unwrap_mut) {
+        Some(Value::Map(map)) => map.remove(&resolve_key(map, last)),
+        Some(Value::Seq(seq)) => map_key(last)
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < seq.len())
+            .map(|index| seq.remove(index)),
+        _ => None,
+    }
+    .map(Node::from)
+}
+
+/// Inserts `value` at the location designated by `segments` inside `root` like [`insert`],
+/// copying only the nodes on the way.
+pub(crate) fn insert_at(
+    root: &mut Node,
+    segments: &[Segment],
+    value: Node,
+) -> crate::Result<Option<Node>> {
+    check_node(root, segments)?;
+    Ok(insert_node(root, segments, value))
+}
+
+/// Removes and returns the value designated by `segments` inside `root`, copying only the nodes
+/// on the way.
+pub(crate) fn remove_at(root: &mut Node, segments: &[Segment]) -> Option<Node> {
+    let (last, parents) = segments.split_last()?;
+    // Shared values are only copied when there is something to remove.
+    if !contains_node(root, segments) {
+        return None;
+    }
+    remove_node(root, parents, last)
+}
+
+/// Inserts `value` at the location designated by `segments` inside the root map of a `Context`.
+pub(crate) fn insert_in(
+    root: &mut Entries,
+    segments: &[Segment],
+    value: Value,
+) -> crate::Result<Option<Value>> {
+    let Some((first, rest)) = segments.split_first() else {
+        return Err(Error::Path(
+            "cannot replace the root of a context".to_string(),
+        ));
+    };
+    let key = map_key(first);
+    if rest.is_empty() {
+        return Ok(root.insert(key, value));
+    }
+    match root.node(&key) {
+        Some(node) => check_node(node, rest)?,
+        None => check_insert(None, rest)?,
+    }
+    if let Some(node) = root.node_mut(&key) {
+        return Ok(insert_node(node, rest, Node::from(value)).map(Value::from));
+    }
+    let mut created = Value::Unit;
+    insert_checked(&mut created, rest, value);
+    root.insert(key, created);
+    Ok(None)
+}
+
+impl Context {
+    /// Retrieves a reference to the value located at the given dotted path.
+    ///
+    /// `path`: A path such as `db.host` or `services[0].name` (of type `&str`).
+    ///
+    /// Returns `None` if the path is invalid or does not lead to any value.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::new();
+    /// let mut db = std::collections::BTreeMap::new();
+    /// db.insert(serde_value::Value::String("host".to_string()), serde_value::Value::String("localhost".to_string()));
+    /// context.insert("db".to_string(), serde_value::Value::Map(db));
+    ///
+    /// assert_eq!(context.get_path("db.host").unwrap(), &serde_value::Value::String("localhost".to_string()));
+    /// assert!(context.get_path("db.port").is_none());
+    /// ```
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let path = path.parse::<Path>().ok()?;
+        lookup_in(&self.inner, path.segments())
+    }
+
+    /// Sets the value located at the given dotted path, creating intermediate maps and sequences
+    /// as needed, and returns the value previously stored there.
+    ///
+    /// `path`: A path such as `db.host` or `features[0]` (of type `&str`).
+    /// `v`: The value to store, of type `serde_value::Value`.
+    ///
+    /// An index equal to the length of a sequence appends to it.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid, goes through a scalar value or
+    ///   uses an index beyond the end of a sequence.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::new();
+    /// context.set_path("db.host", serde_value::Value::String("localhost".to_string())).unwrap();
+    /// context.set_path("features[0]", serde_value::Value::String("a".to_string())).unwrap();
+    ///
+    /// assert_eq!(context.get_path("db.host").unwrap(), &serde_value::Value::String("localhost".to_string()));
+    /// assert_eq!(context.get_path("features.0").unwrap(), &serde_value::Value::String("a".to_string()));
+    /// assert!(context.set_path("db.host.name", serde_value::Value::Unit).is_err());
+    /// ```
+    pub fn set_path(&mut self, path: &str, v: Value) -> crate::Result<Option<Value>> {
+        self.audited(|context| insert_in(&mut context.inner, path.parse::<Path>()?.segments(), v))
+    }
+
+    /// Removes the value located at the given dotted path and returns it.
+    ///
+    /// `path`: A path such as `db.host` or `features[0]` (of type `&str`).
+    ///
+    /// Removing a sequence item shifts the following ones. Returns `None` if the path does not
+    /// lead to any value.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid or designates the root.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "db.host=localhost", "--set", "tags[0]=a", "--set", "tags[1]=b"]).unwrap();
+    ///
+    /// assert_eq!(context.remove_path("db.host").unwrap(), Some(serde_value::Value::String("localhost".to_string())));
+    /// assert_eq!(context.remove_path("tags[0]").unwrap(), Some(serde_value::Value::String("a".to_string())));
+    /// assert_eq!(context.get_path("tags[0]").unwrap(), &serde_value::Value::String("b".to_string()));
+    /// assert_eq!(context.remove_path("db.port").unwrap(), None);
+    /// ```
+    pub fn remove_path(&mut self, path: &str) -> crate::Result<Option<Value>> {
+        self.audited(|context| {
+            let path = path.parse::<Path>()?;
+            let Some((last, parents)) = path.segments().split_last() else {
+                return Err(Error::Path(
+                    "cannot remove the root of a context".to_string(),
+                ));
+            };
+            // Shared values are only copied when there is something to remove.
+            if !contains_in(&context.inner, path.segments()) {
+                return Ok(None);
+            }
+            let Some((first, rest)) = parents.split_first() else {
+                return Ok(context.inner.remove(&map_key(last)));
+            };
+            Ok(context
+                .inner
+                .node_mut(&map_key(first))
+                .and_then(|node| remove_node(node, rest, last))
+                .map(Value::from))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let path: Path = "a.b[1][2].c".parse().unwrap();
+        assert_eq!(
+            path.segments(),
+            &[
+                Segment::Key("a".to_string()),
+                Segment::Key("b".to_string()),
+                Segment::Index(1),
+                Segment::Index(2),
+                Segment::Key("c".to_string()),
+            ]
+        );
+        assert_eq!(path.to_string(), "a.b[1][2].c");
+        assert!("a..b".parse::<Path>().is_err());
+        assert!("a[x]".parse::<Path>().is_err());
+        assert!("a[0".parse::<Path>().is_err());
+        assert!("a.b]".parse::<Path>().is_err());
+        assert!("a[0]b".parse::<Path>().is_err());
+        assert!(r#"a["b"#.parse::<Path>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let keys = [
+            "app.kubernetes.io",
+            "",
+            "a[b]",
+            r#"say "hi""#,
+            r"C:\dir",
+            "plain",
+        ];
+        for key in keys {
+            let path = Path::from(vec![
+                Segment::Key(key.to_string()),
+                Segment::Index(0),
+                Segment::Key(key.to_string()),
+            ]);
+            assert_eq!(path.to_string().parse::<Path>().unwrap(), path, "{}", path);
+        }
+        let path: Path = r#"labels["app.kubernetes.io"][2].x"#.parse().unwrap();
+        assert_eq!(path.to_string(), r#"labels["app.kubernetes.io"][2].x"#);
+        assert_eq!(
+            Path::from(vec![Segment::Index(1)])
+                .to_string()
+                .parse::<Path>()
+                .unwrap()
+                .segments(),
+            &[Segment::Index(1)]
+        );
+        assert!(path.matches(r#"labels["app.kubernetes.io"][*].x"#));
+        assert!(path.matches("labels.*.2.x"));
+    }
+
+    #[test]
+    fn test_get_path_numeric_key() {
+        let mut ctx = Context::new();
+        ctx.insert(
+            "list".to_string(),
+            Value::Seq(vec![
+                Value::String("x".to_string()),
+                Value::String("y".to_string()),
+            ]),
+        );
+        assert_eq!(
+            ctx.get_path("list.1"),
+            Some(&Value::String("y".to_string()))
+        );
+        assert_eq!(
+            ctx.get_path("list[0]"),
+            Some(&Value::String("x".to_string()))
+        );
+        assert_eq!(ctx.get_path("list[2]"), None);
+    }
+
+    #[test]
+    fn test_set_path_failure_leaves_context_unchanged() {
+        let mut ctx = Context::new();
+        assert!(ctx.set_path("x.y[3]", Value::Bool(true)).is_err());
+        assert!(ctx.diff(&Context::new()).is_empty());
+
+        ctx.set_path("a.b[0]", Value::U64(1)).unwrap();
+        let before = ctx.clone();
+        assert!(ctx.set_path("a.c.d[1]", Value::U64(2)).is_err());
+        assert!(ctx.set_path("a.b[0].e", Value::U64(3)).is_err());
+        assert!(ctx.set_path("z[0].w[2]", Value::U64(4)).is_err());
+        assert!(before.diff(&ctx).is_empty());
+    }
+}
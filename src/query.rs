@@ -0,0 +1,203 @@
+use crate::value::{normalize_sequences, scalar_to_string};
+use crate::{Context, Error};
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// A key of a query string parameter, split on its brackets (`a[b][]` gives `a`, `b` and an append).
+#[derive(Debug, PartialEq)]
+enum Key {
+    Name(String),
+    Append,
+}
+
+/// Splits a decoded parameter name such as `a[b][]` into its keys.
+fn parse_key(name: &str) -> crate::Result<Vec<Key>> {
+    let (first, mut rest) = match name.find('[') {
+        Some(position) if position > 0 => name.split_at(position),
+        _ => (name, ""),
+    };
+    let mut keys = vec![Key::Name(first.to_string())];
+    while !rest.is_empty() {
+        let end = rest
+            .find(']')
+            .filter(|_| rest.starts_with('['))
+            .ok_or_else(|| Error::Query(format!("malformed parameter name '{}'", name)))?;
+        keys.push(match &rest[1..end] {
+            "" => Key::Append,
+            key => Key::Name(key.to_string()),
+        });
+        rest = &rest[end + 1..];
+    }
+    Ok(keys)
+}
+
+/// Stores `value` under `keys` inside `slot`. A repeated leaf turns into a sequence.
+fn assign(slot: &mut Option<Value>, keys: &[Key], value: Value, name: &str) -> crate::Result<()> {
+    let Some((key, rest)) = keys.split_first() else {
+        *slot = Some(match slot.take() {
+            None => value,
+            Some(Value::Seq(mut seq)) => {
+                seq.push(value);
+                Value::Seq(seq)
+            }
+            Some(previous) => Value::Seq(vec![previous, value]),
+        });
+        return Ok(());
+    };
+    let conflict = || Error::Query(format!("conflicting values for parameter '{}'", name));
+    match key {
+        Key::Append => {
+            let seq = match slot.get_or_insert_with(|| Value::Seq(Vec::new())) {
+                Value::Seq(seq) => seq,
+                _ => return Err(conflict()),
+            };
+            let mut item = None;
+            assign(&mut item, rest, value, name)?;
+            seq.extend(item);
+        }
+        Key::Name(key) => {
+            let map = match slot.get_or_insert_with(|| Value::Map(BTreeMap::new())) {
+                Value::Map(map) => map,
+                _ => return Err(conflict()),
+            };
+            let key = Value::String(key.clone());
+            let mut item = map.remove(&key);
+            assign(&mut item, rest, value, name)?;
+            map.extend(item.map(|item| (key, item)));
+        }
+    }
+    Ok(())
+}
+
+/// Appends the parameters describing `value` under the parameter name `name`.
+fn encode(name: String, value: &Value, serializer: &mut form_urlencoded::Serializer<String>) {
+    match value {
+        Value::Map(map) => {
+            for (key, value) in map {
+                encode(
+                    format!("{}[{}]", name, scalar_to_string(key)),
+                    value,
+                    serializer,
+                );
+            }
+        }
+        Value::Seq(seq) => {
+            for (index, value) in seq.iter().enumerate() {
+                encode(format!("{}[{}]", name, index), value, serializer);
+            }
+        }
+        Value::Option(Some(value)) | Value::Newtype(value) => encode(name, value, serializer),
+        value => {
+            serializer.append_pair(&name, &scalar_to_string(value));
+        }
+    }
+}
+
+impl Context {
+    /// Creates a `Context` from a URL query string or an `application/x-www-form-urlencoded` body.
+    ///
+    /// Parameter names support bracket-style nesting: `a[b]=1` creates a map, `a[]=1` appends to
+    /// a sequence and `a[0]=1&a[1]=2` creates a sequence. Repeating a parameter (`tag=x&tag=y`)
+    /// collects its values into a sequence. Names and values are percent-decoded and every value
+    /// is kept as a `Value::String`. A leading `?` is ignored.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Query` variant if a parameter name is malformed or conflicts with
+    ///   another one (`a=1&a[b]=2`).
+    ///
+    /// # Example
+    /// ```rust
+    /// let context = oxidex::Context::from_query_string("?a[b]=1&a[c]=2&tag=x&tag=y%20z").unwrap();
+    ///
+    /// assert_eq!(context.get_path("a.b").unwrap(), &serde_value::Value::String("1".to_string()));
+    /// assert_eq!(context.get_path("a.c").unwrap(), &serde_value::Value::String("2".to_string()));
+    /// assert_eq!(context.get_path("tag[1]").unwrap(), &serde_value::Value::String("y z".to_string()));
+    /// ```
+    pub fn from_query_string(query: &str) -> crate::Result<Context> {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let mut root = Some(Value::Map(BTreeMap::new()));
+        for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+            assign(
+                &mut root,
+                &parse_key(&name)?,
+                Value::String(value.into_owned()),
+                &name,
+            )?;
+        }
+        let mut context = Context::new();
+        if let Some(Value::Map(map)) = root {
+            for (key, value) in map {
+                context
+                    .inner
+                    .insert(scalar_to_string(&key), normalize_sequences(value));
+            }
+        }
+        Ok(context)
+    }
+
+    /// Serializes the `Context` into a percent-encoded query string.
+    ///
+    /// Nested maps and sequences use bracket-style names (`a[b]=1`, `a[0]=x`), which
+    /// [`Context::from_query_string`] reads back. Empty maps and sequences are omitted and
+    /// unit values are written as empty parameters.
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut context = oxidex::Context::new();
+    /// context.insert("q".to_string(), serde_value::Value::String("a&b c".to_string()));
+    /// context.insert("page".to_string(), serde_value::Value::U64(2));
+    ///
+    /// assert_eq!(context.to_query_string(), "page=2&q=a%26b+c");
+    /// ```
+    pub fn to_query_string(&self) -> String {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in &self.inner {
+            encode(key.clone(), &value, &mut serializer);
+        }
+        serializer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_roundtrip() {
+        let context =
+            Context::from_query_string("a[b][]=1&a[b][]=2&a[c][d]=x&list[1]=y&list[0]=x").unwrap();
+        assert_eq!(
+            context.get_path("a.b"),
+            Some(&Value::Seq(vec![
+                Value::String("1".to_string()),
+                Value::String("2".to_string())
+            ]))
+        );
+        assert_eq!(
+            context.get_path("list[1]"),
+            Some(&Value::String("y".to_string()))
+        );
+
+        let query = context.to_query_string();
+        assert_eq!(
+            query,
+            "a%5Bb%5D%5B0%5D=1&a%5Bb%5D%5B1%5D=2&a%5Bc%5D%5Bd%5D=x&list%5B0%5D=x&list%5B1%5D=y"
+        );
+        assert_eq!(
+            Context::from_query_string(&query).unwrap().inner,
+            context.inner
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            Context::from_query_string("a=1&a[b]=2"),
+            Err(Error::Query(_))
+        ));
+        assert!(matches!(
+            Context::from_query_string("a[b=1"),
+            Err(Error::Query(_))
+        ));
+    }
+}
@@ -0,0 +1,590 @@
+use crate::diff::key_segment;
+use crate::path::{lookup, Path, Segment};
+use crate::value::{as_float, as_integer, equivalent, to_inline, unwrap};
+use crate::{Context, Error};
+use regex::Regex;
+use serde_value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A numeric keyword, the message reporting its violation and the check it performs.
+type Bound = (&'static str, &'static str, fn(f64, f64) -> bool);
+
+const BOUNDS: [Bound; 4] = [
+    ("minimum", "must be at least", |v, limit| v >= limit),
+    ("maximum", "must be at most", |v, limit| v <= limit),
+    ("exclusiveMinimum", "must be greater than", |v, limit| {
+        v > limit
+    }),
+    ("exclusiveMaximum", "must be less than", |v, limit| {
+        v < limit
+    }),
+];
+
+/// A value that does not satisfy a [`Schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The location of the offending value in the validated context.
+    pub instance_path: Path,
+    /// The location of the failed keyword in the schema, as a JSON Pointer.
+    pub schema_path: String,
+    /// A description of the violation.
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    /// Renders the violation as `db.port: must be at most 65535 (/properties/db/...)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.instance_path.is_root() {
+            true => write!(f, "(root): {} ({})", self.message, self.schema_path),
+            false => write!(
+                f,
+                "{}: {} ({})",
+                self.instance_path, self.message, self.schema_path
+            ),
+        }
+    }
+}
+
+/// A JSON Schema used to validate contexts.
+///
+/// The supported subset of draft 2020-12 covers `type`, `enum`, `const`, `properties`, `required`,
+/// `additionalProperties`, `items`, `minItems`/`maxItems`, `minLength`/`maxLength`, `pattern`,
+/// `minimum`/`maximum`, `exclusiveMinimum`/`exclusiveMaximum` and local `$ref` (`#/$defs/...`).
+/// Other keywords are ignored.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "json")] {
+/// let schema = oxidex::Schema::from_context(&oxidex::Context::from_json(r##"{
+///     "type": "object",
+///     "required": ["db"],
+///     "properties": {
+///         "db": {
+///             "type": "object",
+///             "properties": {"port": {"$ref": "#/$defs/port"}},
+///             "additionalProperties": false
+///         }
+///     },
+///     "$defs": {"port": {"type": "integer", "minimum": 1, "maximum": 65535}}
+/// }"##).unwrap()).unwrap();
+///
+/// let config = oxidex::Context::from_json(r#"{"db": {"port": 70000, "hots": "x"}}"#).unwrap();
+/// let violations = schema.validate(&config);
+/// assert_eq!(violations.len(), 2);
+/// assert_eq!(violations[0].to_string(), "db.hots: is not allowed (/properties/db/additionalProperties)");
+/// assert_eq!(violations[1].instance_path.to_string(), "db.port");
+/// assert_eq!(violations[1].schema_path, "/properties/db/properties/port/$ref/maximum");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub(crate) root: Value,
+    patterns: HashMap<String, Regex>,
+}
+
+/// Escapes a key for use in a JSON Pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Returns the JSON Pointer token designating a map key: the escaped key itself for a string,
+/// and its inline form otherwise.
+fn token(key: &Value) -> String {
+    match key {
+        Value::String(key) => escape(key),
+        key => escape(&to_inline(key)),
+    }
+}
+
+/// Returns the value designated by a local reference such as `#/$defs/port`.
+pub(crate) fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    lookup(root, Path::from_pointer(pointer).ok()?.segments())
+}
+
+/// Returns the name of the JSON type of a value.
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Unit | Value::Option(None) => "null",
+        Value::Bool(_) => "boolean",
+        Value::F32(_) | Value::F64(_) => "number",
+        Value::String(_) | Value::Char(_) | Value::Bytes(_) => "string",
+        Value::Seq(_) => "array",
+        Value::Map(_) => "object",
+        _ => "integer",
+    }
+}
+
+/// Returns `true` if a value is of the JSON type `name`.
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => {
+            as_integer(value).is_some()
+                || as_float(value).is_some_and(|v| v.is_finite() && v.fract() == 0.0)
+        }
+        "number" => as_float(value).is_some(),
+        "string" => matches!(value, Value::String(_) | Value::Char(_)),
+        name => type_name(value) == name,
+    }
+}
+
+/// Returns the length of a string value, in characters.
+fn string_length(value: &Value) -> Option<usize> {
+    match value {
+        Value::String(text) => Some(text.chars().count()),
+        Value::Char(_) => Some(1),
+        _ => None,
+    }
+}
+
+/// Collects the validation state shared across a traversal.
+struct Validator<'a> {
+    schema: &'a Schema,
+    violations: Vec<Violation>,
+    /// The references being followed, with the location they are followed at: following one
+    /// again at the same location would never end.
+    following: HashSet<(String, Path)>,
+}
+
+impl Validator<'_> {
+    fn fail(&mut self, instance: &Path, schema_path: &str, keyword: &str, message: String) {
+        self.violations.push(Violation {
+            instance_path: instance.clone(),
+            schema_path: format!("{}/{}", schema_path, keyword),
+            message,
+        });
+    }
+
+    fn validate(&mut self, schema: &Value, schema_path: &str, value: &Value, instance: &Path) {
+        let map = match unwrap(schema) {
+            Value::Map(map) => map,
+            Value::Bool(false) => {
+                // A `false` schema is reported by the keyword holding it.
+                let (parent, keyword) = schema_path.rsplit_once('/').unwrap_or(("", ""));
+                return self.fail(instance, parent, keyword, "is not allowed".to_string());
+            }
+            _ => return,
+        };
+        let keyword = |name: &str| map.get(&Value::String(name.to_string())).map(unwrap);
+        let value = unwrap(value);
+
+        if let Some(Value::String(reference)) = keyword("$ref") {
+            let location = format!("{}/$ref", schema_path);
+            let following = (reference.clone(), instance.clone());
+            match resolve(&self.schema.root, reference) {
+                Some(target) if self.following.insert(following.clone()) => {
+                    self.validate(target, &location, value, instance);
+                    self.following.remove(&following);
+                }
+                _ => self.fail(
+                    instance,
+                    schema_path,
+                    "$ref",
+                    format!("cannot follow reference '{}'", reference),
+                ),
+            }
+        }
+
+        let types: Vec<&str> = match keyword("type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Seq(names)) => names
+                .iter()
+                .filter_map(|name| match unwrap(name) {
+                    Value::String(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+            self.fail(
+                instance,
+                schema_path,
+                "type",
+                format!(
+                    "expected {}, found {}",
+                    types.join(" or "),
+                    type_name(value)
+                ),
+            );
+        }
+        if let Some(Value::Seq(allowed)) = keyword("enum") {
+            if !allowed.iter().any(|item| equivalent(item, value)) {
+                let allowed: Vec<String> = allowed.iter().map(to_inline).collect();
+                self.fail(
+                    instance,
+                    schema_path,
+                    "enum",
+                    format!("must be one of {}", allowed.join(", ")),
+                );
+            }
+        }
+        if let Some(expected) = keyword("const") {
+            if !equivalent(expected, value) {
+                let message = format!("must be {}", to_inline(expected));
+                self.fail(instance, schema_path, "const", message);
+            }
+        }
+
+        if let (Some(number), false) = (as_float(value), matches!(value, Value::Bool(_))) {
+            for (name, message, check) in BOUNDS {
+                if let Some(limit) = keyword(name) {
+                    if as_float(limit).is_some_and(|limit| !check(number, limit)) {
+                        let message = format!("{} {}", message, to_inline(limit));
+                        self.fail(instance, schema_path, name, message);
+                    }
+                }
+            }
+        }
+
+        if let Some(length) = string_length(value) {
+            if let Some(min) = keyword("minLength").and_then(as_integer) {
+                if (length as i128) < min {
+                    let message = format!("must be at least {} characters long", min);
+                    self.fail(instance, schema_path, "minLength", message);
+                }
+            }
+            if let Some(max) = keyword("maxLength").and_then(as_integer) {
+                if (length as i128) > max {
+                    let message = format!("must be at most {} characters long", max);
+                    self.fail(instance, schema_path, "maxLength", message);
+                }
+            }
+            if let (Some(Value::String(pattern)), Value::String(text)) = (keyword("pattern"), value)
+            {
+                if !self.schema.patterns[pattern].is_match(text) {
+                    let message = format!("must match pattern '{}'", pattern);
+                    self.fail(instance, schema_path, "pattern", message);
+                }
+            }
+        }
+
+        if let Value::Seq(items) = value {
+            if let Some(min) = keyword("minItems").and_then(as_integer) {
+                if (items.len() as i128) < min {
+                    let message = format!("must have at least {} items", min);
+                    self.fail(instance, schema_path, "minItems", message);
+                }
+            }
+            if let Some(max) = keyword("maxItems").and_then(as_integer) {
+                if (items.len() as i128) > max {
+                    let message = format!("must have at most {} items", max);
+                    self.fail(instance, schema_path, "maxItems", message);
+                }
+            }
+            if let Some(item_schema) = keyword("items") {
+                let location = format!("{}/items", schema_path);
+                for (index, item) in items.iter().enumerate() {
+                    let path = instance.join(Segment::Index(index));
+                    self.validate(item_schema, &location, item, &path);
+                }
+            }
+        }
+
+        if let Value::Map(object) = value {
+            if let Some(Value::Seq(required)) = keyword("required") {
+                for name in required {
+                    if !object.contains_key(unwrap(name)) {
+                        let path = instance.join(key_segment(unwrap(name)));
+                        self.fail(&path, schema_path, "required", "is required".to_string());
+                    }
+                }
+            }
+            let properties = match keyword("properties") {
+                Some(Value::Map(properties)) => Some(properties),
+                _ => None,
+            };
+            for (key, item) in object {
+                let path = instance.join(key_segment(key));
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(property) => {
+                        let location = format!("{}/properties/{}", schema_path, token(key));
+                        self.validate(property, &location, item, &path);
+                    }
+                    None => {
+                        if let Some(additional) = keyword("additionalProperties") {
+                            let location = format!("{}/additionalProperties", schema_path);
+                            self.validate(additional, &location, item, &path);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks a schema and compiles its patterns, `location` being its JSON Pointer. Referenced
+/// schemas are checked once, whatever their location.
+fn compile(
+    schema: &Value,
+    location: &str,
+    root: &Value,
+    patterns: &mut HashMap<String, Regex>,
+    references: &mut HashSet<String>,
+) -> crate::Result<()> {
+    let map = match unwrap(schema) {
+        Value::Map(map) => map,
+        Value::Bool(_) => return Ok(()),
+        value => {
+            return Err(Error::Schema(format!(
+                "expected a schema at '{}', found {}",
+                location,
+                to_inline(value)
+            )))
+        }
+    };
+    for (keyword, value) in map {
+        let Value::String(keyword) = keyword else {
+            continue;
+        };
+        let child = format!("{}/{}", location, escape(keyword));
+        match (keyword.as_str(), unwrap(value)) {
+            ("pattern", Value::String(pattern)) => {
+                let regex = Regex::new(pattern).map_err(|err| {
+                    Error::Schema(format!("invalid pattern at '{}': {}", child, err))
+                })?;
+                patterns.insert(pattern.clone(), regex);
+            }
+            ("$ref", Value::String(reference)) => {
+                let target = resolve(root, reference).ok_or_else(|| {
+                    Error::Schema(format!(
+                        "unresolved reference '{}' at '{}'",
+                        reference, child
+                    ))
+                })?;
+                if references.insert(reference.clone()) {
+                    compile(target, reference, root, patterns, references)?;
+                }
+            }
+            ("properties" | "$defs" | "definitions", Value::Map(schemas)) => {
+                for (name, schema) in schemas {
+                    compile(
+                        schema,
+                        &format!("{}/{}", child, token(name)),
+                        root,
+                        patterns,
+                        references,
+                    )?;
+                }
+            }
+            ("items" | "additionalProperties", schema) => {
+                compile(schema, &child, root, patterns, references)?
+            }
+            ("pattern" | "$ref", value) => {
+                return Err(Error::Schema(format!(
+                    "expected a string at '{}', found {}",
+                    child,
+                    to_inline(value)
+                )))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Schema {
+    /// Loads a schema from a `Context`, usually read from a JSON or YAML file.
+    ///
+    /// `schema`: The JSON Schema document.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Schema` variant if a sub-schema is malformed, a `pattern` is not a valid
+    ///   regular expression or a `$ref` cannot be resolved.
+    pub fn from_context(schema: &Context) -> crate::Result<Schema> {
+        let root = schema.to_value();
+        let mut patterns = HashMap::new();
+        compile(&root, "", &root, &mut patterns, &mut HashSet::new())?;
+        Ok(Schema { root, patterns })
+    }
+
+    /// Validates a `Context` and returns every violation found, in document order.
+    ///
+    /// `context`: The `Context` to validate.
+    pub fn validate(&self, context: &Context) -> Vec<Violation> {
+        let mut validator = Validator {
+            schema: self,
+            violations: Vec::new(),
+            following: HashSet::new(),
+        };
+        validator.validate(&self.root, "", &context.to_value(), &Path::root());
+        validator.violations
+    }
+}
+
+impl Context {
+    /// Validates the `Context` against a JSON Schema.
+    ///
+    /// Use [`Schema::validate`] to get the violations as structured values.
+    ///
+    /// `schema`: The schema to satisfy.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Schema` variant listing every violation if the `Context` is invalid.
+    ///
+    /// Example:
+    /// ```
+    /// # #[cfg(feature = "yaml")] {
+    /// let schema = oxidex::Schema::from_context(&oxidex::Context::from_yaml(r#"
+    /// properties:
+    ///   level: {enum: [debug, info, warn]}
+    ///   name: {type: string, pattern: "^[a-z]+$"}
+    /// "#).unwrap()).unwrap();
+    ///
+    /// let config = oxidex::Context::from_yaml("level: trace\nname: App").unwrap();
+    /// assert_eq!(
+    ///     config.validate(&schema).unwrap_err().to_string(),
+    ///     "schema error: level: must be one of \"debug\", \"info\", \"warn\" (/properties/level/enum); \
+    ///      name: must match pattern '^[a-z]+$' (/properties/name/pattern)"
+    /// );
+    /// # }
+    /// ```
+    pub fn validate(&self, schema: &Schema) -> crate::Result<()> {
+        let violations = schema.validate(self);
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Schema(
+                violations
+                    .iter()
+                    .map(Violation::to_string)
+                    .collect::<Vec<String>>()
+                    .join("; "),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(args: &[&str]) -> Schema {
+        Schema::from_context(&Context::from_args(args.iter().copied()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_numbers_across_formats() {
+        let schema = schema(&[
+            "--set",
+            "properties.a.type=integer",
+            "--set",
+            "properties.a.maximum=10",
+        ]);
+        let mut context = Context::new();
+        context.insert("a".to_string(), Value::F64(3.0));
+        assert!(schema.validate(&context).is_empty());
+        context.insert("a".to_string(), Value::I64(11));
+        assert_eq!(schema.validate(&context)[0].message, "must be at most 10");
+        context.insert("a".to_string(), Value::F32(1.5));
+        assert_eq!(
+            schema.validate(&context)[0].message,
+            "expected integer, found number"
+        );
+    }
+
+    #[test]
+    fn test_items_required_and_false_schema() {
+        let schema = schema(&[
+            "--set",
+            "properties.list.items.required[0]=name",
+            "--set",
+            "properties.list.minItems=3",
+            "--set",
+            "properties.debug=false",
+        ]);
+        let context = Context::from_args([
+            "--set",
+            "list[0].name=a",
+            "--set",
+            "list[1].other=b",
+            "--set",
+            "debug=true",
+        ])
+        .unwrap();
+        let violations: Vec<(String, String)> = schema
+            .validate(&context)
+            .into_iter()
+            .map(|violation| (violation.instance_path.to_string(), violation.schema_path))
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                ("debug".to_string(), "/properties/debug".to_string()),
+                ("list".to_string(), "/properties/list/minItems".to_string()),
+                (
+                    "list[1].name".to_string(),
+                    "/properties/list/items/required".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_paths_are_json_pointers() {
+        let schema = schema(&[
+            "--set",
+            r#"properties["app.io/name"].type=string"#,
+            "--set",
+            r#"properties[""].type=string"#,
+        ]);
+        let mut context = Context::new();
+        context.insert("app.io/name".to_string(), Value::U64(1));
+        context.insert(String::new(), Value::U64(1));
+        let paths: Vec<String> = schema
+            .validate(&context)
+            .into_iter()
+            .map(|violation| violation.schema_path)
+            .collect();
+        assert_eq!(
+            paths,
+            ["/properties//type", "/properties/app.io~1name/type"]
+        );
+    }
+
+    #[test]
+    fn test_recursive_references() {
+        let tree = schema(&[
+            "--set",
+            "$ref=#/$defs/node",
+            "--set",
+            "$defs.node.properties.value.type=integer",
+            "--set",
+            "$defs.node.properties.child.$ref=#/$defs/node",
+        ]);
+        let mut node = Value::Map([(Value::String("value".to_string()), Value::Bool(true))].into());
+        let mut path = "value".to_string();
+        for _ in 0..100 {
+            node = Value::Map([(Value::String("child".to_string()), node)].into());
+            path = format!("child.{}", path);
+        }
+        let context = Context::from_value(node).unwrap();
+        let violations = tree.validate(&context);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].instance_path.to_string(), path);
+
+        let cycle = schema(&[
+            "--set",
+            "$ref=#/$defs/a",
+            "--set",
+            "$defs.a.$ref=#/$defs/b",
+            "--set",
+            "$defs.b.$ref=#/$defs/a",
+        ]);
+        let violations = cycle.validate(&Context::new());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].message, "cannot follow reference '#/$defs/a'");
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        for args in [
+            ["--set", "pattern=\"[\""],
+            ["--set", "$ref=#/$defs/missing"],
+            ["--set", "properties.a=1"],
+        ] {
+            let spec = Context::from_args(args).unwrap();
+            assert!(matches!(Schema::from_context(&spec), Err(Error::Schema(_))));
+        }
+    }
+}
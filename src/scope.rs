@@ -0,0 +1,217 @@
+use crate::path::{lookup, Path, Segment};
+use crate::value::unwrap;
+use crate::Context;
+use serde_value::Value;
+use std::collections::BTreeMap;
+
+/// The result of a lookup in the local values of a scope.
+enum Local<'a> {
+    /// The value is defined locally.
+    Found(&'a Value),
+    /// A map misses a key along the path: the lookup falls through to the parent.
+    Missing,
+    /// A local sequence or scalar hides the parent's values.
+    Shadowed,
+}
+
+fn lookup_local<'a>(context: &'a Context, path: &Path) -> Local<'a> {
+    let Some((first, rest)) = path.segments().split_first() else {
+        return Local::Missing;
+    };
+    let key = match first {
+        Segment::Key(key) => key.clone(),
+        Segment::Index(index) => index.to_string(),
+    };
+    let Some(mut current) = context.get(&key) else {
+        return Local::Missing;
+    };
+    for segment in rest {
+        match lookup(current, std::slice::from_ref(segment)) {
+            Some(value) => current = value,
+            None if matches!(unwrap(current), Value::Map(_)) => return Local::Missing,
+            None => return Local::Shadowed,
+        }
+    }
+    Local::Found(current)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Parent<'a> {
+    Context(&'a Context),
+    Scope(&'a Scope<'a>),
+}
+
+/// A child scope of a `Context`, as returned by [`Context::child`].
+///
+/// Writes go to the scope's own values, while lookups fall through to the parent for the keys
+/// missing locally, like variable scopes in a template engine. Maps are merged key by key: a
+/// local `db.user` leaves the parent's `db.host` visible, whereas a local sequence or scalar hides
+/// the parent's value entirely. [`Scope::flatten_scope`] materializes the same view as a plain
+/// `Context`.
+#[derive(Debug, Clone)]
+pub struct Scope<'a> {
+    local: Context,
+    parent: Parent<'a>,
+}
+
+impl<'a> Scope<'a> {
+    /// Returns the value of a top-level key, from the scope or its parents.
+    ///
+    /// `k`: The key to look up.
+    ///
+    /// Returns `None` if no scope defines the key. A map defined locally is returned as is,
+    /// without the keys its parents add to it; use [`Scope::get_path`] to see those.
+    pub fn get(&self, k: &str) -> Option<&Value> {
+        self.local.get(k).or_else(|| match self.parent {
+            Parent::Context(context) => context.get(k),
+            Parent::Scope(scope) => scope.get(k),
+        })
+    }
+
+    /// Returns the value located at the given dotted path, from the scope or its parents.
+    ///
+    /// `path`: A path such as `db.host` or `services[0].name`.
+    ///
+    /// Returns `None` if the path is invalid or does not lead to any value.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let path = path.parse::<Path>().ok()?;
+        self.lookup(&path)
+    }
+
+    fn lookup(&self, path: &Path) -> Option<&Value> {
+        match lookup_local(&self.local, path) {
+            Local::Found(value) => Some(value),
+            Local::Shadowed => None,
+            Local::Missing => match self.parent {
+                Parent::Context(context) => match lookup_local(context, path) {
+                    Local::Found(value) => Some(value),
+                    _ => None,
+                },
+                Parent::Scope(scope) => scope.lookup(path),
+            },
+        }
+    }
+
+    /// Inserts a key-value pair in the scope, leaving its parents untouched.
+    ///
+    /// `k`: The key to insert.
+    /// `v`: The value associated with the key.
+    pub fn insert(&mut self, k: String, v: Value) {
+        self.local.insert(k, v);
+    }
+
+    /// Adds key-value pairs to the scope, leaving its parents untouched.
+    ///
+    /// `data`: The key-value pairs to add.
+    pub fn extend(&mut self, data: BTreeMap<String, Value>) {
+        self.local.extend(data);
+    }
+
+    /// Sets the value located at the given dotted path in the scope, leaving its parents
+    /// untouched.
+    ///
+    /// `path`: A path such as `db.host` or `features[0]`.
+    /// `v`: The value to store.
+    ///
+    /// Returns the value previously stored at this path in the scope itself.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid or leads through a local scalar
+    ///   value.
+    pub fn set_path(&mut self, path: &str, v: Value) -> crate::Result<Option<Value>> {
+        self.local.set_path(path, v)
+    }
+
+    /// Returns the values defined by the scope itself.
+    pub fn local(&self) -> &Context {
+        &self.local
+    }
+
+    /// Returns the values defined by the scope itself, dropping the scope.
+    pub fn into_local(self) -> Context {
+        self.local
+    }
+
+    /// Creates a nested scope whose lookups fall through to this one.
+    pub fn child(&self) -> Scope<'_> {
+        Scope {
+            local: Context::new(),
+            parent: Parent::Scope(self),
+        }
+    }
+
+    /// Materializes the scope as a plain `Context`: the parents' values merged with the local
+    /// ones (see [`Context::merge`]).
+    pub fn flatten_scope(&self) -> Context {
+        let mut context = match self.parent {
+            Parent::Context(context) => context.clone(),
+            Parent::Scope(scope) => scope.flatten_scope(),
+        };
+        context.merge(self.local.clone());
+        context
+    }
+}
+
+impl Context {
+    /// Creates a child scope whose lookups fall through to this `Context`.
+    ///
+    /// The scope borrows the `Context` instead of copying or merging it, which makes it a cheap
+    /// way to overlay per-request or per-task values on a shared base.
+    ///
+    /// Example:
+    /// ```
+    /// let base = oxidex::Context::from_args(["--set", "db.host=localhost", "--set", "db.port=5432"]).unwrap();
+    /// let mut request = base.child();
+    /// request.set_path("db.user", serde_value::Value::String("alice".to_string())).unwrap();
+    /// request.set_path("db.port", serde_value::Value::U64(6432)).unwrap();
+    ///
+    /// assert_eq!(request.get_path("db.host").unwrap(), &serde_value::Value::String("localhost".to_string()));
+    /// assert_eq!(request.get_path("db.port").unwrap(), &serde_value::Value::U64(6432));
+    /// assert!(base.get_path("db.user").is_none());
+    ///
+    /// let flat = request.flatten_scope();
+    /// assert_eq!(flat.get_path("db.user").unwrap(), &serde_value::Value::String("alice".to_string()));
+    /// assert_eq!(flat.get_path("db.host").unwrap(), &serde_value::Value::String("localhost".to_string()));
+    /// ```
+    pub fn child(&self) -> Scope<'_> {
+        Scope {
+            local: Context::new(),
+            parent: Parent::Context(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_scopes_agree_with_flatten_scope() {
+        let base = Context::from_args([
+            "--set",
+            "a.b=1",
+            "--set",
+            "a.c=2",
+            "--set",
+            "list[0]=x",
+            "--set",
+            "list[1]=y",
+        ])
+        .unwrap();
+        let mut task = base.child();
+        task.set_path("a.c", Value::U64(3)).unwrap();
+        task.set_path("list[0]", Value::String("z".to_string()))
+            .unwrap();
+        let mut step = task.child();
+        step.insert("d".to_string(), Value::Bool(true));
+
+        let flat = step.flatten_scope();
+        for path in ["a.b", "a.c", "list[0]", "list[1]", "d", "missing.key"] {
+            assert_eq!(step.get_path(path), flat.get_path(path), "{}", path);
+        }
+        assert_eq!(step.get_path("a.c"), Some(&Value::U64(3)));
+        assert!(step.get_path("list[1]").is_none());
+        assert_eq!(step.get("a"), task.local().get("a"));
+        assert_eq!(base.get_path("a.c"), Some(&Value::U64(2)));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::diff::key_segment;
+use crate::entries::Node;
+use crate::path::{Path, Segment};
+use crate::value::{to_inline, unwrap};
+use crate::Context;
+use serde_value::Value;
+use std::fmt;
+
+/// The text replacing secret values in redacted output.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+impl Context {
+    /// Marks the values whose path matches a glob pattern as secret.
+    ///
+    /// Secret values, and everything below them, are masked by `Debug`, `Display` and the
+    /// `to_*_redacted` exporters, while accessors such as [`Context::get_path`] still return them.
+    /// Patterns follow [`Path::matches`]: `db.password` marks a single value and `**.*token*` any
+    /// key containing `token`, at any depth.
+    ///
+    /// `pattern`: The glob pattern of the secret paths.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "db.user=admin", "--set", "db.password=hunter2"]).unwrap();
+    /// context.mark_secret("db.password");
+    ///
+    /// assert_eq!(format!("{:?}", context), r#"Context { inner: {"db": Map({String("password"): String("[REDACTED]"), String("user"): String("admin")})} }"#);
+    /// assert_eq!(context.to_string(), "db.password: \"[REDACTED]\"\ndb.user: \"admin\"\n");
+    /// assert_eq!(context.get_path("db.password").unwrap(), &serde_value::Value::String("hunter2".to_string()));
+    /// ```
+    pub fn mark_secret(&mut self, pattern: &str) {
+        mark(&mut self.secrets, pattern);
+    }
+
+    /// Marks the values whose key matches a glob pattern as secret, at any depth.
+    ///
+    /// This is a shorthand for [`Context::mark_secret`] with a `**.` prefix.
+    ///
+    /// `pattern`: The glob pattern of the secret keys, such as `*password*`.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "api_token=abc", "--set", "services[0].db_password=x"]).unwrap();
+    /// context.mark_secret_keys("*password*");
+    /// context.mark_secret_keys("*token*");
+    ///
+    /// assert!(context.is_secret("api_token"));
+    /// assert!(context.is_secret("services[0].db_password"));
+    /// assert!(!context.to_string().contains("abc"));
+    /// ```
+    pub fn mark_secret_keys(&mut self, pattern: &str) {
+        self.mark_secret(&format!("**.{}", pattern));
+    }
+
+    /// Sets the value at the given path and marks it as secret.
+    ///
+    /// `path`: A path such as `db.password`.
+    /// `v`: The secret value.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if the path is invalid or leads through a scalar value.
+    pub fn set_secret(&mut self, path: &str, v: Value) -> crate::Result<Option<Value>> {
+        self.audited(|context| {
+            let previous = context.set_path(path, v)?;
+            context.mark_secret(&path.parse::<Path>()?.to_string());
+            Ok(previous)
+        })
+    }
+
+    /// Returns `true` if the value at the given path, or one of its parents, is secret.
+    ///
+    /// Values moved or copied by [`Context::apply_transform`] or [`Context::apply_json_patch`]
+    /// stay secret at their new location.
+    ///
+    /// `path`: A path such as `db.password`.
+    pub fn is_secret(&self, path: &str) -> bool {
+        path.parse::<Path>()
+            .is_ok_and(|path| covered(&self.secrets, &path))
+    }
+
+    /// Returns a copy of the `Context` whose secret values are replaced by `"[REDACTED]"`.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "credentials.key=k", "--set", "port=80"]).unwrap();
+    /// context.mark_secret("credentials");
+    ///
+    /// let redacted = context.redacted();
+    /// assert_eq!(redacted.get("credentials").unwrap(), &serde_value::Value::String("[REDACTED]".to_string()));
+    /// assert_eq!(redacted.get("port").unwrap(), &serde_value::Value::U64(80));
+    /// ```
+    pub fn redacted(&self) -> Context {
+        let mut redacted = self.clone();
+        if self.secrets.is_empty() {
+            return redacted;
+        }
+        for (key, node) in self.inner.nodes() {
+            let mut copy = node.clone();
+            self.redact_node(&mut copy, &mut vec![Segment::Key(key.clone())]);
+            if !copy.same(node) {
+                redacted.inner.insert_node(key.clone(), copy);
+            }
+        }
+        redacted
+    }
+
+    /// Masks the secret values below `path` in `value`, the value stored at `path`.
+    pub(crate) fn redact_below(&self, path: &Path, mut value: Value) -> Value {
+        if !self.secrets.is_empty() {
+            self.redact(&mut value, &mut path.segments().to_vec());
+        }
+        value
+    }
+
+    /// Masks the secret values below `segments` in `node` like [`Context::redact`], copying only
+    /// the nodes on the way to them.
+    fn redact_node(&self, node: &mut Node, segments: &mut Vec<Segment>) {
+        let path = Path::from(segments.clone());
+        if self.secrets.iter().any(|pattern| path.matches(pattern)) {
+            *node = Node::Leaf(Value::String(REDACTED.to_string()));
+            return;
+        }
+        match node {
+            Node::Leaf(value) => self.redact(value, segments),
+            node => node.update_items(|segment, item| {
+                segments.push(segment);
+                self.redact_node(item, segments);
+                segments.pop();
+            }),
+        }
+    }
+
+    fn redact(&self, value: &mut Value, segments: &mut Vec<Segment>) {
+        let path = Path::from(segments.clone());
+        if self.secrets.iter().any(|pattern| path.matches(pattern)) {
+            *value = Value::String(REDACTED.to_string());
+            return;
+        }
+        match value {
+            Value::Map(map) => {
+                for (key, value) in map.iter_mut() {
+                    segments.push(key_segment(key));
+                    self.redact(value, segments);
+                    segments.pop();
+                }
+            }
+            Value::Seq(seq) => {
+                for (index, value) in seq.iter_mut().enumerate() {
+                    segments.push(Segment::Index(index));
+                    self.redact(value, segments);
+                    segments.pop();
+                }
+            }
+            Value::Option(Some(inner)) | Value::Newtype(inner) => self.redact(inner, segments),
+            _ => {}
+        }
+    }
+}
+
+/// Adds `pattern` to `secrets` unless it is already there.
+fn mark(secrets: &mut Vec<String>, pattern: &str) {
+    if !secrets.iter().any(|known| known == pattern) {
+        secrets.push(pattern.to_string());
+    }
+}
+
+/// Returns `true` if `path`, or one of its parents, matches one of the `secrets` patterns.
+fn covered(secrets: &[String], path: &Path) -> bool {
+    let segments = path.segments();
+    (1..=segments.len()).any(|len| {
+        let path = Path::from(segments[..len].to_vec());
+        secrets.iter().any(|pattern| path.matches(pattern))
+    })
+}
+
+/// Marks the secret values of `value`, just moved or copied from `from` to `to`, as secret at
+/// their new location.
+///
+/// The marks of the old location are kept: they are harmless once it is empty, and still needed
+/// after a copy.
+pub(crate) fn carry_secrets(secrets: &mut Vec<String>, from: &Path, to: &Path, node: &Node) {
+    if covered(secrets, from) {
+        mark(secrets, &to.to_string());
+        return;
+    }
+    let mut marks = Vec::new();
+    find_secrets(
+        secrets,
+        node,
+        &mut from.segments().to_vec(),
+        from.segments().len(),
+        &mut marks,
+    );
+    for relative in marks {
+        let mut path = to.clone();
+        relative.into_iter().for_each(|segment| path.push(segment));
+        mark(secrets, &path.to_string());
+    }
+}
+
+/// Collects in `marks` the paths, relative to the first `base` segments, of the values below
+/// `segments` matching one of the `secrets` patterns.
+fn find_secrets(
+    secrets: &[String],
+    node: &Node,
+    segments: &mut Vec<Segment>,
+    base: usize,
+    marks: &mut Vec<Vec<Segment>>,
+) {
+    if segments.len() > base {
+        let path = Path::from(segments.clone());
+        if secrets.iter().any(|pattern| path.matches(pattern)) {
+            marks.push(segments[base..].to_vec());
+            return;
+        }
+    }
+    if let Node::Leaf(value) = node {
+        if let inner @ (Value::Map(_) | Value::Seq(_)) = unwrap(value) {
+            find_secrets(secrets, &Node::from(inner.clone()), segments, base, marks);
+        }
+        return;
+    }
+    for (segment, node) in node.items() {
+        segments.push(segment);
+        find_secrets(secrets, node, segments, base, marks);
+        segments.pop();
+    }
+}
+
+impl fmt::Debug for Context {
+    /// Formats the `Context` like a derived implementation would, with secret values masked.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("inner", &self.redacted().inner)
+            .finish()
+    }
+}
+
+/// Writes one `path: value` line per leaf of `value`.
+fn write_leaves(f: &mut fmt::Formatter<'_>, path: &Path, value: &Value) -> fmt::Result {
+    match unwrap(value) {
+        Value::Map(map) if !map.is_empty() => map
+            .iter()
+            .try_for_each(|(key, value)| write_leaves(f, &path.join(key_segment(key)), value)),
+        Value::Seq(seq) if !seq.is_empty() => {
+            seq.iter().enumerate().try_for_each(|(index, value)| {
+                write_leaves(f, &path.join(Segment::Index(index)), value)
+            })
+        }
+        value => writeln!(f, "{}: {}", path, to_inline(value)),
+    }
+}
+
+/// Writes one `path: value` line per leaf of `node`, like [`write_leaves`].
+fn write_node(f: &mut fmt::Formatter<'_>, path: &Path, node: &Node) -> fmt::Result {
+    if let Node::Leaf(value) = node {
+        return write_leaves(f, path, value);
+    }
+    match node.items() {
+        items if items.is_empty() => writeln!(f, "{}: {}", path, to_inline(&node.as_value())),
+        items => items
+            .into_iter()
+            .try_for_each(|(segment, node)| write_node(f, &path.join(segment), node)),
+    }
+}
+
+impl fmt::Display for Context {
+    /// Renders one `path: value` line per leaf value, with secret values masked.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, node) in self.redacted().inner.nodes() {
+            write_node(f, &Path::root().join(Segment::Key(key.clone())), node)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_survive_updates() {
+        let mut context = Context::new();
+        context
+            .set_secret("db.password", Value::String("hunter2".to_string()))
+            .unwrap();
+        let mut overrides = Context::from_args(["--set", "api.key=k"]).unwrap();
+        overrides.mark_secret("api.key");
+        context.merge(overrides);
+        context
+            .apply_transform(&crate::Transform::new().move_to("db", "database"))
+            .unwrap();
+
+        assert!(context.is_secret("api.key"));
+        assert!(context.is_secret("database.password"));
+        assert_eq!(
+            context.to_string(),
+            "api.key: \"[REDACTED]\"\ndatabase.password: \"[REDACTED]\"\n"
+        );
+        assert!(!format!("{:?}", context).contains("hunter2"));
+
+        context
+            .apply_transform(&crate::Transform::new().rename("database.password", "pass"))
+            .unwrap();
+        assert!(context.is_secret("database.pass"));
+        context.mark_secret("database");
+        assert_eq!(
+            context.to_string(),
+            "api.key: \"[REDACTED]\"\ndatabase: \"[REDACTED]\"\n"
+        );
+    }
+
+    #[test]
+    fn test_secrets_follow_patches() {
+        let mut context =
+            Context::from_args(["--set", "db.user=admin", "--set", "list[0]=x"]).unwrap();
+        context
+            .set_secret("db.password", Value::String("hunter2".to_string()))
+            .unwrap();
+        context
+            .apply_json_patch(&[
+                crate::Op::Copy {
+                    from: Path::from_pointer("/db").unwrap(),
+                    path: Path::from_pointer("/backup").unwrap(),
+                },
+                crate::Op::Move {
+                    from: Path::from_pointer("/db/password").unwrap(),
+                    path: Path::from_pointer("/list/-").unwrap(),
+                },
+            ])
+            .unwrap();
+
+        assert!(context.is_secret("backup.password"));
+        assert!(!context.is_secret("backup.user"));
+        assert!(context.is_secret("list[1]"));
+        assert!(!context.is_secret("list[0]"));
+        assert!(!context.to_string().contains("hunter2"));
+    }
+}
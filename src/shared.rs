@@ -0,0 +1,192 @@
+use crate::Context;
+use arc_swap::ArcSwap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A `Context` shared between threads, read through immutable snapshots.
+///
+/// Readers get an `Arc` to the current version, which stays valid and unchanged for as long as
+/// they hold it. Writers build the next version on a private copy and publish it with an atomic
+/// pointer swap, so readers never wait, not even for that swap, and never see a partially applied
+/// transaction. Writers are serialized with each other. If the `Context` has an audit log
+/// attached, every published transaction is recorded in it, and only those.
+///
+/// Example:
+/// ```
+/// use std::sync::Arc;
+///
+/// let shared = Arc::new(oxidex::SharedContext::new(oxidex::Context::from_args(["--set", "pool.size=4"]).unwrap()));
+/// let before = shared.snapshot();
+///
+/// let writer = shared.clone();
+/// std::thread::spawn(move || {
+///     writer.transaction(|context| {
+///         context.set_path("pool.size", serde_value::Value::U64(8)).unwrap();
+///         context.set_path("pool.timeout", serde_value::Value::U64(30)).unwrap();
+///     })
+/// })
+/// .join()
+/// .unwrap();
+///
+/// assert_eq!(before.get_path("pool.size").unwrap(), &serde_value::Value::U64(4));
+/// assert_eq!(shared.snapshot().get_path("pool.size").unwrap(), &serde_value::Value::U64(8));
+/// ```
+#[derive(Default)]
+pub struct SharedContext {
+    current: ArcSwap<Context>,
+    writer: Mutex<()>,
+}
+
+impl fmt::Debug for SharedContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedContext")
+            .field(&self.snapshot())
+            .finish()
+    }
+}
+
+impl From<Context> for SharedContext {
+    fn from(context: Context) -> Self {
+        SharedContext::new(context)
+    }
+}
+
+impl SharedContext {
+    /// Creates a `SharedContext` whose first version is `context`.
+    pub fn new(context: Context) -> SharedContext {
+        SharedContext {
+            current: ArcSwap::from_pointee(context),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current version of the `Context`.
+    ///
+    /// The snapshot is never modified: later updates publish new versions instead.
+    pub fn snapshot(&self) -> Arc<Context> {
+        self.current.load_full()
+    }
+
+    /// Publishes a new version of the `Context`.
+    ///
+    /// `context`: The new version.
+    ///
+    /// Returns the previous version.
+    pub fn store(&self, context: Context) -> Arc<Context> {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        self.publish(context)
+    }
+
+    fn publish(&self, context: Context) -> Arc<Context> {
+        self.current.swap(Arc::new(context))
+    }
+
+    /// Returns a copy of the current version to build the next one on, with its audit log.
+    fn next_version(&self) -> Context {
+        let current = self.snapshot();
+        let mut context = Context::clone(&current);
+        context.audit = current.audit.clone();
+        context
+    }
+
+    /// Updates several values atomically.
+    ///
+    /// `f` runs on a copy of the current version, which is published once it returns: readers
+    /// see either none or all of its updates. If `f` panics, nothing is published.
+    ///
+    /// `f`: The function modifying the `Context`.
+    pub fn transaction<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Context) -> T,
+    {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut context = self.next_version();
+        let result = context.audited(f);
+        self.publish(context);
+        result
+    }
+
+    /// Updates several values atomically, publishing them only if `f` succeeds.
+    ///
+    /// `f`: The function modifying the `Context`.
+    ///
+    /// # Errors
+    /// - Returns the error of `f`; the current version is kept then.
+    pub fn try_transaction<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Context) -> Result<T, E>,
+    {
+        let _writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut context = self.next_version();
+        let result = context.try_audited(f)?;
+        self.publish(context);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_value::Value;
+
+    #[test]
+    fn test_readers_never_see_partial_transactions() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedContext>();
+
+        let shared = Arc::new(SharedContext::new(
+            Context::from_args(["--set", "a=0", "--set", "b=0"]).unwrap(),
+        ));
+        let writer = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for i in 1..=200u64 {
+                    shared.transaction(|context| {
+                        context.insert("a".to_string(), Value::U64(i));
+                        context.insert("b".to_string(), Value::U64(i));
+                    });
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let snapshot = shared.snapshot();
+            assert_eq!(snapshot.get("a"), snapshot.get("b"));
+        }
+        writer.join().unwrap();
+
+        let failed: Result<(), &str> = shared.try_transaction(|context| {
+            context.insert("a".to_string(), Value::U64(0));
+            Err("rejected")
+        });
+        assert!(failed.is_err());
+        assert_eq!(shared.snapshot().get("a"), Some(&Value::U64(200)));
+    }
+
+    #[test]
+    fn test_only_published_transactions_are_audited() {
+        let log = crate::AuditLog::new("ops");
+        let mut context = Context::new();
+        context.attach_audit(log.clone());
+        let shared = SharedContext::new(context);
+
+        let failed: Result<(), &str> = shared.try_transaction(|context| {
+            context.insert("a".to_string(), Value::U64(1));
+            Err("rejected")
+        });
+        assert!(failed.is_err());
+        assert!(log.is_empty());
+
+        shared.transaction(|context| {
+            context.insert("a".to_string(), Value::U64(1));
+            context.insert("b".to_string(), Value::U64(2));
+        });
+        let _ = Context::clone(&shared.snapshot()).set_path("c", Value::U64(3));
+        let paths: Vec<String> = log
+            .entries()
+            .iter()
+            .map(|entry| entry.path.to_string())
+            .collect();
+        assert_eq!(paths, ["a", "b"]);
+        assert!(shared.snapshot().audit_log().is_some());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::canonical::write_canonical;
+use crate::value::unwrap;
+use crate::{Context, Error};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signer, Verifier};
+use hmac::{Hmac, Mac};
+use serde_value::Value;
+use sha2::Sha256;
+use std::fmt;
+
+/// The top-level key under which [`Context::sign`] embeds the signature.
+pub const SIGNATURE_KEY: &str = "_signature";
+
+const HMAC_SHA256: &str = "hmac-sha256";
+const ED25519: &str = "ed25519";
+
+#[derive(Clone)]
+enum KeyKind {
+    HmacSha256(Vec<u8>),
+    Ed25519(ed25519_dalek::SigningKey),
+    Ed25519Public(ed25519_dalek::VerifyingKey),
+}
+
+/// A key to sign contexts with, or to verify their signatures.
+///
+/// HMAC-SHA256 keys are shared secrets used both to sign and to verify. Ed25519 keys are built
+/// from a private seed, and their public half (see [`SigningKey::public_key`]) can be handed to
+/// the agents verifying signatures without letting them sign.
+#[derive(Clone)]
+pub struct SigningKey {
+    kind: KeyKind,
+}
+
+impl fmt::Debug for SigningKey {
+    /// Formats the algorithm of the key, never the key material itself.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self.kind {
+            KeyKind::HmacSha256(_) => "HmacSha256",
+            KeyKind::Ed25519(_) => "Ed25519",
+            KeyKind::Ed25519Public(_) => "Ed25519Public",
+        };
+        f.debug_tuple("SigningKey").field(&algorithm).finish()
+    }
+}
+
+impl SigningKey {
+    /// Creates an HMAC-SHA256 key from a shared secret.
+    ///
+    /// `secret`: The shared secret, ideally at least 32 random bytes.
+    pub fn hmac_sha256<K: AsRef<[u8]>>(secret: K) -> SigningKey {
+        SigningKey {
+            kind: KeyKind::HmacSha256(secret.as_ref().to_vec()),
+        }
+    }
+
+    /// Creates an Ed25519 signing key from its 32-byte private seed.
+    pub fn ed25519(seed: &[u8; 32]) -> SigningKey {
+        SigningKey {
+            kind: KeyKind::Ed25519(ed25519_dalek::SigningKey::from_bytes(seed)),
+        }
+    }
+
+    /// Creates an Ed25519 key that can only verify signatures, from a 32-byte public key.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Signature` variant if the bytes are not a valid public key.
+    pub fn ed25519_public(public_key: &[u8; 32]) -> crate::Result<SigningKey> {
+        let key = ed25519_dalek::VerifyingKey::from_bytes(public_key)
+            .map_err(|err| Error::Signature(format!("invalid Ed25519 public key: {}", err)))?;
+        Ok(SigningKey {
+            kind: KeyKind::Ed25519Public(key),
+        })
+    }
+
+    /// Returns the verification-only half of an Ed25519 key, or `None` for an HMAC key.
+    pub fn public_key(&self) -> Option<SigningKey> {
+        let key = match &self.kind {
+            KeyKind::HmacSha256(_) => return None,
+            KeyKind::Ed25519(key) => key.verifying_key(),
+            KeyKind::Ed25519Public(key) => *key,
+        };
+        Some(SigningKey {
+            kind: KeyKind::Ed25519Public(key),
+        })
+    }
+
+    /// Returns the bytes of the Ed25519 public key, or `None` for an HMAC key.
+    pub fn public_key_bytes(&self) -> Option<[u8; 32]> {
+        match self.public_key()?.kind {
+            KeyKind::Ed25519Public(key) => Some(key.to_bytes()),
+            _ => None,
+        }
+    }
+
+    fn mac(secret: &[u8]) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length")
+    }
+
+    /// Signs a message, returning an `<algorithm>:<base64>` signature.
+    fn sign(&self, message: &[u8]) -> crate::Result<String> {
+        let (algorithm, signature) = match &self.kind {
+            KeyKind::HmacSha256(secret) => {
+                let mut mac = SigningKey::mac(secret);
+                mac.update(message);
+                (HMAC_SHA256, mac.finalize().into_bytes().to_vec())
+            }
+            KeyKind::Ed25519(key) => (ED25519, key.sign(message).to_bytes().to_vec()),
+            KeyKind::Ed25519Public(_) => {
+                return Err(Error::Signature(
+                    "an Ed25519 public key cannot sign".to_string(),
+                ))
+            }
+        };
+        Ok(format!("{}:{}", algorithm, STANDARD.encode(signature)))
+    }
+
+    /// Verifies an `<algorithm>:<base64>` signature of a message.
+    fn verify(&self, message: &[u8], signature: &str) -> crate::Result<()> {
+        let (algorithm, encoded) = signature
+            .split_once(':')
+            .ok_or_else(|| Error::Signature("malformed signature".to_string()))?;
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|err| Error::Signature(format!("malformed signature: {}", err)))?;
+        let valid = match (&self.kind, algorithm) {
+            (KeyKind::HmacSha256(secret), HMAC_SHA256) => {
+                let mut mac = SigningKey::mac(secret);
+                mac.update(message);
+                mac.verify_slice(&bytes).is_ok()
+            }
+            (KeyKind::Ed25519(_) | KeyKind::Ed25519Public(_), ED25519) => {
+                let Some(KeyKind::Ed25519Public(key)) = self.public_key().map(|key| key.kind)
+                else {
+                    unreachable!("Ed25519 keys have a public key")
+                };
+                ed25519_dalek::Signature::from_slice(&bytes)
+                    .map(|signature| key.verify(message, &signature).is_ok())
+                    .unwrap_or(false)
+            }
+            (_, algorithm) => {
+                return Err(Error::Signature(format!(
+                    "the key cannot verify '{}' signatures",
+                    algorithm
+                )))
+            }
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(Error::Signature("signature mismatch".to_string())),
+        }
+    }
+}
+
+impl Context {
+    /// Returns the canonical serialization of the `Context`, the message covered by signatures.
+    ///
+    /// The canonical form is compact JSON with keys sorted by their UTF-8 bytes, integers
+    /// written without a fractional part and floats with one, and it leaves out the embedded
+    /// signature. A context therefore has the same canonical form after a round trip through
+    /// JSON, YAML or TOML.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Signature` variant if the `Context` holds a NaN or infinite float,
+    ///   which has no canonical form.
+    ///
+    /// Example:
+    /// ```
+    /// let context = oxidex::Context::from_args(["--set", "b=1", "--set", "a.y=0.5", "--set", "a.x=\"hi\""]).unwrap();
+    ///
+    /// assert_eq!(context.to_canonical().unwrap(), r#"{"a":{"x":"hi","y":0.5},"b":1}"#);
+    /// ```
+    pub fn to_canonical(&self) -> crate::Result<String> {
+        let mut root = self.to_value();
+        if let Value::Map(map) = &mut root {
+            map.remove(&Value::String(SIGNATURE_KEY.to_string()));
+        }
+        let mut out = String::new();
+        write_canonical(&mut out, &root).ok_or_else(|| {
+            Error::Signature("NaN and infinite floats have no canonical form".to_string())
+        })?;
+        Ok(out)
+    }
+
+    /// Returns a detached signature of the canonical form of the `Context`.
+    ///
+    /// `key`: The key to sign with.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Signature` variant if the key can only verify signatures or if the
+    ///   `Context` has no canonical form.
+    pub fn signature(&self, key: &SigningKey) -> crate::Result<String> {
+        key.sign(self.to_canonical()?.as_bytes())
+    }
+
+    /// Signs the `Context`, embedding the signature under the top-level `_signature` key.
+    ///
+    /// The embedded signature is exported along with the other values and does not cover itself,
+    /// so a signed context can be sent in any format and checked with [`Context::verify`].
+    ///
+    /// `key`: The key to sign with.
+    ///
+    /// Returns the signature.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Signature` variant if the key can only verify signatures or if the
+    ///   `Context` has no canonical form.
+    ///
+    /// Example:
+    /// ```
+    /// let key = oxidex::SigningKey::ed25519(&[7; 32]);
+    /// let mut context = oxidex::Context::from_args(["--set", "feature.enabled=true"]).unwrap();
+    /// context.sign(&key).unwrap();
+    ///
+    /// let public = key.public_key().unwrap();
+    /// assert!(context.verify(&public).is_ok());
+    ///
+    /// context.set_path("feature.enabled", serde_value::Value::Bool(false)).unwrap();
+    /// assert!(context.verify(&public).is_err());
+    /// ```
+    pub fn sign(&mut self, key: &SigningKey) -> crate::Result<String> {
+        let signature = self.signature(key)?;
+        self.insert(SIGNATURE_KEY.to_string(), Value::String(signature.clone()));
+        Ok(signature)
+    }
+
+    /// Verifies the signature embedded by [`Context::sign`].
+    ///
+    /// `key`: The key to verify with.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Signature` variant if the `Context` has no embedded signature, if the
+    ///   key does not match the signature's algorithm or if the signature does not match.
+    pub fn verify(&self, key: &SigningKey) -> crate::Result<()> {
+        match self.get(SIGNATURE_KEY).map(unwrap) {
+            Some(Value::String(signature)) => self.verify_signature(key, signature),
+            _ => Err(Error::Signature("the context is not signed".to_string())),
+        }
+    }
+
+    /// Verifies a detached signature returned by [`Context::signature`].
+    ///
+    /// `key`: The key to verify with.
+    /// `signature`: The signature.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Signature` variant if the signature is malformed, if the key does not
+    ///   match its algorithm, if it does not match or if the `Context` has no canonical form.
+    pub fn verify_signature(&self, key: &SigningKey, signature: &str) -> crate::Result<()> {
+        key.verify(self.to_canonical()?.as_bytes(), signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Context {
+        let mut context = Context::from_args([
+            "--set",
+            "name=\"edge \\\"eu\\\"\"",
+            "--set",
+            "limits.ratio=1.0",
+            "--set",
+            "limits.burst=20",
+            "--set",
+            "limits.offset=-4",
+            "--set",
+            "tags[0]=a",
+            "--set",
+            "tags[1]=b",
+        ])
+        .unwrap();
+        context.insert("small".to_string(), Value::F32(0.1));
+        context
+    }
+
+    #[test]
+    fn test_hmac_signature_and_tampering() {
+        let key = SigningKey::hmac_sha256(b"shared secret");
+        let mut context = sample();
+        let signature = context.signature(&key).unwrap();
+        assert!(signature.starts_with("hmac-sha256:"));
+        assert_eq!(context.sign(&key).unwrap(), signature);
+        assert!(context.verify(&key).is_ok());
+
+        assert!(context.verify(&SigningKey::hmac_sha256(b"other")).is_err());
+        assert!(context.verify(&SigningKey::ed25519(&[1; 32])).is_err());
+        context
+            .set_path("tags[1]", Value::String("c".to_string()))
+            .unwrap();
+        assert!(matches!(context.verify(&key), Err(Error::Signature(_))));
+        assert!(SigningKey::ed25519(&[1; 32])
+            .public_key()
+            .map(|key| Context::new().signature(&key).is_err())
+            .unwrap());
+    }
+
+    #[test]
+    fn test_non_finite_floats_cannot_be_signed() {
+        let key = SigningKey::hmac_sha256(b"secret");
+        let mut context = sample();
+        context.insert("ratio".to_string(), Value::F64(f64::INFINITY));
+        assert!(matches!(context.to_canonical(), Err(Error::Signature(_))));
+        assert!(matches!(context.sign(&key), Err(Error::Signature(_))));
+        assert!(context.get(SIGNATURE_KEY).is_none());
+    }
+
+    #[cfg(all(feature = "json", feature = "toml", feature = "yaml"))]
+    #[test]
+    fn test_signature_survives_format_round_trips() {
+        let key = SigningKey::ed25519(&[3; 32]);
+        let mut context = sample();
+        context.sign(&key).unwrap();
+        let public = SigningKey::ed25519_public(&key.public_key_bytes().unwrap()).unwrap();
+
+        let json = Context::from_json(&context.to_json(true).unwrap()).unwrap();
+        let toml = Context::from_toml(&json.to_toml(false).unwrap()).unwrap();
+        let yaml = Context::from_yaml(&toml.to_yaml().unwrap()).unwrap();
+        for reloaded in [json, toml, yaml] {
+            assert!(reloaded.verify(&public).is_ok());
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use crate::canonical::write_canonical;
+use crate::{Context, Diff, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Returns the number of milliseconds between the Unix epoch and a time.
+fn millis(time: &SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn serialize_millis<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(millis(time))
+}
+
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+    Ok(UNIX_EPOCH + Duration::from_millis(u64::deserialize(deserializer)?))
+}
+
+/// A version committed to a [`ContextStore`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Version {
+    /// The number of the version, starting at 1.
+    pub number: u64,
+    /// The SHA-256 of the canonical serialization of the `Context`, in hexadecimal.
+    pub hash: String,
+    /// The message given to [`ContextStore::commit`].
+    pub message: String,
+    /// When the version was committed, serialized as milliseconds since the Unix epoch.
+    #[serde(
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub timestamp: SystemTime,
+}
+
+/// Where the versions are persisted.
+enum Backend {
+    /// A directory holding a `log.jsonl` file, one JSON line per version, and an `objects`
+    /// directory with one `<hash>.json` file per distinct content.
+    Directory(PathBuf),
+    /// An SQLite database with an `objects` and a `versions` table.
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Connection),
+}
+
+/// Returns the canonical serialization of a `Context` and its SHA-256, in hexadecimal.
+fn canonical(context: &Context) -> crate::Result<(String, String)> {
+    let mut content = String::new();
+    write_canonical(&mut content, &context.to_value())
+        .ok_or_else(|| Error::Store("NaN and infinite floats cannot be stored".to_string()))?;
+    let mut hash = String::with_capacity(64);
+    for byte in Sha256::digest(content.as_bytes()) {
+        let _ = write!(hash, "{:02x}", byte);
+    }
+    Ok((content, hash))
+}
+
+/// A history of the published versions of a `Context`, persisted to disk.
+///
+/// Versions are stored in their canonical serialization, the compact JSON with sorted keys that
+/// signatures also cover, and identified by its hash, so identical versions share their stored
+/// content, whatever format they were loaded from. Committing a `Context` identical to the latest
+/// version records nothing. Secret marks are not persisted: encrypt secret values before
+/// committing them.
+///
+/// The store is meant to have a single writer: two `ContextStore` opened on the same location
+/// do not see each other's commits.
+///
+/// Example:
+/// ```
+/// let directory = std::env::temp_dir().join(format!("oxidex-doc-store-{}", std::process::id()));
+/// let mut store = oxidex::ContextStore::open(&directory).unwrap();
+///
+/// let mut context = oxidex::Context::from_args(["--set", "db.port=5432"]).unwrap();
+/// let first = store.commit(&context, "initial version").unwrap();
+/// context.set_path("db.port", serde_value::Value::U64(6432)).unwrap();
+/// let second = store.commit(&context, "move to the pooler").unwrap();
+/// assert_eq!(store.commit(&context, "no change").unwrap(), second);
+///
+/// let store = oxidex::ContextStore::open(&directory).unwrap();
+/// assert_eq!(store.log().len(), 2);
+/// assert_eq!(store.get(first.number).unwrap().get_path("db.port").unwrap(), &serde_value::Value::U64(5432));
+/// assert_eq!(store.diff(first.number, second.number).unwrap().to_string(), "- db.port: 5432\n+ db.port: 6432\n");
+/// # std::fs::remove_dir_all(&directory).unwrap();
+/// ```
+pub struct ContextStore {
+    backend: Backend,
+    log: Vec<Version>,
+}
+
+impl std::fmt::Debug for ContextStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = match &self.backend {
+            Backend::Directory(directory) => directory.display().to_string(),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(connection) => connection.path().unwrap_or(":memory:").to_string(),
+        };
+        f.debug_struct("ContextStore")
+            .field("location", &location)
+            .field("versions", &self.log.len())
+            .finish()
+    }
+}
+
+impl ContextStore {
+    /// Opens the store persisted in a directory, creating it if needed.
+    ///
+    /// `directory`: The directory holding the store.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Io` variant if the directory cannot be created or read.
+    /// - Returns an `Error::Store` variant if its log is malformed.
+    pub fn open<P: AsRef<Path>>(directory: P) -> crate::Result<ContextStore> {
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(directory.join("objects"))?;
+        let log = match std::fs::read_to_string(directory.join("log.jsonl")) {
+            Ok(text) => text
+                .lines()
+                .enumerate()
+                .map(|(index, line)| {
+                    serde_json::from_str(line).map_err(|err| {
+                        Error::Store(format!("malformed log line {}: {}", index + 1, err))
+                    })
+                })
+                .collect::<crate::Result<Vec<Version>>>()?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(ContextStore {
+            backend: Backend::Directory(directory),
+            log,
+        })
+    }
+
+    /// Opens the store persisted in an SQLite database, creating it if needed, available if the
+    /// "sqlite" feature is enabled.
+    ///
+    /// `path`: The database file.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Store` variant if the database cannot be opened or read.
+    #[cfg(feature = "sqlite")]
+    pub fn open_sqlite<P: AsRef<Path>>(path: P) -> crate::Result<ContextStore> {
+        let connection = rusqlite::Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS objects (
+                 hash TEXT PRIMARY KEY,
+                 content TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS versions (
+                 number INTEGER PRIMARY KEY,
+                 hash TEXT NOT NULL REFERENCES objects (hash),
+                 message TEXT NOT NULL,
+                 timestamp INTEGER NOT NULL
+             );",
+        )?;
+        let log = connection
+            .prepare("SELECT number, hash, message, timestamp FROM versions ORDER BY number")?
+            .query_map([], |row| {
+                Ok(Version {
+                    number: row.get(0)?,
+                    hash: row.get(1)?,
+                    message: row.get(2)?,
+                    timestamp: UNIX_EPOCH + Duration::from_millis(row.get(3)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Version>>>()?;
+        Ok(ContextStore {
+            backend: Backend::Sqlite(connection),
+            log,
+        })
+    }
+
+    /// Commits a new version of a `Context`.
+    ///
+    /// `context`: The version to store.
+    /// `message`: A description of the version.
+    ///
+    /// Returns the new version, or the latest one if it is identical to `context`.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Io` or `Error::Store` variant if the version cannot be persisted.
+    /// - Returns an `Error::Store` variant if the context holds a float JSON cannot represent.
+    pub fn commit(&mut self, context: &Context, message: &str) -> crate::Result<Version> {
+        let (content, hash) = canonical(context)?;
+        if let Some(head) = self.log.last().filter(|head| head.hash == hash) {
+            return Ok(head.clone());
+        }
+        let version = Version {
+            number: self.log.len() as u64 + 1,
+            hash,
+            message: message.to_string(),
+            timestamp: SystemTime::now(),
+        };
+        match &mut self.backend {
+            Backend::Directory(directory) => {
+                let object = directory
+                    .join("objects")
+                    .join(format!("{}.json", version.hash));
+                if !object.exists() {
+                    // Written aside then renamed, so that an object file is never half-written.
+                    let staging = object.with_extension("tmp");
+                    std::fs::write(&staging, &content)?;
+                    std::fs::rename(staging, object)?;
+                }
+                let mut line = serde_json::to_string(&version)?;
+                line.push('\n');
+                let mut log = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(directory.join("log.jsonl"))?;
+                log.write_all(line.as_bytes())?;
+                log.sync_all()?;
+            }
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(connection) => {
+                let transaction = connection.transaction()?;
+                transaction.execute(
+                    "INSERT OR IGNORE INTO objects (hash, content) VALUES (?1, ?2)",
+                    (&version.hash, &content),
+                )?;
+                transaction.execute(
+                    "INSERT INTO versions (number, hash, message, timestamp) VALUES (?1, ?2, ?3, ?4)",
+                    (
+                        version.number,
+                        &version.hash,
+                        &version.message,
+                        millis(&version.timestamp),
+                    ),
+                )?;
+                transaction.commit()?;
+            }
+        }
+        self.log.push(version.clone());
+        Ok(version)
+    }
+
+    /// Returns the committed versions, oldest first.
+    pub fn log(&self) -> &[Version] {
+        &self.log
+    }
+
+    /// Returns the latest version, or `None` if nothing was committed.
+    pub fn head(&self) -> Option<&Version> {
+        self.log.last()
+    }
+
+    /// Loads a committed version.
+    ///
+    /// `number`: The number of the version.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Store` variant if the version does not exist or its stored content
+    ///   does not match its hash.
+    /// - Returns an `Error::Io` variant if the stored content cannot be read.
+    pub fn get(&self, number: u64) -> crate::Result<Context> {
+        let version = number
+            .checked_sub(1)
+            .and_then(|index| self.log.get(index as usize))
+            .ok_or_else(|| Error::Store(format!("unknown version {}", number)))?;
+        let content = match &self.backend {
+            Backend::Directory(directory) => std::fs::read_to_string(
+                directory
+                    .join("objects")
+                    .join(format!("{}.json", version.hash)),
+            )?,
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite(connection) => connection.query_row(
+                "SELECT content FROM objects WHERE hash = ?1",
+                [&version.hash],
+                |row| row.get(0),
+            )?,
+        };
+        let context = Context::from_json(&content)?;
+        match canonical(&context)?.1 == version.hash {
+            true => Ok(context),
+            false => Err(Error::Store(format!(
+                "the content of version {} does not match its hash",
+                number
+            ))),
+        }
+    }
+
+    /// Computes the structural differences between two committed versions.
+    ///
+    /// `from`: The number of the old version.
+    /// `to`: The number of the new version.
+    ///
+    /// # Errors
+    /// - Returns the errors of [`ContextStore::get`].
+    pub fn diff(&self, from: u64, to: u64) -> crate::Result<Diff> {
+        Ok(self.get(from)?.diff(&self.get(to)?))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    /// Converts a `rusqlite::Error` into an `Error::Store`.
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Store(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_value::Value;
+    use std::fs::File;
+
+    fn check(store: &mut ContextStore) {
+        let base = Context::from_args(["--set", "a=1", "--set", "b.c=\"x\""]).unwrap();
+        let mut changed = base.clone();
+        changed.set_path("b.c", Value::F32(0.1)).unwrap();
+        let first = store.commit(&base, "first").unwrap();
+        let second = store.commit(&changed, "second").unwrap();
+        assert_eq!(store.commit(&changed, "again").unwrap(), second);
+        let third = store.commit(&base, "revert").unwrap();
+
+        assert_eq!(third.hash, first.hash);
+        assert_eq!(third.number, 3);
+        assert!(store.get(third.number).unwrap().diff(&base).is_empty());
+        assert_eq!(store.diff(1, 2).unwrap().len(), 1);
+        assert!(matches!(store.get(4), Err(Error::Store(_))));
+        let mut invalid = Context::new();
+        invalid.insert("x".to_string(), Value::F64(f64::NAN));
+        assert!(matches!(
+            store.commit(&invalid, "nan"),
+            Err(Error::Store(_))
+        ));
+    }
+
+    #[test]
+    fn test_directory_dedupes_objects() {
+        let directory = std::env::temp_dir().join(format!("oxidex-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        check(&mut ContextStore::open(&directory).unwrap());
+
+        assert_eq!(
+            std::fs::read_dir(directory.join("objects"))
+                .unwrap()
+                .count(),
+            2
+        );
+        let reopened = ContextStore::open(&directory).unwrap();
+        assert_eq!(reopened.log()[1].message, "second");
+        assert_eq!(
+            reopened.get(2).unwrap().get_path("b.c"),
+            Some(&Value::F64(0.1))
+        );
+        File::create(directory.join("log.jsonl"))
+            .unwrap()
+            .write_all(b"{")
+            .unwrap();
+        assert!(matches!(
+            ContextStore::open(&directory),
+            Err(Error::Store(_))
+        ));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_persists_versions() {
+        let path = std::env::temp_dir().join(format!("oxidex-store-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        check(&mut ContextStore::open_sqlite(&path).unwrap());
+
+        let reopened = ContextStore::open_sqlite(&path).unwrap();
+        assert_eq!(reopened.log().len(), 3);
+        assert_eq!(reopened.head().unwrap().message, "revert");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -64,6 +64,7 @@ impl Context {
                 .into_iter()
                 .map(|(key, value)| (key, serde_value::Value::deserialize(value).unwrap()))
                 .collect(),
+            ..Context::default()
         })
     }
 
@@ -93,4 +94,13 @@ impl Context {
             false => Ok(toml::to_string(&self)?),
         }
     }
+
+    /// Serializes the `Context` into a TOML string, with secret values masked (see
+    /// [`Context::mark_secret`]).
+    ///
+    /// # Errors
+    /// - Returns an `Error::Toml` variant if serialization fails.
+    pub fn to_toml_redacted(&self, pretty: bool) -> crate::Result<String> {
+        self.redacted().to_toml(pretty)
+    }
 }
@@ -0,0 +1,486 @@
+use crate::diff::key_segment;
+use crate::entries::Node;
+use crate::path::{descend_mut, find_node, insert_at, lookup_mut, remove_at, Path, Segment};
+use crate::secret::carry_secrets;
+use crate::value::unwrap_mut;
+use crate::{Context, Error};
+use serde::{Deserialize, Serialize};
+use serde_value::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// A function applied to strings by [`TransformStep::MapStrings`].
+///
+/// In a spec, functions without argument are written as a name (`lowercase`) and the others as a
+/// single-entry map (`{"prefix": "https://"}`, `{"replace": {"from": "-", "to": "_"}}`).
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StringFunction {
+    /// Converts the string to lowercase.
+    Lowercase,
+    /// Converts the string to uppercase.
+    Uppercase,
+    /// Removes leading and trailing whitespace.
+    Trim,
+    /// Replaces every occurrence of `from` by `to`.
+    Replace {
+        /// The text to look for.
+        from: String,
+        /// The replacement text.
+        to: String,
+    },
+    /// Adds a prefix to the string.
+    Prefix(String),
+    /// Adds a suffix to the string.
+    Suffix(String),
+    /// Applies a Rust function; it cannot be written in, or loaded from, a spec.
+    #[serde(skip)]
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+impl StringFunction {
+    /// Wraps a Rust function into a `StringFunction`.
+    pub fn custom<F>(function: F) -> StringFunction
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        StringFunction::Custom(Arc::new(function))
+    }
+
+    /// Applies the function to `text`.
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            StringFunction::Lowercase => text.to_lowercase(),
+            StringFunction::Uppercase => text.to_uppercase(),
+            StringFunction::Trim => text.trim().to_string(),
+            StringFunction::Replace { from, to } => text.replace(from.as_str(), to),
+            StringFunction::Prefix(prefix) => format!("{}{}", prefix, text),
+            StringFunction::Suffix(suffix) => format!("{}{}", text, suffix),
+            StringFunction::Custom(function) => function(text),
+        }
+    }
+}
+
+impl fmt::Debug for StringFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StringFunction::Lowercase => write!(f, "Lowercase"),
+            StringFunction::Uppercase => write!(f, "Uppercase"),
+            StringFunction::Trim => write!(f, "Trim"),
+            StringFunction::Replace { from, to } => f
+                .debug_struct("Replace")
+                .field("from", from)
+                .field("to", to)
+                .finish(),
+            StringFunction::Prefix(prefix) => f.debug_tuple("Prefix").field(prefix).finish(),
+            StringFunction::Suffix(suffix) => f.debug_tuple("Suffix").field(suffix).finish(),
+            StringFunction::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+fn default_separator() -> String {
+    ".".to_string()
+}
+
+/// A single step of a [`Transform`].
+///
+/// Steps are tagged by `op` in a spec, e.g. `{"op": "rename", "path": "db.hostname", "to": "host"}`.
+/// Paths use the dotted syntax of [`Path`]; steps whose source path is missing do nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TransformStep {
+    /// Renames the last key of `path` to `to`, keeping the value under the same parent.
+    Rename {
+        /// The location of the value to rename.
+        path: String,
+        /// The new key.
+        to: String,
+    },
+    /// Moves the value at `from` to `to`, creating the missing parents.
+    Move {
+        /// The location of the value to move.
+        from: String,
+        /// The new location of the value.
+        to: String,
+    },
+    /// Removes every value whose path matches a glob pattern (see [`Path::matches`]).
+    Drop {
+        /// The glob pattern, e.g. `**.password`.
+        pattern: String,
+    },
+    /// Applies a function to every string under `path` (the whole context by default).
+    MapStrings {
+        /// The location of the values to update.
+        #[serde(default)]
+        path: String,
+        /// The function to apply.
+        function: StringFunction,
+    },
+    /// Turns the context into a single-level map whose keys join the nested keys with `separator`.
+    Flatten {
+        /// The separator between keys, `.` by default.
+        #[serde(default = "default_separator")]
+        separator: String,
+    },
+    /// Rebuilds nested maps from keys joined with `separator`, reversing
+    /// [`TransformStep::Flatten`].
+    Unflatten {
+        /// The separator between keys, `.` by default.
+        #[serde(default = "default_separator")]
+        separator: String,
+    },
+}
+
+/// A composable list of [`TransformStep`]s reshaping a `Context`, applied with
+/// [`Context::apply_transform`].
+///
+/// A transform is either built in Rust or loaded from a spec (JSON, YAML, ...) holding a `steps`
+/// list, which lets configuration migrations be shipped as data.
+///
+/// # Example
+/// ```
+/// use oxidex::{Context, StringFunction, Transform};
+///
+/// let mut context = Context::from_args([
+///     "--set", "db.hostname=DB.LOCAL",
+///     "--set", "db.password=secret",
+/// ]).unwrap();
+/// let transform = Transform::new()
+///     .rename("db.hostname", "host")
+///     .map_strings("db", StringFunction::Lowercase)
+///     .drop("**.password")
+///     .move_to("db", "database");
+///
+/// context.apply_transform(&transform).unwrap();
+/// assert_eq!(context.get_path("database.host").unwrap(), &serde_value::Value::String("db.local".to_string()));
+/// assert!(context.get_path("database.password").is_none());
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transform {
+    steps: Vec<TransformStep>,
+}
+
+impl Transform {
+    /// Creates an empty `Transform`.
+    pub fn new() -> Transform {
+        Transform::default()
+    }
+
+    /// Loads a transform from a spec holding a `steps` list.
+    ///
+    /// `spec`: A `Context` usually read from a JSON or YAML file.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Transform` variant if the spec does not describe valid steps.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "yaml")] {
+    /// let spec = oxidex::Context::from_yaml(r#"
+    /// steps:
+    ///   - op: rename
+    ///     path: server.addr
+    ///     to: address
+    ///   - op: map_strings
+    ///     path: server
+    ///     function: {prefix: "http://"}
+    /// "#).unwrap();
+    /// let transform = oxidex::Transform::from_context(&spec).unwrap();
+    ///
+    /// let mut context = oxidex::Context::from_args(["--set", "server.addr=localhost"]).unwrap();
+    /// context.apply_transform(&transform).unwrap();
+    /// assert_eq!(
+    ///     context.get_path("server.address").unwrap(),
+    ///     &serde_value::Value::String("http://localhost".to_string())
+    /// );
+    /// # }
+    /// ```
+    pub fn from_context(spec: &Context) -> crate::Result<Transform> {
+        spec.to_value()
+            .deserialize_into()
+            .map_err(|err| Error::Transform(err.to_string()))
+    }
+
+    /// Returns the steps, in application order.
+    pub fn steps(&self) -> &[TransformStep] {
+        &self.steps
+    }
+
+    /// Appends a step.
+    pub fn then(mut self, step: TransformStep) -> Transform {
+        self.steps.push(step);
+        self
+    }
+
+    /// Appends a [`TransformStep::Rename`] step.
+    pub fn rename(self, path: &str, to: &str) -> Transform {
+        self.then(TransformStep::Rename {
+            path: path.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    /// Appends a [`TransformStep::Move`] step.
+    pub fn move_to(self, from: &str, to: &str) -> Transform {
+        self.then(TransformStep::Move {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    /// Appends a [`TransformStep::Drop`] step.
+    pub fn drop(self, pattern: &str) -> Transform {
+        self.then(TransformStep::Drop {
+            pattern: pattern.to_string(),
+        })
+    }
+
+    /// Appends a [`TransformStep::MapStrings`] step.
+    pub fn map_strings(self, path: &str, function: StringFunction) -> Transform {
+        self.then(TransformStep::MapStrings {
+            path: path.to_string(),
+            function,
+        })
+    }
+
+    /// Appends a [`TransformStep::Flatten`] step.
+    pub fn flatten(self, separator: &str) -> Transform {
+        self.then(TransformStep::Flatten {
+            separator: separator.to_string(),
+        })
+    }
+
+    /// Appends a [`TransformStep::Unflatten`] step.
+    pub fn unflatten(self, separator: &str) -> Transform {
+        self.then(TransformStep::Unflatten {
+            separator: separator.to_string(),
+        })
+    }
+}
+
+/// Keeps the secret values moved from `from` secret at `to`.
+fn carry(root: &Node, from: &Path, to: &Path, secrets: &mut Vec<String>) {
+    if let Some(node) = find_node(root, to.segments()) {
+        carry_secrets(secrets, from, to, &node);
+    }
+}
+
+/// Removes the values below `path` matching `pattern`.
+fn drop_matching(value: &mut Value, path: &Path, pattern: &str) {
+    match unwrap_mut(value) {
+        Value::Map(map) => {
+            map.retain(|key, _| !path.join(key_segment(key)).matches(pattern));
+            for (key, value) in map.iter_mut() {
+                drop_matching(value, &path.join(key_segment(key)), pattern);
+            }
+        }
+        Value::Seq(seq) => {
+            let mut index = 0;
+            seq.retain(|_| {
+                index += 1;
+                !path.join(Segment::Index(index - 1)).matches(pattern)
+            });
+            // Remaining items are renumbered, as they would be once the context is written back.
+            for (index, value) in seq.iter_mut().enumerate() {
+                drop_matching(value, &path.join(Segment::Index(index)), pattern);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes the values below `path` matching `pattern` from `node` like [`drop_matching`], copying
+/// only the nodes it changes.
+fn drop_node(node: &mut Node, path: &Path, pattern: &str) {
+    if let Node::Leaf(value) = node {
+        return drop_matching(value, path, pattern);
+    }
+    if let Some(map) = node.map() {
+        let dropped: Vec<Value> = map
+            .keys()
+            .filter(|key| path.join(key_segment(key)).matches(pattern))
+            .cloned()
+            .collect();
+        if !dropped.is_empty() {
+            let map = node.map_mut().expect("the node is a map");
+            dropped.iter().for_each(|key| drop(map.remove(key)));
+        }
+    } else if let Some(seq) = node.seq() {
+        let kept: Vec<bool> = (0..seq.len())
+            .map(|index| !path.join(Segment::Index(index)).matches(pattern))
+            .collect();
+        if kept.contains(&false) {
+            let mut kept = kept.into_iter();
+            let seq = node.seq_mut().expect("the node is a sequence");
+            seq.retain(|_| kept.next().unwrap_or(true));
+        }
+    }
+    // Remaining items are renumbered, as they would be once the context is written back.
+    node.update_items(|segment, item| drop_node(item, &path.join(segment), pattern));
+}
+
+/// Applies `function` to every string inside `value`.
+fn map_strings(value: &mut Value, function: &StringFunction) {
+    match unwrap_mut(value) {
+        Value::String(text) => *text = function.apply(text),
+        Value::Map(map) => map
+            .values_mut()
+            .for_each(|value| map_strings(value, function)),
+        Value::Seq(seq) => seq
+            .iter_mut()
+            .for_each(|value| map_strings(value, function)),
+        _ => {}
+    }
+}
+
+/// Applies `step` to `root`, adding to `secrets` the marks of the values it moves.
+/// Applies `function` to every string inside `node`, copying only the nodes it changes.
+fn map_node_strings(node: &mut Node, function: &StringFunction) {
+    match node {
+        Node::Leaf(value) => map_strings(value, function),
+        node => node.update_items(|_, item| map_node_strings(item, function)),
+    }
+}
+
+fn apply(root: &mut Node, step: &TransformStep, secrets: &mut Vec<String>) -> crate::Result<()> {
+    match step {
+        TransformStep::Rename { path, to } => {
+            let path: Path = path.parse()?;
+            let target = path
+                .parent()
+                .ok_or_else(|| Error::Transform("the root cannot be renamed".to_string()))?
+                .join(Segment::Key(to.clone()));
+            if let Some(value) = remove_at(root, path.segments()) {
+                insert_at(root, target.segments(), value)?;
+                carry(root, &path, &target, secrets);
+            }
+        }
+        TransformStep::Move { from, to } => {
+            let (from, to): (Path, Path) = (from.parse()?, to.parse()?);
+            if from.is_root() || to.is_root() {
+                return Err(Error::Transform("the root cannot be moved".to_string()));
+            }
+            if from != to && from.contains(&to) {
+                return Err(Error::Transform(format!(
+                    "cannot move '{}' into one of its children",
+                    from
+                )));
+            }
+            if let Some(value) = remove_at(root, from.segments()) {
+                insert_at(root, to.segments(), value)?;
+                carry(root, &from, &to, secrets);
+            }
+        }
+        TransformStep::Drop { pattern } => drop_node(root, &Path::root(), pattern),
+        TransformStep::MapStrings { path, function } => {
+            let path: Path = match path.is_empty() {
+                true => Path::root(),
+                false => path.parse()?,
+            };
+            match descend_mut(root, path.segments()) {
+                Some((node, [])) => map_node_strings(node, function),
+                // The value lies inside a leaf.
+                Some((node, rest)) => {
+                    if let Some(value) = lookup_mut(node.value_mut(), rest) {
+                        map_strings(value, function);
+                    }
+                }
+                None => {}
+            }
+        }
+        TransformStep::Flatten { separator } => {
+            let context = Context::from_node(std::mem::replace(root, Node::Leaf(Value::Unit)))?;
+            *root = context.flatten(separator).to_node();
+        }
+        TransformStep::Unflatten { separator } => {
+            let context = Context::from_node(std::mem::replace(root, Node::Leaf(Value::Unit)))?;
+            *root = context.unflatten(separator)?.to_node();
+        }
+    }
+    Ok(())
+}
+
+impl Context {
+    /// Applies the steps of a [`Transform`] in order.
+    ///
+    /// The transform is atomic: if a step fails, the `Context` is left untouched. Secret values
+    /// that are renamed or moved stay secret (see [`Context::mark_secret`]).
+    ///
+    /// `transform`: The steps to apply.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Path` variant if a path of a step is malformed or leads through a scalar.
+    /// - Returns an `Error::Transform` variant if a step cannot be applied, such as moving a value
+    ///   into one of its children.
+    ///
+    /// Example:
+    /// ```
+    /// let mut context = oxidex::Context::from_args(["--set", "a.b=1", "--set", "a.c[0]=x"]).unwrap();
+    ///
+    /// context.apply_transform(&oxidex::Transform::new().flatten("_")).unwrap();
+    /// assert_eq!(context.get("a_c_0").unwrap(), &serde_value::Value::String("x".to_string()));
+    ///
+    /// context.apply_transform(&oxidex::Transform::new().unflatten("_")).unwrap();
+    /// assert_eq!(context.get_path("a.c[0]").unwrap(), &serde_value::Value::String("x".to_string()));
+    /// ```
+    pub fn apply_transform(&mut self, transform: &Transform) -> crate::Result<()> {
+        self.audited(|context| {
+            let mut root = context.to_node();
+            let mut secrets = context.secrets.clone();
+            for step in &transform.steps {
+                apply(&mut root, step, &mut secrets)?;
+            }
+            context.inner = Context::from_node(root)?.inner;
+            context.secrets = secrets;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_in_sequences() {
+        let mut context = Context::from_args([
+            "--set",
+            "users[0].name=a",
+            "--set",
+            "users[0].token=x",
+            "--set",
+            "users[1].name=b",
+            "--set",
+            "users[2].name=c",
+        ])
+        .unwrap();
+        let transform = Transform::new().drop("users[1]").drop("users.*.token");
+        context.apply_transform(&transform).unwrap();
+
+        let expected =
+            Context::from_args(["--set", "users[0].name=a", "--set", "users[1].name=c"]).unwrap();
+        assert!(context.diff(&expected).is_empty());
+    }
+
+    #[test]
+    fn test_failed_transform_is_atomic() {
+        let mut context = Context::from_args(["--set", "a.b=1", "--set", "c=2"]).unwrap();
+        let transform = Transform::new().rename("c", "d").move_to("a", "a.b.x");
+        assert!(matches!(
+            context.apply_transform(&transform),
+            Err(Error::Transform(_))
+        ));
+        assert_eq!(context.get("c").unwrap(), &Value::U64(2));
+    }
+
+    #[test]
+    fn test_custom_function_is_not_serialized() {
+        let transform = Transform::new().map_strings("", StringFunction::custom(|s| s.repeat(2)));
+        assert!(serde_value::to_value(&transform).is_err());
+
+        let mut context = Context::from_args(["--set", "a=x"]).unwrap();
+        context.apply_transform(&transform).unwrap();
+        assert_eq!(context.get("a").unwrap(), &Value::String("xx".to_string()));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::entries::Node;
+use crate::{Context, Error};
+use serde_value::Value;
+use std::time::Duration;
+
+/// Renders a scalar value as plain text, as used by text-based formats (CSV, query strings).
+///
+/// Empty values and containers are rendered as an empty string.
+#[cfg(any(feature = "csv", feature = "query"))]
+pub(crate) fn scalar_to_string(value: &Value) -> String {
+    match value {
+        Value::Bool(v) => v.to_string(),
+        Value::U8(v) => v.to_string(),
+        Value::U16(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::U64(v) => v.to_string(),
+        Value::I8(v) => v.to_string(),
+        Value::I16(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        Value::Char(v) => v.to_string(),
+        Value::String(v) => v.clone(),
+        Value::Bytes(v) => String::from_utf8_lossy(v).into_owned(),
+        Value::Unit | Value::Option(None) => String::new(),
+        Value::Option(Some(v)) | Value::Newtype(v) => scalar_to_string(v),
+        Value::Map(_) | Value::Seq(_) => String::new(),
+    }
+}
+
+/// Parses a plain-text scalar the way YAML and JSON read it: `null`/`~`, booleans, integers and
+/// floats are typed, quoted strings are unquoted and anything else is kept as a string.
+pub(crate) fn parse_scalar(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return Value::Unit,
+        "true" | "True" | "TRUE" => return Value::Bool(true),
+        "false" | "False" | "FALSE" => return Value::Bool(false),
+        _ => {}
+    }
+    if let Some(quoted) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        return Value::String(quoted.replace("''", "'"));
+    }
+    if let Some(quoted) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        return Value::String(unescape(quoted));
+    }
+    if let Ok(v) = text.parse::<u64>() {
+        return Value::U64(v);
+    }
+    if let Ok(v) = text.parse::<i64>() {
+        return Value::I64(v);
+    }
+    match text.parse::<f64>() {
+        Ok(v) if text.bytes().any(|b| b.is_ascii_digit()) => Value::F64(v),
+        _ => Value::String(text.to_string()),
+    }
+}
+
+/// Resolves the backslash escapes of a double-quoted string.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    Some(c) => result.push(c),
+                    None => {
+                        result.push_str("\\u");
+                        result.push_str(&code);
+                    }
+                }
+            }
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Turns maps whose keys are exactly `"0".."n"` into sequences, recursively. Such maps are
+/// produced when rebuilding nested values from flat keys (`a[0]=x&a[1]=y`, `a.0`, `a.1`).
+pub(crate) fn normalize_sequences(value: Value) -> Value {
+    match value {
+        Value::Map(map) => {
+            let indexed =
+                (0..map.len()).all(|index| map.contains_key(&Value::String(index.to_string())));
+            match indexed && !map.is_empty() {
+                true => {
+                    let mut items: Vec<(usize, Value)> = map
+                        .into_iter()
+                        .filter_map(|(key, value)| match key {
+                            Value::String(key) => {
+                                Some((key.parse().ok()?, normalize_sequences(value)))
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    items.sort_by_key(|(index, _)| *index);
+                    Value::Seq(items.into_iter().map(|(_, value)| value).collect())
+                }
+                false => Value::Map(
+                    map.into_iter()
+                        .map(|(key, value)| (key, normalize_sequences(value)))
+                        .collect(),
+                ),
+            }
+        }
+        Value::Seq(seq) => Value::Seq(seq.into_iter().map(normalize_sequences).collect()),
+        value => value,
+    }
+}
+
+/// Parses a human-readable duration such as `30s`, `250ms` or `1h 30m`. A plain number is read as
+/// seconds. Supported units are `ns`, `us`, `ms`, `s`, `m`, `h` and `d`, with their long forms.
+pub(crate) fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if let Ok(seconds) = text.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let mut nanos: u64 = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let amount: f64 = rest[..split].parse().ok()?;
+        rest = rest[split..].trim_start();
+        let split = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit: u64 = match &rest[..split] {
+            "ns" | "nanos" => 1,
+            "us" | "micros" => 1_000,
+            "ms" | "millis" => 1_000_000,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1_000_000_000,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60_000_000_000,
+            "h" | "hr" | "hour" | "hours" => 3_600_000_000_000,
+            "d" | "day" | "days" => 86_400_000_000_000,
+            _ => return None,
+        };
+        // Float casts saturate, so out-of-range amounts are rejected before converting.
+        let part = (amount * unit as f64).round();
+        if !part.is_finite() || part >= u64::MAX as f64 {
+            return None;
+        }
+        nanos = nanos.checked_add(part as u64)?;
+        rest = rest[split..].trim_start_matches([' ', ',']);
+    }
+    (!text.is_empty()).then(|| Duration::from_nanos(nanos))
+}
+
+/// Returns the integer held by a value, whatever its width and signedness.
+pub(crate) fn as_integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::U8(v) => Some(*v as i128),
+        Value::U16(v) => Some(*v as i128),
+        Value::U32(v) => Some(*v as i128),
+        Value::U64(v) => Some(*v as i128),
+        Value::I8(v) => Some(*v as i128),
+        Value::I16(v) => Some(*v as i128),
+        Value::I32(v) => Some(*v as i128),
+        Value::I64(v) => Some(*v as i128),
+        _ => None,
+    }
+}
+
+/// Returns the number held by a value as a float.
+pub(crate) fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::F32(v) => Some(*v as f64),
+        Value::F64(v) => Some(*v),
+        value => as_integer(value).map(|v| v as f64),
+    }
+}
+
+/// Strips the `Option(Some(_))` and `Newtype(_)` wrappers around a value.
+pub(crate) fn unwrap(value: &Value) -> &Value {
+    match value {
+        Value::Option(Some(inner)) | Value::Newtype(inner) => unwrap(inner),
+        value => value,
+    }
+}
+
+/// Strips the `Option(Some(_))` and `Newtype(_)` wrappers around a mutable value.
+pub(crate) fn unwrap_mut(value: &mut Value) -> &mut Value {
+    match value {
+        Value::Option(Some(inner)) | Value::Newtype(inner) => unwrap_mut(inner),
+        value => value,
+    }
+}
+
+/// Returns `true` if the value is a null, whatever the format it was loaded from.
+pub(crate) fn is_null(value: &Value) -> bool {
+    matches!(unwrap(value), Value::Unit | Value::Option(None))
+}
+
+/// Compares two scalar values regardless of the format they were loaded from: numbers are
+/// compared by value whatever their type (`U64(30)` from JSON equals `I64(30)` from TOML),
+/// characters equal one-character strings and `Option`/`Newtype` wrappers are ignored.
+pub(crate) fn scalar_eq(a: &Value, b: &Value) -> bool {
+    let (a, b) = (unwrap(a), unwrap(b));
+    if let (Some(x), Some(y)) = (as_integer(a), as_integer(b)) {
+        return x == y;
+    }
+    if let (Some(x), Some(y)) = (as_float(a), as_float(b)) {
+        return x == y;
+    }
+    match (a, b) {
+        (Value::Char(c), Value::String(s)) | (Value::String(s), Value::Char(c)) => {
+            s.chars().eq(std::iter::once(*c))
+        }
+        (Value::Unit, Value::Option(None)) | (Value::Option(None), Value::Unit) => true,
+        (a, b) => a == b,
+    }
+}
+
+/// Compares two values deeply with the same rules as [`scalar_eq`].
+pub(crate) fn equivalent(a: &Value, b: &Value) -> bool {
+    match (unwrap(a), unwrap(b)) {
+        (Value::Map(x), Value::Map(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(key, value)| y.get(key).is_some_and(|other| equivalent(value, other)))
+        }
+        (Value::Seq(x), Value::Seq(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| equivalent(a, b))
+        }
+        (a, b) => scalar_eq(a, b),
+    }
+}
+
+/// Orders two scalar values of the same kind: numbers (whatever their type) and strings.
+///
+/// Returns `None` for values that cannot be ordered against each other.
+pub(crate) fn scalar_cmp(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    let (a, b) = (unwrap(a), unwrap(b));
+    if let (Some(x), Some(y)) = (as_integer(a), as_integer(b)) {
+        return Some(x.cmp(&y));
+    }
+    if let (Some(x), Some(y)) = (as_float(a), as_float(b)) {
+        return x.partial_cmp(&y);
+    }
+    match (a, b) {
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+/// Renders a value on a single line with a JSON-like syntax, for human-readable reports.
+pub(crate) fn to_inline(value: &Value) -> String {
+    match unwrap(value) {
+        Value::Unit | Value::Option(None) => "null".to_string(),
+        Value::String(v) => format!("{:?}", v),
+        Value::Char(v) => format!("{:?}", v.to_string()),
+        Value::Bytes(v) => format!("{:?}", v),
+        Value::Seq(seq) => format!(
+            "[{}]",
+            seq.iter().map(to_inline).collect::<Vec<_>>().join(", ")
+        ),
+        Value::Map(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!("{}: {}", to_inline(key), to_inline(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Bool(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        value => as_integer(value).map(|v| v.to_string()).unwrap_or_default(),
+    }
+}
+
+impl Context {
+    /// Returns a copy of the `Context` as a `Value::Map` with string keys.
+    pub(crate) fn to_value(&self) -> Value {
+        Value::Map(
+            self.inner
+                .nodes()
+                .map(|(key, node)| (Value::String(key.clone()), node.to_value()))
+                .collect(),
+        )
+    }
+
+    /// Creates a `Context` from a `Value::Map` whose keys are strings.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Generic` variant if the value is not a map or has a non-string key.
+    pub(crate) fn from_value(value: Value) -> crate::Result<Context> {
+        let Value::Map(map) = strip(value) else {
+            return Err(Error::Generic("a context must be a map".to_string()));
+        };
+        let mut context = Context::new();
+        for (key, value) in map {
+            match key {
+                Value::String(key) => context.inner.insert(key, value),
+                key => {
+                    return Err(Error::Generic(format!(
+                        "context key {} is not a string",
+                        to_inline(&key)
+                    )))
+                }
+            };
+        }
+        Ok(context)
+    }
+
+    /// Returns the `Context` as a map node with string keys, sharing its nodes.
+    pub(crate) fn to_node(&self) -> Node {
+        self.inner
+            .nodes()
+            .map(|(key, node)| (Value::String(key.clone()), node.clone()))
+            .collect()
+    }
+
+    /// Creates a `Context` from a map node whose keys are strings, sharing its nodes.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Generic` variant if the node is not a map or has a non-string key.
+    pub(crate) fn from_node(node: Node) -> crate::Result<Context> {
+        let Some(map) = node.map() else {
+            return match node {
+                Node::Leaf(value) => Context::from_value(value),
+                _ => Err(Error::Generic("a context must be a map".to_string())),
+            };
+        };
+        let mut context = Context::new();
+        for (key, node) in map {
+            match key {
+                Value::String(key) => context.inner.insert_node(key.clone(), node.clone()),
+                key => {
+                    return Err(Error::Generic(format!(
+                        "context key {} is not a string",
+                        to_inline(key)
+                    )))
+                }
+            };
+        }
+        Ok(context)
+    }
+}
+
+/// Strips the `Option(Some(_))` and `Newtype(_)` wrappers around an owned value.
+fn strip(value: Value) -> Value {
+    match value {
+        Value::Option(Some(inner)) | Value::Newtype(inner) => strip(*inner),
+        value => value,
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{Context, Diff, Error};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, Weak};
+
+/// Parses the contents of a source file into a `Context`.
+type Parser = dyn Fn(&Path, &str) -> crate::Result<Context> + Send + Sync;
+
+/// A callback invoked with the new `Context` and its changes.
+type Callback = dyn Fn(&Context, &Diff) + Send + Sync;
+
+impl From<notify::Error> for Error {
+    /// Converts a `notify::Error` (file watching error) into `Error::Watch(String)`.
+    fn from(err: notify::Error) -> Self {
+        Error::Watch(err.to_string())
+    }
+}
+
+struct Shared {
+    sources: Vec<PathBuf>,
+    parser: Box<Parser>,
+    current: RwLock<Arc<Context>>,
+    callbacks: Mutex<Vec<Arc<Callback>>>,
+    last_error: Mutex<Option<String>>,
+    /// Held from loading the sources until the callbacks return, so that concurrent reloads
+    /// publish their versions, and report them, in the order the sources were read.
+    reloading: Mutex<()>,
+}
+
+impl Shared {
+    /// Parses every source and merges them in order.
+    fn load(&self) -> crate::Result<Context> {
+        let mut context = Context::new();
+        for source in &self.sources {
+            let data = std::fs::read_to_string(source)?;
+            context.merge((self.parser)(source, &data).map_err(|err| {
+                Error::Watch(format!("cannot load '{}': {}", source.display(), err))
+            })?);
+        }
+        Ok(context)
+    }
+
+    fn reload(&self) -> crate::Result<bool> {
+        let _reloading = self
+            .reloading
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let context = match self.load() {
+            Ok(context) => context,
+            Err(err) => {
+                *self.last_error.lock().unwrap() = Some(err.to_string());
+                return Err(err);
+            }
+        };
+        *self.last_error.lock().unwrap() = None;
+
+        let context = Arc::new(context);
+        let diff = {
+            let mut current = self.current.write().unwrap();
+            let diff = current.diff(&context);
+            if diff.is_empty() {
+                return Ok(false);
+            }
+            *current = context.clone();
+            diff
+        };
+        // The callbacks run with the list unlocked, so that they can register other callbacks,
+        // and a panicking one does not poison the list.
+        let callbacks = self
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for callback in callbacks {
+            callback(&context, &diff);
+        }
+        Ok(true)
+    }
+}
+
+/// Parses a source file according to its extension.
+#[cfg_attr(
+    not(any(feature = "json", feature = "toml", feature = "yaml", feature = "xml")),
+    allow(unused_variables)
+)]
+fn parse_by_extension(path: &Path, data: &str) -> crate::Result<Context> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        #[cfg(feature = "json")]
+        "json" => Context::from_json(data),
+        #[cfg(feature = "toml")]
+        "toml" => Context::from_toml(data),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => Context::from_yaml(data),
+        #[cfg(feature = "xml")]
+        "xml" => Context::from_xml(data),
+        _ => Err(Error::Watch(format!(
+            "unsupported format for '{}'",
+            path.display()
+        ))),
+    }
+}
+
+/// A `Context` loaded from source files and reloaded whenever they change.
+///
+/// The sources are parsed and merged in order (later files override earlier ones), then the
+/// directories holding them are watched, so that files replaced by editors or deployment tools
+/// are picked up as well. On every change the sources are parsed again: the new `Context` is
+/// published atomically and the change callbacks are invoked with the differences, while a
+/// source failing to parse leaves the last good version in place.
+///
+/// Example:
+/// ```no_run
+/// let config = oxidex::WatchedContext::open(&["/etc/app/base.yaml", "/etc/app/local.yaml"]).unwrap();
+/// config.on_change(|_, diff| {
+///     for change in diff.changes() {
+///         println!("{} changed", change.path());
+///     }
+/// });
+///
+/// let port = config.current().get_path("server.port").cloned();
+/// ```
+pub struct WatchedContext {
+    shared: Arc<Shared>,
+    _watcher: RecommendedWatcher,
+}
+
+impl std::fmt::Debug for WatchedContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchedContext")
+            .field("sources", &self.shared.sources)
+            .field("current", &self.current())
+            .finish()
+    }
+}
+
+impl WatchedContext {
+    /// Loads and watches source files, parsed according to their extension (`json`, `toml`,
+    /// `yaml`/`yml` or `xml`, if the matching feature is enabled).
+    ///
+    /// `paths`: The source files, from the lowest to the highest precedence.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Io` variant if a source file cannot be read.
+    /// - Returns an `Error::Watch` variant if a source file cannot be parsed or watched.
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> crate::Result<WatchedContext> {
+        WatchedContext::open_with(paths, parse_by_extension)
+    }
+
+    /// Loads and watches source files, parsed by a custom function.
+    ///
+    /// `paths`: The source files, from the lowest to the highest precedence.
+    /// `parser`: The function parsing the contents of a source file, given its path.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Io` variant if a source file cannot be read.
+    /// - Returns an `Error::Watch` variant if a source file cannot be parsed or watched.
+    pub fn open_with<P, F>(paths: &[P], parser: F) -> crate::Result<WatchedContext>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Path, &str) -> crate::Result<Context> + Send + Sync + 'static,
+    {
+        let sources = paths
+            .iter()
+            .map(|path| std::path::absolute(path.as_ref()))
+            .collect::<std::io::Result<Vec<PathBuf>>>()?;
+        let mut shared = Shared {
+            sources,
+            parser: Box::new(parser),
+            current: RwLock::default(),
+            callbacks: Mutex::default(),
+            last_error: Mutex::default(),
+            reloading: Mutex::default(),
+        };
+        shared.current = RwLock::new(Arc::new(shared.load()?));
+        let shared = Arc::new(shared);
+
+        let weak: Weak<Shared> = Arc::downgrade(&shared);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let (Ok(event), Some(shared)) = (event, weak.upgrade()) else {
+                    return;
+                };
+                let relevant = !matches!(event.kind, EventKind::Access(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| shared.sources.iter().any(|source| source == path));
+                if relevant {
+                    // Errors are recorded by `reload` and surfaced by `last_error`.
+                    let _ = shared.reload();
+                }
+            })?;
+        let mut directories: Vec<&Path> = shared
+            .sources
+            .iter()
+            .filter_map(|source| source.parent())
+            .collect();
+        directories.sort();
+        directories.dedup();
+        for directory in directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(WatchedContext {
+            shared,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the last good version of the `Context`.
+    ///
+    /// The returned snapshot is never modified: a reload publishes a new `Context` instead.
+    pub fn current(&self) -> Arc<Context> {
+        self.shared.current.read().unwrap().clone()
+    }
+
+    /// Registers a callback invoked with the new `Context` and its differences with the previous
+    /// version, after every reload that changes it.
+    ///
+    /// Callbacks run on the watcher thread, or on the thread calling [`WatchedContext::reload`],
+    /// one reload at a time and in the order the versions were published. A callback must not
+    /// call [`WatchedContext::reload`] itself, which would wait for the callback to return.
+    ///
+    /// `callback`: The function to invoke.
+    pub fn on_change<F>(&self, callback: F)
+    where
+        F: Fn(&Context, &Diff) + Send + Sync + 'static,
+    {
+        self.shared
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(callback));
+    }
+
+    /// Parses and merges the sources again, without waiting for a change notification.
+    ///
+    /// Returns `true` if the `Context` changed. A reload running on the watcher thread is
+    /// waited for first, so that the most recent read of the sources is the one published.
+    ///
+    /// # Errors
+    /// - Returns an `Error::Io` variant if a source file cannot be read.
+    /// - Returns an `Error::Watch` variant if a source file cannot be parsed; the last good
+    ///   version is kept then.
+    pub fn reload(&self) -> crate::Result<bool> {
+        self.shared.reload()
+    }
+
+    /// Returns the error of the last reload, or `None` if it succeeded.
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.last_error.lock().unwrap().clone()
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("oxidex-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Replaces a file through a rename, so that the watcher never sees it half-written.
+    fn replace(path: &Path, contents: &str) {
+        let staging = path.with_extension("tmp");
+        std::fs::write(&staging, contents).unwrap();
+        std::fs::rename(staging, path).unwrap();
+    }
+
+    #[test]
+    fn test_reload_keeps_last_good_version() {
+        let directory = directory("reload");
+        let (base, local) = (directory.join("base.json"), directory.join("local.json"));
+        std::fs::write(&base, r#"{"db": {"host": "a", "port": 1}}"#).unwrap();
+        std::fs::write(&local, r#"{"db": {"host": "b"}}"#).unwrap();
+        let watched = WatchedContext::open(&[&base, &local]).unwrap();
+        let first = watched.current();
+        assert_eq!(
+            first.get_path("db.host").unwrap(),
+            &serde_value::Value::String("b".to_string())
+        );
+
+        replace(&local, "{");
+        assert!(matches!(watched.reload(), Err(Error::Watch(_))));
+        assert!(watched.last_error().unwrap().contains("local.json"));
+        assert!(Arc::ptr_eq(&first, &watched.current()));
+
+        replace(&local, r#"{"db": {"host": "b"}}"#);
+        assert!(!watched.reload().unwrap());
+        assert!(watched.last_error().is_none());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_changes_are_published_with_their_diff() {
+        let directory = directory("notify");
+        let path = directory.join("config.json");
+        std::fs::write(&path, r#"{"level": "info"}"#).unwrap();
+        let watched = WatchedContext::open(&[&path]).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        watched.on_change(move |context, diff| {
+            let paths: Vec<String> = diff
+                .changes()
+                .iter()
+                .map(|change| change.path().to_string())
+                .collect();
+            let level = context.get("level").cloned();
+            let _ = sender.lock().unwrap().send((paths, level));
+        });
+
+        replace(&path, r#"{"level": "debug"}"#);
+        let (paths, level) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(paths, vec!["level".to_string()]);
+        assert_eq!(level, Some(serde_value::Value::String("debug".to_string())));
+        assert_eq!(watched.current().get("level"), level.as_ref());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_reloads_are_published_in_order() {
+        let directory = directory("concurrent");
+        let path = directory.join("config.json");
+        std::fs::write(&path, r#"{"version": 0}"#).unwrap();
+        let watched = Arc::new(WatchedContext::open(&[&path]).unwrap());
+        let versions = Arc::new(Mutex::new(Vec::new()));
+        let published = versions.clone();
+        watched.on_change(move |context, _| {
+            published
+                .lock()
+                .unwrap()
+                .push(context.get("version").cloned());
+        });
+
+        let writer = Mutex::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        {
+                            let mut version = writer.lock().unwrap();
+                            *version += 1;
+                            replace(&path, &format!(r#"{{"version": {}}}"#, version));
+                        }
+                        let _ = watched.reload();
+                    }
+                });
+            }
+        });
+        let _ = watched.reload();
+        let versions = versions.lock().unwrap();
+        let numbers: Vec<u64> = versions
+            .iter()
+            .map(|version| match version {
+                Some(serde_value::Value::U64(version)) => *version,
+                other => panic!("unexpected version {:?}", other),
+            })
+            .collect();
+        assert!(
+            numbers.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            numbers
+        );
+        assert_eq!(numbers.last(), Some(&40));
+        assert_eq!(
+            watched.current().get("version"),
+            Some(&serde_value::Value::U64(40))
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_callbacks_can_register_callbacks() {
+        let directory = directory("nested");
+        let path = directory.join("config.json");
+        std::fs::write(&path, r#"{"level": "info"}"#).unwrap();
+        let watched = Arc::new(WatchedContext::open(&[&path]).unwrap());
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let weak = Arc::downgrade(&watched);
+        watched.on_change(move |_, _| {
+            if let Some(watched) = weak.upgrade() {
+                watched.on_change(|_, _| {});
+            }
+            let _ = sender.lock().unwrap().send(());
+        });
+
+        replace(&path, r#"{"level": "debug"}"#);
+        let _ = watched.reload();
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(watched.shared.callbacks.lock().unwrap().len() >= 2);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
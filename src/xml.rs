@@ -52,6 +52,7 @@ impl Context {
                 .into_iter()
                 .map(|(key, value)| (key, serde_value::Value::deserialize(value).unwrap()))
                 .collect(),
+            ..Context::default()
         })
     }
 
@@ -71,4 +72,13 @@ impl Context {
     pub fn to_xml(&self) -> crate::Result<String> {
         Ok(serde_xml_rs::to_string(&self)?)
     }
+
+    /// Serializes the `Context` into an XML string, with secret values masked (see
+    /// [`Context::mark_secret`]).
+    ///
+    /// # Errors
+    /// - Returns an `Error::Xml` variant if serialization fails.
+    pub fn to_xml_redacted(&self) -> crate::Result<String> {
+        self.redacted().to_xml()
+    }
 }
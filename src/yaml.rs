@@ -47,6 +47,7 @@ impl Context {
                 .into_iter()
                 .map(|(key, value)| (key, serde_value::Value::deserialize(value).unwrap()))
                 .collect(),
+            ..Context::default()
         })
     }
 
@@ -66,4 +67,13 @@ impl Context {
     pub fn to_yaml(&self) -> crate::Result<String> {
         Ok(serde_yaml::to_string(&self)?)
     }
+
+    /// Serializes the `Context` into a YAML string, with secret values masked (see
+    /// [`Context::mark_secret`]).
+    ///
+    /// # Errors
+    /// - Returns an `Error::Yaml` variant if serialization fails.
+    pub fn to_yaml_redacted(&self) -> crate::Result<String> {
+        self.redacted().to_yaml()
+    }
 }